@@ -1,17 +1,46 @@
-use super::cube::gen_scramble;
+use super::chat::{
+    self,
+    history::{self, History, Record},
+    identity::{self, AddressBook, BlockList},
+    outbox::{Delivery, Outbox, Outgoing},
+    wire::{self, Body, Envelope},
+    ChatCommand,
+};
+use super::commands::copy_to_clipboard;
+use super::cube::{
+    bld::{self, LetterScheme, Memo},
+    format_alg, gen_scramble_with, parse_alg,
+    reconstruction::{Reconstruction, Step},
+    CubeState, Face, Move,
+};
 use super::global_rt::global_rt;
+use super::handlers::{
+    command::{self, expand_home, Candidates, Cmd, CommandLine, ExportFormat, RoomCmd, SessionCmd},
+    config::{CompleteConfig, NetworkingConfig},
+    event::{EventSender, NetworkEvent, TaskResult},
+    keymap::{Context, Keymap},
+    theme::{Theme, BUILTIN},
+};
+use super::local_git::LocalGit;
+use super::trainer::{library::case_key, Trainer};
+use super::ui::layout;
 use ordered_float::*;
+use rand::{rngs::StdRng, SeedableRng};
 use std::{
     error::Error,
     fmt::{self, Formatter},
     fs,
-    path::Path,
-    time::{Duration, Instant},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
+use tokio::sync::mpsc;
 use tui::{
-    style::{Color, Modifier, Style},
+    layout::Rect,
+    style::{Modifier, Style},
     widgets::{ListState, TableState},
 };
+use tui_input::Input;
 
 pub struct Route {
     pub screen: Screen,
@@ -43,6 +72,10 @@ impl Route {
 pub enum Screen {
     Default,
     Help,
+    Library,
+    Replay,
+    Memo,
+    Solve,
 }
 
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
@@ -57,39 +90,215 @@ pub enum ActiveBlock {
     Main,
 }
 
-#[derive(Clone, Copy)]
+/// Names of the files in `dir` with extension `ext`, or without one when
+/// `ext` is empty, sorted
+fn file_stems(dir: &Path, ext: &str) -> Vec<String> {
+    let mut stems = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_file() && p.extension().map_or("", |e| e.to_str().unwrap_or("")) == ext)
+        .filter_map(|p| p.file_stem().and_then(|s| s.to_str()).map(str::to_string))
+        .collect::<Vec<String>>();
+    stems.sort();
+    stems
+}
+
+/// Penalty of a solve as in the WCA regulations
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Penalty {
+    #[default]
+    None,
+    PlusTwo,
+    Dnf,
+}
+
+impl fmt::Display for Penalty {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        f.write_str(match self {
+            Penalty::None => "ok",
+            Penalty::PlusTwo => "+2",
+            Penalty::Dnf => "dnf",
+        })
+    }
+}
+
+impl FromStr for Penalty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "ok" => Ok(Penalty::None),
+            "+2" | "2" => Ok(Penalty::PlusTwo),
+            "dnf" => Ok(Penalty::Dnf),
+            _ => Err(format!("{:?} is not a penalty, expected ok, +2 or dnf", s)),
+        }
+    }
+}
+
+/// A time or average for display, where infinity stands for a DNF
+pub fn format_time(value: f64, decimals: usize) -> String {
+    match value.is_finite() {
+        true => format!("{:.*}", decimals, value),
+        false => "DNF".to_string(),
+    }
+}
+
+/// `secs` since the Unix epoch as a UTC date and time, e.g. `2024-05-01 18:30`
+pub fn format_date(secs: u64) -> String {
+    // Days to a civil date, after Howard Hinnant's `civil_from_days`
+    let days = (secs / 86400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    let minutes = secs % 86400 / 60;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+#[derive(Clone)]
 pub struct Time {
     pub time: f64,
+    pub penalty: Penalty,
+    /// Seconds since the Unix epoch when the solve was recorded
+    pub date: Option<u64>,
+    pub comment: String,
     pub ao5: Option<f64>,
     pub ao12: Option<f64>,
+    /// Move count of a solve done on the virtual cube
+    pub moves: Option<usize>,
+    pub scramble: Option<String>,
+    /// Cumulative times at the end of each reconstruction step
+    pub splits: Vec<f64>,
+    pub reconstruction: Option<Reconstruction>,
 }
 
 impl Time {
     pub fn from(time: f64) -> Self {
         Self {
             time,
+            penalty: Penalty::None,
+            date: None,
+            comment: String::new(),
             ao5: None,
             ao12: None,
+            moves: None,
+            scramble: None,
+            splits: vec![],
+            reconstruction: None,
+        }
+    }
+
+    /// The time counted in statistics: with two seconds added for a +2, or
+    /// infinity for a DNF so it sorts as the worst
+    pub fn result(&self) -> f64 {
+        match self.penalty {
+            Penalty::None => self.time,
+            Penalty::PlusTwo => self.time + 2.0,
+            Penalty::Dnf => f64::INFINITY,
+        }
+    }
+
+    /// The result as shown in tables, e.g. `12.34`, `14.34+` or `DNF`
+    pub fn label(&self, decimals: usize) -> String {
+        match self.penalty {
+            Penalty::PlusTwo => format!("{}+", format_time(self.result(), decimals)),
+            _ => format_time(self.result(), decimals),
+        }
+    }
+
+    /// Turns per second, only known for virtual cube solves
+    pub fn tps(&self) -> Option<f64> {
+        match self.moves {
+            Some(n) if self.time > 0.0 => Some(n as f64 / self.time),
+            _ => None,
         }
     }
 
-    pub fn gen_stats(&mut self, times: &Vec<Time>) {
-        let mut tr = times.clone();
-        tr.push(*self);
-        tr.reverse();
+    /// One line of the times file: the time, followed by tab separated
+    /// `key=value` fields for whatever else is known about the solve
+    pub fn record(&self) -> String {
+        let mut fields = vec![self.time.to_string()];
+        if self.penalty != Penalty::None {
+            fields.push(format!("penalty={}", self.penalty));
+        }
+        if let Some(date) = self.date {
+            fields.push(format!("date={}", date));
+        }
+        if !self.comment.is_empty() {
+            fields.push(format!("comment={}", self.comment));
+        }
+        if let Some(n) = self.moves {
+            fields.push(format!("moves={}", n));
+        }
+        if let Some(scramble) = &self.scramble {
+            fields.push(format!("scramble={}", scramble.trim()));
+        }
+        if !self.splits.is_empty() {
+            let splits = self
+                .splits
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<String>>();
+            fields.push(format!("splits={}", splits.join(",")));
+        }
+        if let Some(recon) = &self.reconstruction {
+            fields.push(format!("recon={}", recon));
+        }
+        fields.join("\t")
+    }
 
-        self.ao5 = if tr.len() >= 5 {
-            let set = &tr[0..5];
-            Some(Times::calc_aon(set))
+    pub fn parse_record(line: &str) -> Option<Self> {
+        let mut fields = line.split('\t');
+        // Older records are `time [moves]` separated by spaces
+        let mut first = fields.next()?.split_whitespace();
+        let mut time = Time::from(first.next()?.parse::<f64>().ok()?);
+        time.moves = first.next().and_then(|v| v.parse::<usize>().ok());
+
+        for field in fields {
+            match field.split_once('=') {
+                Some(("penalty", v)) => time.penalty = v.parse().unwrap_or_default(),
+                Some(("date", v)) => time.date = v.parse::<u64>().ok(),
+                Some(("comment", v)) => time.comment = v.to_string(),
+                Some(("moves", v)) => time.moves = v.parse::<usize>().ok(),
+                Some(("scramble", v)) => time.scramble = Some(v.to_string()),
+                Some(("splits", v)) => {
+                    time.splits = v.split(',').filter_map(|v| v.parse::<f64>().ok()).collect()
+                }
+                Some(("recon", v)) => time.reconstruction = v.parse::<Reconstruction>().ok(),
+                _ => (),
+            }
+        }
+        Some(time)
+    }
+
+    pub fn gen_stats(&mut self, times: &[Time]) {
+        let len = times.len() + 1;
+        let recent = || std::iter::once(&*self).chain(times.iter().rev());
+
+        let ao5 = if len >= 5 {
+            Some(Times::calc_aon(recent().take(5)))
         } else {
             None
         };
-        self.ao12 = if tr.len() >= 12 {
-            let set = &tr[0..12];
-            Some(Times::calc_aon(set))
+        let ao12 = if len >= 12 {
+            Some(Times::calc_aon(recent().take(12)))
         } else {
             None
         };
+        self.ao5 = ao5;
+        self.ao12 = ao12;
     }
 }
 
@@ -109,6 +318,8 @@ pub struct Times {
     pub ao1k: Option<f64>,
     pub rollingavg: Option<f64>,
     pub sum: f64,
+    /// Solves that count towards `sum`, i.e. all but DNFs
+    pub finished: usize,
     pub worst: f64,
 }
 
@@ -123,33 +334,47 @@ impl Times {
             ao1k: None,
             rollingavg: None,
             sum: 0.0,
+            finished: 0,
             worst: 0.0,
         }
     }
 
     pub fn insert(&mut self, time: Time) {
+        let t = time.result();
+        if t.is_finite() {
+            Times::update_best(&mut self.pbsingle, Some(t));
+        }
+        Times::update_best(&mut self.pbao5, time.ao5.filter(|v| v.is_finite()));
+        Times::update_best(&mut self.pbao12, time.ao12.filter(|v| v.is_finite()));
         self.times.push(time);
-        Times::update_best(&mut self.pbsingle, Some(time.time));
-        Times::update_best(&mut self.pbao5, time.ao5);
-        Times::update_best(&mut self.pbao12, time.ao12);
 
         if self.times.len() >= 100 {
-            let mut tr = self.times.clone();
-            tr.reverse();
-            self.ao100 = Some(Times::calc_aon(&tr[0..100]));
+            self.ao100 = Some(Times::calc_aon(self.times.iter().rev().take(100)));
             if self.times.len() >= 1000 {
-                self.ao1k = Some(Times::calc_aon(&tr[0..1000]));
+                self.ao1k = Some(Times::calc_aon(self.times.iter().rev().take(1000)));
             }
         }
 
-        self.sum += time.time;
+        // DNFs are left out of the mean
+        if !t.is_finite() {
+            return;
+        }
+        self.sum += t;
+        self.finished += 1;
+        self.rollingavg = Some(self.sum / self.finished as f64);
+        if t > self.worst {
+            self.worst = t;
+        }
+    }
 
-        self.rollingavg = match self.rollingavg {
-            Some(_) => Some(self.sum / self.times.len() as f64),
-            None => Some(time.time),
-        };
-        if time.time > self.worst {
-            self.worst = time.time;
+    /// Work out every average and best again, e.g. after a solve was edited
+    /// or deleted
+    pub fn recalc(&mut self) {
+        let times = std::mem::take(&mut self.times);
+        *self = Times::new();
+        for mut time in times {
+            time.gen_stats(&self.times);
+            self.insert(time);
         }
     }
 
@@ -169,17 +394,16 @@ impl Times {
         }
     }
 
-    fn calc_aon(set: &[Time]) -> f64 {
+    fn calc_aon<'t>(set: impl Iterator<Item = &'t Time>) -> f64 {
         let mut t = set
-            .iter()
-            .take(set.len())
-            .map(|v| OrderedFloat(v.time))
+            .map(|v| OrderedFloat(v.result()))
             .collect::<Vec<OrderedFloat<f64>>>();
         // Remove best and worst time
         t.sort();
         t.pop();
         t.remove(0);
 
+        // More than one DNF leaves infinity in, making the average a DNF
         let mut sum = OrderedFloat(0.0);
         let _ = t.iter().map(|v| sum += v).collect::<Vec<()>>();
         sum.into_inner() / t.len() as f64
@@ -203,7 +427,7 @@ impl<'a> Iterator for TimesIterator<'a> {
     type Item = Time;
     fn next(&mut self) -> Option<Self::Item> {
         self.curr += 1;
-        self.times.get(self.curr).copied()
+        self.times.get(self.curr).cloned()
     }
 }
 
@@ -256,11 +480,12 @@ impl CubeTimer {
         }
     }
 
-    pub fn text(&self) -> String {
+    pub fn text(&self, decimals: usize) -> String {
         match self.starttime {
             Some(v) => format!("{:.1}", v.elapsed().as_secs_f64()),
             None => format!(
-                "{:.3}",
+                "{:.*}",
+                decimals,
                 self.lasttime
                     .unwrap_or(Duration::from_secs(0))
                     .as_secs_f64()
@@ -269,25 +494,480 @@ impl CubeTimer {
     }
 }
 
-#[derive(Copy, Clone)]
+/// csTimer style keyboard mapping for turning the virtual cube
+pub fn virtual_key_move(c: char) -> Option<Move> {
+    let (face, prime) = match c {
+        'j' => (Face::U, false),
+        'f' => (Face::U, true),
+        'i' => (Face::R, false),
+        'k' => (Face::R, true),
+        'h' => (Face::F, false),
+        'g' => (Face::F, true),
+        'w' => (Face::B, false),
+        'o' => (Face::B, true),
+        'd' => (Face::L, false),
+        'e' => (Face::L, true),
+        's' => (Face::D, false),
+        'l' => (Face::D, true),
+        '5' | '6' => (Face::M, false),
+        'x' | '.' => (Face::M, true),
+        't' | 'y' => (Face::X, false),
+        'b' | 'n' => (Face::X, true),
+        ';' => (Face::Y, false),
+        'a' => (Face::Y, true),
+        'p' => (Face::Z, false),
+        'q' => (Face::Z, true),
+        'u' | 'm' | 'v' | 'r' => {
+            let face = match c {
+                'u' | 'm' => Face::R,
+                _ => Face::L,
+            };
+            return Some(Move {
+                face,
+                wide: true,
+                prime: matches!(c, 'm' | 'r'),
+                half_turn: false,
+            });
+        }
+        _ => return None,
+    };
+    Some(Move::new(face, prime, false))
+}
+
+/// The scrambled cube shown in the main panel and solved with keyboard turns
+pub struct VirtualCube {
+    pub state: CubeState,
+    pub moves: Vec<Move>,
+}
+
+impl VirtualCube {
+    pub fn new(scramble: &str) -> Self {
+        let mut cube = Self {
+            state: CubeState::solved(),
+            moves: vec![],
+        };
+        cube.reset(scramble);
+        cube
+    }
+
+    pub fn reset(&mut self, scramble: &str) {
+        self.state = CubeState::from_moves(&parse_alg(scramble).unwrap_or_default());
+        self.moves.clear();
+    }
+
+    /// Moves counted for the solve record, whole cube rotations are free
+    pub fn move_count(&self) -> usize {
+        self.moves.iter().filter(|m| !m.face.is_rotation()).count()
+    }
+}
+
+/// Steps through the reconstruction of a solve on the cube renderer
+pub struct Replay {
+    /// Index of the solve in `Times::times`
+    pub solve: usize,
+    pub start: CubeState,
+    pub moves: Vec<Move>,
+    /// Number of moves applied to `state`
+    pub pos: usize,
+    pub state: CubeState,
+    pub playing: bool,
+    pub editing: bool,
+    pub input: Input,
+    pub error: Option<String>,
+    last_step: Instant,
+}
+
+impl Replay {
+    const STEP_INTERVAL: Duration = Duration::from_millis(500);
+
+    pub fn new(solve: usize, time: &Time) -> Self {
+        let moves = time
+            .reconstruction
+            .as_ref()
+            .map(Reconstruction::moves)
+            .unwrap_or_default();
+        // Without a stored scramble, assume the reconstruction solves the cube
+        let start = match time.scramble.as_deref().map(parse_alg) {
+            Some(Ok(scramble)) => CubeState::from_moves(&scramble),
+            _ => CubeState::from_moves(
+                &moves
+                    .iter()
+                    .rev()
+                    .map(|m| m.inverse())
+                    .collect::<Vec<Move>>(),
+            ),
+        };
+        Self {
+            solve,
+            state: start.clone(),
+            start,
+            moves,
+            pos: 0,
+            playing: false,
+            editing: false,
+            input: Input::default(),
+            error: None,
+            last_step: Instant::now(),
+        }
+    }
+
+    pub fn step_forward(&mut self) {
+        match self.moves.get(self.pos) {
+            Some(m) => {
+                self.state.apply(*m);
+                self.pos += 1;
+            }
+            None => self.playing = false,
+        }
+    }
+
+    pub fn step_back(&mut self) {
+        if self.pos > 0 {
+            self.pos -= 1;
+            self.state = self.start.clone();
+            self.state.apply_all(&self.moves[..self.pos]);
+        }
+    }
+
+    pub fn restart(&mut self) {
+        self.pos = 0;
+        self.state = self.start.clone();
+    }
+
+    pub fn toggle_play(&mut self) {
+        if self.pos == self.moves.len() {
+            self.restart();
+        }
+        self.playing = !self.playing;
+        self.last_step = Instant::now();
+    }
+
+    fn tick(&mut self) {
+        if self.playing && self.last_step.elapsed() >= Replay::STEP_INTERVAL {
+            self.step_forward();
+            self.last_step = Instant::now();
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum LibraryEdit {
+    AddAlg,
+    Notes,
+}
+
+/// Solves shown by the relay chart, changed with the mouse wheel and drags
+#[derive(Default)]
+pub struct ChartView {
+    /// Number of solves shown, 0 for all of them
+    pub zoom: usize,
+    /// Solves between the newest one and the right edge
+    pub pan: usize,
+    /// Column of the last drag event while panning
+    pub drag: Option<u16>,
+}
+
+impl ChartView {
+    /// Range of solve indices in view out of `len` solves
+    pub fn window(&self, len: usize) -> (usize, usize) {
+        let shown = match self.zoom {
+            0 => len,
+            zoom => zoom.min(len),
+        };
+        let end = len - self.pan.min(len - shown);
+        (end - shown, end)
+    }
+
+    pub fn zoom(&mut self, len: usize, zoom_in: bool) {
+        let (start, end) = self.window(len);
+        let shown = match zoom_in {
+            true => ((end - start) * 3 / 4).max(5),
+            false => (end - start) * 4 / 3 + 1,
+        };
+        self.zoom = if shown >= len { 0 } else { shown };
+        self.pan = self.pan.min(len - shown.min(len));
+    }
+
+    /// Move the view `solves` back in time, or forward when negative
+    pub fn pan_by(&mut self, len: usize, solves: isize) {
+        let (start, end) = self.window(len);
+        let max = len - (end - start);
+        self.pan = (self.pan as isize + solves).clamp(0, max as isize) as usize;
+    }
+}
+
+/// What the edit line of the solve popup changes
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum SolveEdit {
+    Comment,
+    Penalty,
+}
+
+/// The solve detail popup over the times table
+pub struct SolveView {
+    /// Index of the solve in `Times::times`
+    pub solve: usize,
+    pub edit: Option<SolveEdit>,
+    pub input: Input,
+    /// Delete was pressed once and waits for a second press
+    pub confirm_delete: bool,
+    pub message: Option<String>,
+}
+
+/// Selection and edit line of the algorithm library screen
+pub struct LibraryView {
+    pub state: TableState,
+    pub edit: Option<LibraryEdit>,
+    pub input: Input,
+}
+
+/// Rooms of sessions other than the default one are this plus the name
+pub const SESSION_ROOMS: &str = "cube-tui/";
+
+/// Messages read from the history at a time, on opening a room or
+/// conversation and on scrolling back
+const HISTORY_PAGE: usize = 100;
+
+/// Opening a room or conversation reads none of its history older than
+/// this many milliseconds
+const RECENT: u64 = 7 * 24 * 60 * 60 * 1000;
+
+/// A gossipsub topic joined as a chat room
+pub struct Room {
+    pub name: String,
+    /// Messages and status lines, oldest first
+    pub log: Vec<NetworkEvent>,
+    /// Lines scrolled back from the newest
+    pub scroll: usize,
+    /// Messages that arrived while the room was not shown
+    pub unread: usize,
+    /// Position in the history of the oldest message in the log
+    pub first: usize,
+}
+
+impl Room {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            log: vec![],
+            scroll: 0,
+            unread: 0,
+            first: 0,
+        }
+    }
+
+    fn push(&mut self, event: NetworkEvent) {
+        self.log.push(event);
+        // Keep what was scrolled back to in view
+        if self.scroll > 0 {
+            self.scroll += 1;
+        }
+    }
+}
+
+/// The gossipsub chat of the Gnostr panel
+pub struct ChatView {
+    /// Never empty, the last room cannot be left
+    pub rooms: Vec<Room>,
+    /// Index of the room shown and written to
+    pub current: usize,
+    pub input: Input,
+    /// Our peer id once the swarm is up
+    pub local: Option<String>,
+    pub peers: Vec<String>,
+    pub listening: Vec<String>,
+    /// Commands to the chat task, `None` while it is not running
+    pub sender: Option<mpsc::Sender<ChatCommand>>,
+}
+
+impl ChatView {
+    /// In the default room and the other rooms of the config
+    pub fn new(config: &NetworkingConfig) -> Self {
+        let mut chat = Self {
+            rooms: vec![Room::new(&config.topic)],
+            current: 0,
+            input: Input::default(),
+            local: None,
+            peers: vec![],
+            listening: vec![],
+            sender: None,
+        };
+        for room in &config.rooms {
+            chat.join(room);
+        }
+        chat
+    }
+
+    pub fn room(&self) -> &Room {
+        &self.rooms[self.current]
+    }
+
+    pub fn room_mut(&mut self) -> &mut Room {
+        &mut self.rooms[self.current]
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.rooms.iter().position(|r| r.name == name)
+    }
+
+    /// Index of room `name`, adding it if it is new
+    fn join(&mut self, name: &str) -> usize {
+        self.find(name).unwrap_or_else(|| {
+            self.rooms.push(Room::new(name));
+            self.rooms.len() - 1
+        })
+    }
+
+    /// Show room `index`, which counts as reading it
+    pub fn show(&mut self, index: usize) {
+        self.current = index;
+        self.room_mut().unread = 0;
+    }
+
+    /// Show the next room, or the previous one when `forward` is false
+    pub fn cycle(&mut self, forward: bool) {
+        let len = self.rooms.len();
+        self.show(match forward {
+            true => (self.current + 1) % len,
+            false => (self.current + len - 1) % len,
+        });
+    }
+
+    /// A status line in the room shown
+    fn status(&mut self, text: String) {
+        self.room_mut().push(NetworkEvent::Status(text));
+    }
+
+    /// Connection status for the panel title
+    pub fn connection(&self) -> String {
+        match (&self.sender, &self.local) {
+            (None, _) => "offline".to_string(),
+            (Some(_), None) => "starting".to_string(),
+            (Some(_), Some(_)) if self.listening.is_empty() => "not listening".to_string(),
+            (Some(_), Some(_)) => match self.peers.len() {
+                1 => "1 peer".to_string(),
+                n => format!("{} peers", n),
+            },
+        }
+    }
+}
+
+/// A message of a direct conversation
+pub struct DirectLine {
+    pub id: u64,
+    /// We sent it rather than the peer
+    pub mine: bool,
+    /// Where a message of ours sent this run is, `None` for the rest
+    pub delivery: Option<Delivery>,
+    pub text: String,
+}
+
+/// Direct messages with one peer
+pub struct Conversation {
+    pub peer: String,
+    /// Oldest first
+    pub log: Vec<DirectLine>,
+    /// Lines scrolled back from the newest
+    pub scroll: usize,
+    /// Messages that arrived while the conversation was not shown
+    pub unread: usize,
+    /// Position in the history of the oldest message in the log
+    pub first: usize,
+}
+
+/// The direct conversations of the Direct panel
+#[derive(Default)]
+pub struct DirectView {
+    pub conversations: Vec<Conversation>,
+    /// Index of the conversation shown and written to
+    pub current: usize,
+    pub input: Input,
+}
+
+impl DirectView {
+    pub fn conversation(&self) -> Option<&Conversation> {
+        self.conversations.get(self.current)
+    }
+
+    pub fn conversation_mut(&mut self) -> Option<&mut Conversation> {
+        self.conversations.get_mut(self.current)
+    }
+
+    pub fn find(&self, peer: &str) -> Option<usize> {
+        self.conversations.iter().position(|c| c.peer == peer)
+    }
+
+    /// Index of the conversation with `peer`, starting it if it is new
+    fn open(&mut self, peer: &str) -> usize {
+        self.find(peer).unwrap_or_else(|| {
+            self.conversations.push(Conversation {
+                peer: peer.to_string(),
+                log: vec![],
+                scroll: 0,
+                unread: 0,
+                first: 0,
+            });
+            self.conversations.len() - 1
+        })
+    }
+
+    /// Show conversation `index`, which counts as reading it
+    pub fn show(&mut self, index: usize) {
+        self.current = index;
+        if let Some(conversation) = self.conversation_mut() {
+            conversation.unread = 0;
+        }
+    }
+
+    /// Show the next conversation, or the previous one when `forward` is
+    /// false
+    pub fn cycle(&mut self, forward: bool) {
+        let len = self.conversations.len();
+        if len > 0 {
+            self.show(match forward {
+                true => (self.current + 1) % len,
+                false => (self.current + len - 1) % len,
+            });
+        }
+    }
+
+    /// The message `id` of ours, wherever it is
+    fn line_mut(&mut self, peer: &str, id: u64) -> Option<&mut DirectLine> {
+        let index = self.find(peer)?;
+        self.conversations[index]
+            .log
+            .iter_mut()
+            .rev()
+            .find(|l| l.id == id && l.mine)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
 pub enum Tool {
     Gnostr,
+    Direct,
     Relay,
     Commit,
+    Virtual,
+    Trainer,
 }
 
 impl fmt::Display for Tool {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
         let text = match self {
             Tool::Gnostr => "Gnostr",
+            Tool::Direct => "Direct",
             Tool::Relay => "Relay",
             Tool::Commit => "Commit",
+            Tool::Virtual => "Virtual",
+            Tool::Trainer => "Trainer",
         };
         write!(f, "{}", text)?;
         Ok(())
     }
 }
 
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
 pub enum Dir {
     Up,
     Down,
@@ -295,46 +975,131 @@ pub enum Dir {
     Right,
 }
 
-pub struct App<'a> {
+/// Name of the session kept in the configured times file
+pub const DEFAULT_SESSION: &str = "default";
+
+pub struct App {
+    pub config: CompleteConfig,
+    pub keymap: Keymap,
+    pub theme: Theme,
     pub tick_rate: Duration,
     pub timer: CubeTimer,
+    /// The newest solve was just timed and is highlighted until the next start
+    pub new_result: bool,
     pub route: Route,
-    pub path: &'a Path,
-    pub pos: (usize, usize),
+    /// Times file of the current session
+    pub path: PathBuf,
+    /// Times file of the `default` session, the configured one
+    pub default_path: PathBuf,
+    pub session: String,
     pub times: Times,
     pub times_state: TableState,
     pub tools_state: ListState,
-    layout: Vec<Vec<ActiveBlock>>,
+    /// Panels of the default screen where they were last drawn, which is
+    /// what navigation and mouse clicks go by
+    pub panels: Vec<(ActiveBlock, Rect)>,
+    /// Rects mouse clicks are tested against, the most specific last
+    pub hit_rects: Vec<(ActiveBlock, Rect)>,
+    /// First rows shown by the times table and tools list
+    pub times_offset: usize,
+    pub tools_offset: usize,
+    pub chart: ChartView,
     pub scramble: String,
+    /// Scrambles come from here, seeded with `:seed` to repeat them
+    pub scramble_rng: StdRng,
+    pub virtual_cube: VirtualCube,
+    pub replay: Option<Replay>,
+    pub trainer: Trainer,
+    pub library_view: LibraryView,
+    pub letter_scheme: LetterScheme,
+    /// Blindfolded memo of a solve's scramble, shown on demand
+    pub memo: Option<(usize, Memo)>,
+    pub solve_view: Option<SolveView>,
+    pub command_line: CommandLine,
+    /// Multiaddrs `:connect` asked for before the chat task started
+    pub dial: Vec<String>,
+    /// Feeds background task results back into the event stream
+    pub events: Option<EventSender>,
+    pub chat: ChatView,
+    pub direct: DirectView,
+    /// Messages of every room and conversation, kept across runs
+    pub history: History,
+    /// Text of the last `:search` and the position of its match
+    pub search: Option<(String, usize)>,
+    /// Nicknames shown instead of peer ids
+    pub address_book: AddressBook,
+    /// Peers whose messages are dropped
+    pub blocked: BlockList,
+    pub git_log: Option<Result<String, String>>,
     pub tools: Vec<Tool>,
     pub active_tool: Tool,
 }
 
-impl<'a> App<'a> {
-    pub fn new(tick_rate: Duration, path: &'a Path) -> Result<Self, Box<dyn Error>> {
+impl App {
+    pub fn new(config: CompleteConfig, path: &Path) -> Result<Self, Box<dyn Error>> {
         // Setup state
         let mut tools_state = ListState::default();
         tools_state.select(Some(0));
 
+        let mut scramble_rng = StdRng::from_entropy();
+        let scramble = gen_scramble_with(&mut scramble_rng);
+        let chat = ChatView::new(&config.networking);
+
         // Construct app
-        Ok(App {
-            tick_rate,
+        let mut app = App {
+            tick_rate: Duration::from_secs(config.terminal.tick_delay),
+            letter_scheme: config.bld.letter_scheme()?,
+            keymap: Keymap::new(&config.keymap)?,
+            theme: Theme::load(&config.frontend.theme)?,
+            config,
             timer: CubeTimer::default(),
+            new_result: false,
             route: Route::default(),
-            path,
+            path: path.to_path_buf(),
+            default_path: path.to_path_buf(),
+            session: DEFAULT_SESSION.to_string(),
             times: Times::new(),
             times_state: TableState::default(),
             tools_state,
-            //this plus default active block determine initial navigation move
-            pos: (0, 0),
-            layout: vec![//effects nav order
-                vec![ActiveBlock::Tools, ActiveBlock::Timer, ActiveBlock::Times],
-                vec![ActiveBlock::Stats, ActiveBlock::Scramble, ActiveBlock::Main],
+            panels: vec![],
+            hit_rects: vec![],
+            times_offset: 0,
+            tools_offset: 0,
+            chart: ChartView::default(),
+            virtual_cube: VirtualCube::new(&scramble),
+            scramble,
+            scramble_rng,
+            replay: None,
+            trainer: Trainer::new(&path.with_file_name("")),
+            library_view: LibraryView {
+                state: TableState::default(),
+                edit: None,
+                input: Input::default(),
+            },
+            memo: None,
+            solve_view: None,
+            command_line: CommandLine::new(&path.with_file_name("command_history")),
+            dial: vec![],
+            events: None,
+            chat,
+            direct: DirectView::default(),
+            history: History::new(&path.with_file_name("history")),
+            search: None,
+            address_book: AddressBook::load(&path.with_file_name("peers")),
+            blocked: BlockList::load(&path.with_file_name("blocked")),
+            git_log: None,
+            tools: vec![
+                Tool::Gnostr,
+                Tool::Direct,
+                Tool::Relay,
+                Tool::Commit,
+                Tool::Virtual,
+                Tool::Trainer,
             ],
-            scramble: gen_scramble(),
-            tools: vec![Tool::Gnostr, Tool::Relay, Tool::Commit],
             active_tool: Tool::Gnostr,
-        })
+        };
+        app.load_history();
+        Ok(app)
     }
 
     pub fn load_times(&mut self) -> Result<(), Box<dyn Error>> {
@@ -347,17 +1112,12 @@ impl<'a> App<'a> {
             Ok(_) => (),
         };
 
-        let mut times: Vec<Time> = fs::read_to_string(&self.path)?
+        self.times = Times::new();
+        self.times.times = fs::read_to_string(&self.path)?
             .lines()
-            .filter_map(|v| v.parse::<f64>().ok())
-            .map(|v| Time::from(v))
+            .filter_map(Time::parse_record)
             .collect();
-
-        self.times = Times::new();
-        for time in &mut times {
-            time.gen_stats(&self.times.times);
-            self.times.insert(*time);
-        }
+        self.times.recalc();
         Ok(())
     }
 
@@ -366,7 +1126,7 @@ impl<'a> App<'a> {
             .times
             .times
             .iter()
-            .flat_map(|v| format!("{}\n", v.to_string()).bytes().collect::<Vec<u8>>())
+            .flat_map(|v| format!("{}\n", v.record()).bytes().collect::<Vec<u8>>())
             .collect();
         fs::write(&self.path, write_data)?;
         Ok(())
@@ -376,6 +1136,29 @@ impl<'a> App<'a> {
         match self.route.screen {
             Screen::Default => self.route.esc(),
             Screen::Help => self.route.screen = Screen::Default,
+            Screen::Library => match self.library_view.edit {
+                Some(_) => self.library_view.edit = None,
+                None => self.route.screen = Screen::Default,
+            },
+            Screen::Replay => match &mut self.replay {
+                Some(replay) if replay.editing => replay.editing = false,
+                _ => {
+                    self.replay = None;
+                    self.route.screen = Screen::Default;
+                    self.update_tick_rate();
+                }
+            },
+            Screen::Memo => {
+                self.memo = None;
+                self.route.screen = Screen::Default;
+            }
+            Screen::Solve => match &mut self.solve_view {
+                Some(view) if view.edit.is_some() => view.edit = None,
+                _ => {
+                    self.solve_view = None;
+                    self.route.screen = Screen::Default;
+                }
+            },
         }
     }
 
@@ -383,25 +1166,128 @@ impl<'a> App<'a> {
         self.route.screen = Screen::Help;
     }
 
+    pub fn library(&mut self) {
+        self.route.screen = Screen::Library;
+        if self.library_view.state.selected().is_none() {
+            self.library_view.state.select(Some(0));
+        }
+    }
+
+    /// Library key and case set algorithm of every case in the trainer's set
+    pub fn library_cases(&self) -> Vec<(String, String)> {
+        match self.trainer.current_set() {
+            Some(set) => set
+                .cases
+                .iter()
+                .map(|c| (case_key(&set.name, &c.name), format_alg(&c.alg)))
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    pub fn library_mv(&mut self, dir: Dir) {
+        let len = self.library_cases().len();
+        if len == 0 {
+            return;
+        }
+        let i = self.library_view.state.selected().unwrap_or(0);
+        let i = match dir {
+            Dir::Down => (i + 1) % len,
+            Dir::Up => (i + len - 1) % len,
+            _ => i,
+        };
+        self.library_view.state.select(Some(i));
+    }
+
+    fn selected_library_case(&self) -> Option<(String, String)> {
+        self.library_cases()
+            .into_iter()
+            .nth(self.library_view.state.selected()?)
+    }
+
+    /// Make the next algorithm of the selected case its main one
+    pub fn library_cycle_main(&mut self) {
+        if let Some((key, alg)) = self.selected_library_case() {
+            let entry = self.trainer.library.entry(&key, &alg);
+            entry.main = (entry.main + 1) % entry.algs.len().max(1);
+            self.write_library();
+        }
+    }
+
+    pub fn library_delete_alg(&mut self) {
+        if let Some((key, alg)) = self.selected_library_case() {
+            let entry = self.trainer.library.entry(&key, &alg);
+            if entry.algs.len() > 1 {
+                entry.algs.remove(entry.main);
+                entry.main = entry.main.min(entry.algs.len() - 1);
+                self.write_library();
+            }
+        }
+    }
+
+    pub fn library_edit(&mut self, edit: LibraryEdit) {
+        let Some((key, alg)) = self.selected_library_case() else {
+            return;
+        };
+        let text = match edit {
+            LibraryEdit::AddAlg => String::new(),
+            LibraryEdit::Notes => self.trainer.library.entry(&key, &alg).notes.clone(),
+        };
+        self.library_view.input = Input::new(text);
+        self.library_view.edit = Some(edit);
+    }
+
+    pub fn submit_library_edit(&mut self) {
+        let (Some(edit), Some((key, alg))) = (self.library_view.edit, self.selected_library_case())
+        else {
+            return;
+        };
+        let value = self.library_view.input.value().trim().to_string();
+        let entry = self.trainer.library.entry(&key, &alg);
+        match edit {
+            LibraryEdit::AddAlg => {
+                if parse_alg(&value).is_err() || value.is_empty() {
+                    return;
+                }
+                entry.algs.push(value);
+            }
+            LibraryEdit::Notes => entry.notes = value,
+        }
+        self.library_view.edit = None;
+        self.write_library();
+    }
+
+    fn write_library(&mut self) {
+        if let Err(e) = self.trainer.library.write() {
+            self.trainer.error = Some(e.to_string());
+        }
+    }
+
     pub fn get_border_style_from_id(&self, id: ActiveBlock) -> Style {
-        let style = Style::default();
+        let border = &self.theme.border;
         if id == self.route.active_block {
-            return style.fg(Color::Magenta).add_modifier(Modifier::BOLD | Modifier::ITALIC);
+            return border
+                .active
+                .fg()
+                .add_modifier(Modifier::BOLD | Modifier::ITALIC);
         } else if id == self.route.selected_block {
-            return style.fg(Color::Magenta);//.add_modifier(Modifier::BOLD);
+            return border.selected.fg(); //.add_modifier(Modifier::BOLD);
         } else {
-            return style.fg(Color::Gray);
+            return border.normal.fg();
         }
     }
 
     pub fn get_highlight_style_from_id(&self, id: ActiveBlock) -> Style {
-        let style = Style::default();
+        let highlight = &self.theme.highlight;
         if id == self.route.active_block {
-            return style.fg(Color::Magenta);
+            return highlight.active.fg();
         } else if id == self.route.selected_block {
-            return style.fg(Color::Magenta).add_modifier(Modifier::BOLD | Modifier::ITALIC)
+            return highlight
+                .selected
+                .fg()
+                .add_modifier(Modifier::BOLD | Modifier::ITALIC);
         } else {
-            return style.fg(Color::Magenta);
+            return highlight.active.fg();
         }
     }
 
@@ -412,6 +1298,19 @@ impl<'a> App<'a> {
         }
     }
 
+    /// Select a block and make it active, e.g. when clicked
+    pub fn focus(&mut self, block: ActiveBlock) {
+        self.route.selected_block = block;
+        self.route.active_block = block;
+    }
+
+    /// Select the solve in row `row` of the times table, newest first
+    pub fn select_time(&mut self, row: usize) {
+        if row < self.times.times.len() {
+            self.times_state.select(Some(row));
+        }
+    }
+
     pub fn mv(&mut self, dir: Dir) {
         match self.route.active_block {
             ActiveBlock::Times => match dir {
@@ -439,43 +1338,12 @@ impl<'a> App<'a> {
                 }
             },
             _ => {
-                match dir {
-                    Dir::Up => self.mv_up(),
-                    Dir::Down => self.mv_down(),
-                    Dir::Right => self.mv_right(),
-                    Dir::Left => self.mv_left(),
+                if let Some(block) = layout::adjacent(&self.panels, self.route.selected_block, dir)
+                {
+                    self.route.selected_block = block;
                 }
                 self.route.active_block = ActiveBlock::Home;
-                self.route.selected_block = self.layout[self.pos.0][self.pos.1];
-            }
-        }
-    }
-
-    fn mv_up(&mut self) {
-        if (self.pos.1) as i32 - 1 >= 0 {
-            self.pos.1 -= 1;
-        }
-    }
-
-    fn mv_down(&mut self) {
-        if self.pos.1 + 1 < self.layout[self.pos.0].len() {
-            self.pos.1 += 1;
-        }
-    }
-
-    fn mv_right(&mut self) {
-        if self.layout.len() > self.pos.0 + 1 {
-            let max = self.layout[self.pos.0 + 1].len() - 1;
-            if self.pos.1 + 1 > max {
-                self.pos.1 = max;
             }
-            self.pos.0 += 1;
-        }
-    }
-
-    pub fn mv_left(&mut self) {
-        if (self.pos.0) as i32 - 1 >= 0 {
-            self.pos.0 -= 1;
         }
     }
 
@@ -497,7 +1365,7 @@ impl<'a> App<'a> {
         self.times_state.select(Some(i));
     }
 
-    fn previous_time(&mut self) {
+    pub fn previous_time(&mut self) {
         let len = self.times.times.len();
         if len == 0 {
             return;
@@ -516,25 +1384,34 @@ impl<'a> App<'a> {
     }
 
     fn del_time(&mut self) {
-        match self.times_state.selected() {
-            Some(v) => {
-                // Edge cases (literally)
-                let len = self.times.times.len();
-                if len <= 0 || v >= len {
-                    return;
-                }
-                self.times.times.remove(len - v - 1);
-                // Go up one if selection fell off
-                if v == self.times.times.len() {
-                    self.previous_time();
-                }
-            }
-            None => (),
-        };
+        if let Some(i) = self.selected_solve() {
+            self.delete_solve(i);
+        }
     }
 
-    pub fn next_tool(&mut self) {
-        let i = match self.tools_state.selected() {
+    fn delete_solve(&mut self, i: usize) {
+        if i >= self.times.times.len() {
+            return;
+        }
+        self.times.times.remove(i);
+        self.times.recalc();
+        self.new_result = false;
+        // Go up one if selection fell off
+        if self.times_state.selected() == Some(self.times.times.len()) {
+            self.previous_time();
+        }
+        self.autosave();
+    }
+
+    fn autosave(&mut self) {
+        if self.config.storage.autosave {
+            // Quitting writes the times again and reports any error
+            let _ = self.write_times();
+        }
+    }
+
+    pub fn next_tool(&mut self) {
+        let i = match self.tools_state.selected() {
             Some(i) => {
                 if i >= self.tools.len() - 1 {
                     0
@@ -544,11 +1421,10 @@ impl<'a> App<'a> {
             }
             None => 0,
         };
-        self.tools_state.select(Some(i));
-        self.active_tool = self.tools[self.tools_state.selected().unwrap_or(0)];
+        self.select_tool(i);
     }
 
-    fn previous_tool(&mut self) {
+    pub fn previous_tool(&mut self) {
         let i = match self.tools_state.selected() {
             Some(i) => {
                 if i == 0 {
@@ -559,15 +1435,1237 @@ impl<'a> App<'a> {
             }
             None => 0,
         };
+        self.select_tool(i);
+    }
+
+    pub fn select_tool(&mut self, i: usize) {
+        if i >= self.tools.len() {
+            return;
+        }
         self.tools_state.select(Some(i));
-        self.active_tool = self.tools[self.tools_state.selected().unwrap_or(0)];
+        self.active_tool = self.tools[i];
+        self.tool_selected();
+    }
+
+    // Refresh what the selected tool shows without blocking the timer
+    fn tool_selected(&mut self) {
+        if let (Tool::Commit, Some(events)) = (self.active_tool, &self.events) {
+            events.spawn_task("git log", || {
+                LocalGit::new("git")
+                    .args(&["log", "--oneline", "-n", "20"])
+                    .execute_and_get_stdout()
+                    .map_err(|e| e.to_string())
+            });
+        }
     }
 
     pub fn new_scramble(&mut self) {
-        self.scramble = gen_scramble();
+        self.scramble = gen_scramble_with(&mut self.scramble_rng);
+        self.virtual_cube.reset(&self.scramble);
+    }
+
+    pub fn timer_press(&mut self) {
+        match self.timer.space_press() {
+            Some(t) => self.record_time(t),
+            None => self.new_result = false,
+        }
+        self.update_tick_rate();
+    }
+
+    fn record_time(&mut self, mut t: Time) {
+        t.scramble = Some(self.scramble.trim().to_string());
+        t.date = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .ok();
+        t.gen_stats(&self.times.times);
+        self.times.insert(t);
+        self.new_result = true;
+        self.new_scramble();
+        self.autosave();
+    }
+
+    fn update_tick_rate(&mut self) {
+        let playing = self.replay.as_ref().is_some_and(|r| r.playing);
+        self.tick_rate = if self.timer.on || playing || self.trainer.running() {
+            Duration::from_millis(self.config.timer.refresh_ms)
+        } else {
+            Duration::from_secs(self.config.terminal.tick_delay)
+        };
+    }
+
+    /// Index into `times.times` of the row selected in the Times table
+    pub fn selected_solve(&self) -> Option<usize> {
+        let len = self.times.times.len();
+        match self.times_state.selected() {
+            Some(v) if v < len => Some(len - v - 1),
+            _ => None,
+        }
+    }
+
+    pub fn open_replay(&mut self) {
+        if self.route.active_block != ActiveBlock::Times {
+            return;
+        }
+        if let Some(i) = self.selected_solve() {
+            self.replay = Some(Replay::new(i, &self.times.times[i]));
+            self.route.screen = Screen::Replay;
+        }
+    }
+
+    /// Show the details of the selected solve in a popup
+    pub fn open_solve(&mut self) {
+        if self.route.active_block != ActiveBlock::Times {
+            return;
+        }
+        if let Some(solve) = self.selected_solve() {
+            self.solve_view = Some(SolveView {
+                solve,
+                edit: None,
+                input: Input::default(),
+                confirm_delete: false,
+                message: None,
+            });
+            self.route.screen = Screen::Solve;
+        }
+    }
+
+    pub fn solve_edit(&mut self, edit: SolveEdit) {
+        let Some(view) = &mut self.solve_view else {
+            return;
+        };
+        let time = &self.times.times[view.solve];
+        let text = match edit {
+            SolveEdit::Comment => time.comment.clone(),
+            SolveEdit::Penalty => time.penalty.to_string(),
+        };
+        view.input = Input::new(text);
+        view.edit = Some(edit);
+        view.message = None;
+    }
+
+    pub fn submit_solve_edit(&mut self) {
+        let Some(view) = &mut self.solve_view else {
+            return;
+        };
+        let Some(edit) = view.edit else {
+            return;
+        };
+        let time = &mut self.times.times[view.solve];
+        match edit {
+            // Tabs and newlines would break the times file
+            SolveEdit::Comment => {
+                time.comment = view
+                    .input
+                    .value()
+                    .split_whitespace()
+                    .collect::<Vec<&str>>()
+                    .join(" ")
+            }
+            SolveEdit::Penalty => match view.input.value().parse() {
+                Ok(penalty) => time.penalty = penalty,
+                Err(e) => {
+                    view.message = Some(e);
+                    return;
+                }
+            },
+        }
+        view.edit = None;
+        if edit == SolveEdit::Penalty {
+            self.times.recalc();
+        }
+        self.autosave();
+    }
+
+    /// Put the scramble of the solve in the popup on the clipboard, through
+    /// the terminal so it also works over ssh
+    pub fn copy_scramble(&mut self) {
+        let Some(view) = &mut self.solve_view else {
+            return;
+        };
+        let Some(scramble) = &self.times.times[view.solve].scramble else {
+            view.message = Some("the solve has no scramble".to_string());
+            return;
+        };
+        view.message = Some(match copy_to_clipboard(scramble) {
+            Ok(()) => "copied the scramble".to_string(),
+            Err(e) => format!("could not copy the scramble: {}", e),
+        });
+    }
+
+    /// Delete the solve in the popup on the second press
+    pub fn solve_delete(&mut self) {
+        let Some(view) = &mut self.solve_view else {
+            return;
+        };
+        if !view.confirm_delete {
+            view.confirm_delete = true;
+            return;
+        }
+        let solve = view.solve;
+        self.solve_view = None;
+        self.route.screen = Screen::Default;
+        self.delete_solve(solve);
+    }
+
+    /// Run a command of the `:` command line or the CLI, returning what it
+    /// did or why it failed
+    pub fn run_command(&mut self, cmd: Cmd) -> Result<String, String> {
+        match cmd {
+            Cmd::Session(SessionCmd::New { name }) => {
+                let path = self.session_path(&name)?;
+                if path.exists() {
+                    return Err(format!("session {} already exists", name));
+                }
+                self.switch_session(&name, path)?;
+                Ok(format!("started session {}", name))
+            }
+            Cmd::Session(SessionCmd::Switch { name }) => {
+                let path = self.session_path(&name)?;
+                if !path.exists() {
+                    return Err(format!("no session {}, start one with session new", name));
+                }
+                self.switch_session(&name, path)?;
+                Ok(format!(
+                    "switched to session {} with {} solves",
+                    name,
+                    self.times.times.len()
+                ))
+            }
+            Cmd::Session(SessionCmd::List) => Ok(self
+                .sessions()
+                .into_iter()
+                .map(|s| match s == self.session {
+                    true => format!("*{}", s),
+                    false => s,
+                })
+                .collect::<Vec<String>>()
+                .join(" ")),
+            Cmd::Export { format, path } => {
+                let path = expand_home(&path);
+                fs::write(&path, self.export(format))
+                    .map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+                Ok(format!(
+                    "wrote {} solves to {}",
+                    self.times.times.len(),
+                    path.display()
+                ))
+            }
+            Cmd::Seed { seed } => {
+                self.scramble_rng = StdRng::seed_from_u64(seed);
+                self.new_scramble();
+                Ok(format!("scrambles seeded with {}", seed))
+            }
+            Cmd::Goto { solve } => {
+                let len = self.times.times.len();
+                if solve == 0 || solve > len {
+                    return Err(format!("no solve {}, there are {}", solve, len));
+                }
+                self.focus(ActiveBlock::Times);
+                self.select_time(len - solve);
+                Ok(format!("selected solve {}", solve))
+            }
+            Cmd::Theme { name } => {
+                self.theme = Theme::load(&name)?;
+                self.config.frontend.theme = name.clone();
+                Ok(format!("theme {}", name))
+            }
+            Cmd::Connect { addr } => {
+                let multiaddr = addr
+                    .parse::<libp2p::Multiaddr>()
+                    .map_err(|e| format!("{:?} is not a multiaddr: {}", addr, e))?;
+                match &self.chat.sender {
+                    Some(sender) => {
+                        sender
+                            .try_send(ChatCommand::Dial(multiaddr))
+                            .map_err(|e| e.to_string())?;
+                        Ok(format!("dialing {}", addr))
+                    }
+                    None => {
+                        self.dial.push(addr.clone());
+                        Ok(format!("will dial {} once the network is up", addr))
+                    }
+                }
+            }
+            Cmd::Addrs => {
+                let Some(local) = &self.chat.local else {
+                    return Err("the network is not up".to_string());
+                };
+                let addrs = self
+                    .chat
+                    .listening
+                    .iter()
+                    .map(|addr| format!("{}/p2p/{}", addr, local))
+                    .collect::<Vec<String>>();
+                if addrs.is_empty() {
+                    return Err("not listening yet".to_string());
+                }
+                // The message line is too short for more than one
+                for addr in &addrs {
+                    self.chat.status(addr.clone());
+                }
+                Ok(format!(
+                    "listed {} addresses in #{}, e.g. {}",
+                    addrs.len(),
+                    self.chat.room().name,
+                    addrs[0]
+                ))
+            }
+            Cmd::Room(RoomCmd::Join { room }) => self.join_room(&room),
+            Cmd::Room(RoomCmd::Leave { room }) => self.leave_room(&room),
+            Cmd::Room(RoomCmd::Switch { room }) => match self.chat.find(&room) {
+                Some(index) => {
+                    self.chat.show(index);
+                    Ok(format!("in #{}", room))
+                }
+                None => Err(format!("not in #{}, join it first", room)),
+            },
+            Cmd::Room(RoomCmd::List) => Ok(self
+                .chat
+                .rooms
+                .iter()
+                .enumerate()
+                .map(|(i, r)| match (i == self.chat.current, r.unread) {
+                    (true, _) => format!("*#{}", r.name),
+                    (false, 0) => format!("#{}", r.name),
+                    (false, unread) => format!("#{} ({})", r.name, unread),
+                })
+                .collect::<Vec<String>>()
+                .join(" ")),
+            Cmd::Dm { peer, text } => self.open_direct(&peer, &text),
+            Cmd::Search { text } => self.search(&text.join(" ")),
+            Cmd::Block { peer: None } if self.blocked.peers.is_empty() => {
+                Ok("no peers blocked".to_string())
+            }
+            Cmd::Block { peer: None } => Ok(self
+                .blocked
+                .peers
+                .iter()
+                .map(|p| self.address_book.name(p))
+                .collect::<Vec<String>>()
+                .join(" ")),
+            Cmd::Block { peer: Some(peer) } => self.block(&peer, true),
+            Cmd::Unblock { peer } => self.block(&peer, false),
+            Cmd::Nick { peer, name } => {
+                let peer = self.find_peer(&peer)?;
+                let name = name.unwrap_or_default();
+                if name.chars().any(char::is_control) {
+                    return Err(format!("{:?} is not a nickname", name));
+                }
+                self.address_book
+                    .set(&peer, &name)
+                    .map_err(|e| format!("could not save the nickname: {}", e))?;
+                Ok(match name.as_str() {
+                    "" => format!("forgot the nickname of {}", peer),
+                    name => format!("{} is now {}", peer, name),
+                })
+            }
+        }
+    }
+
+    /// Show the conversation with `peer`, first sending `text` if any
+    fn open_direct(&mut self, peer: &str, text: &[String]) -> Result<String, String> {
+        let peer = self.find_peer(peer)?;
+        let index = self.direct.open(&peer);
+        self.direct.show(index);
+        if let Some(i) = self.tools.iter().position(|t| *t == Tool::Direct) {
+            self.select_tool(i);
+        }
+        self.focus(ActiveBlock::Main);
+        let name = self.address_book.name(&peer);
+        if text.is_empty() {
+            return Ok(format!("talking to {}", name));
+        }
+        self.send_direct(text.join(" "))?;
+        Ok(format!("sent to {}", name))
+    }
+
+    /// Block the peer called `name`, or unblock it, here and in the swarm
+    fn block(&mut self, name: &str, blocked: bool) -> Result<String, String> {
+        let peer = self.find_peer(name)?;
+        self.blocked
+            .set(&peer, blocked)
+            .map_err(|e| format!("could not save the block list: {}", e))?;
+        // Only peers seen on the network are blocked by name, so they parse
+        if let (Some(sender), Ok(id)) = (&self.chat.sender, peer.parse()) {
+            let command = match blocked {
+                true => ChatCommand::Block(id),
+                false => ChatCommand::Unblock(id),
+            };
+            sender.try_send(command).map_err(|e| e.to_string())?;
+        }
+        let name = self.address_book.name(&peer);
+        Ok(match blocked {
+            true => format!("blocked {}", name),
+            false => format!("unblocked {}", name),
+        })
+    }
+
+    /// Join room `room` if need be and show it
+    pub fn join_room(&mut self, room: &str) -> Result<String, String> {
+        if room.is_empty() || room.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return Err(format!("{:?} is not a room name", room));
+        }
+        if self.chat.find(room).is_none() {
+            if let Some(sender) = &self.chat.sender {
+                sender
+                    .try_send(ChatCommand::Join(room.to_string()))
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        let index = self.open_room(room);
+        self.chat.show(index);
+        Ok(format!("in #{}", room))
+    }
+
+    pub fn leave_room(&mut self, room: &str) -> Result<String, String> {
+        let Some(index) = self.chat.find(room) else {
+            return Err(format!("not in #{}", room));
+        };
+        if self.chat.rooms.len() == 1 {
+            return Err(format!("#{} is the last room", room));
+        }
+        if let Some(sender) = &self.chat.sender {
+            sender
+                .try_send(ChatCommand::Leave(room.to_string()))
+                .map_err(|e| e.to_string())?;
+        }
+        self.chat.rooms.remove(index);
+        if self.chat.current >= index && self.chat.current > 0 {
+            self.chat.current -= 1;
+        }
+        self.chat.show(self.chat.current);
+        Ok(format!("left #{}", room))
+    }
+
+    /// The peer id `name` stands for: a full id, a nickname, or the end of
+    /// the id of exactly one peer we know
+    fn find_peer(&self, name: &str) -> Result<String, String> {
+        if name.parse::<libp2p::PeerId>().is_ok() {
+            return Ok(name.to_string());
+        }
+        if let Some(peer) = self.address_book.peer(name) {
+            return Ok(peer);
+        }
+        let mut known = self
+            .chat
+            .peers
+            .iter()
+            .chain(self.address_book.names.keys())
+            .chain(&self.blocked.peers)
+            .filter(|p| p.ends_with(name.trim_start_matches('…')))
+            .collect::<Vec<&String>>();
+        known.sort();
+        known.dedup();
+        match known.as_slice() {
+            [peer] => Ok(peer.to_string()),
+            [] => Err(format!("no peer {}", name)),
+            _ => Err(format!("{} could be {} peers", name, known.len())),
+        }
+    }
+
+    pub fn submit_command(&mut self) {
+        let line = self.command_line.submit();
+        if line.is_empty() {
+            return;
+        }
+        let result = command::parse(&line).and_then(|cmd| self.run_command(cmd));
+        self.command_line.message = Some(result);
+    }
+
+    /// Tab completion of the command line, listing the matches when the
+    /// word can still end several ways
+    pub fn complete_command(&mut self) {
+        let themes_dir = Theme::path("theme").with_file_name("");
+        let mut themes = BUILTIN
+            .iter()
+            .map(|t| t.to_string())
+            .collect::<Vec<String>>();
+        themes.extend(file_stems(&themes_dir, "toml"));
+        let candidates = Candidates {
+            themes,
+            sessions: self.sessions(),
+            rooms: self.chat.rooms.iter().map(|r| r.name.clone()).collect(),
+        };
+        let (line, matches) = command::complete(self.command_line.input.value(), &candidates);
+        self.command_line.input = Input::new(line);
+        self.command_line.message = match matches.len() {
+            0 | 1 => None,
+            _ => Some(Ok(matches.join(" "))),
+        };
+    }
+
+    fn sessions_dir(&self) -> PathBuf {
+        self.default_path.with_file_name("sessions")
+    }
+
+    /// Every session, the default one first
+    pub fn sessions(&self) -> Vec<String> {
+        let mut sessions = vec![DEFAULT_SESSION.to_string()];
+        sessions.extend(file_stems(&self.sessions_dir(), ""));
+        sessions
+    }
+
+    fn session_path(&self, name: &str) -> Result<PathBuf, String> {
+        if name == DEFAULT_SESSION {
+            return Ok(self.default_path.clone());
+        }
+        if !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(format!(
+                "{:?} is not a session name, use letters, digits, - and _",
+                name
+            ));
+        }
+        Ok(self.sessions_dir().join(name))
+    }
+
+    /// Save the solves of this session and load those of `name`
+    fn switch_session(&mut self, name: &str, path: PathBuf) -> Result<(), String> {
+        self.write_times().map_err(|e| e.to_string())?;
+        let previous = std::mem::replace(&mut self.path, path);
+        if let Err(e) = self.load_times() {
+            self.path = previous;
+            self.load_times().map_err(|e| e.to_string())?;
+            return Err(format!("could not switch to session {}: {}", name, e));
+        }
+        self.session = name.to_string();
+        if self.config.networking.session_rooms {
+            let room = match name {
+                DEFAULT_SESSION => self.config.networking.topic.clone(),
+                name => format!("{}{}", SESSION_ROOMS, name),
+            };
+            // The session is switched either way
+            let _ = self.join_room(&room);
+        }
+        self.times_state.select(None);
+        self.times_offset = 0;
+        self.new_result = false;
+        Ok(())
+    }
+
+    /// The solves of this session as `format`
+    pub fn export(&self, format: ExportFormat) -> String {
+        match format {
+            ExportFormat::Txt => self.times.times.iter().map(|t| t.record() + "\n").collect(),
+            ExportFormat::Csv => {
+                let field = |text: String| match text.contains([',', '"']) {
+                    true => format!("\"{}\"", text.replace('"', "\"\"")),
+                    false => text,
+                };
+                let average = |v: Option<f64>| v.map(|v| format_time(v, 3)).unwrap_or_default();
+                let mut csv =
+                    "solve,time,penalty,result,ao5,ao12,date,scramble,comment\n".to_string();
+                for (i, t) in self.times.times.iter().enumerate() {
+                    let row = [
+                        (i + 1).to_string(),
+                        t.time.to_string(),
+                        t.penalty.to_string(),
+                        format_time(t.result(), 3),
+                        average(t.ao5),
+                        average(t.ao12),
+                        t.date.map(format_date).unwrap_or_default(),
+                        field(t.scramble.clone().unwrap_or_default()),
+                        field(t.comment.clone()),
+                    ];
+                    csv += &(row.join(",") + "\n");
+                }
+                csv
+            }
+        }
+    }
+
+    /// Show the blindfolded memo of the selected solve's scramble, or of the
+    /// last solve outside the Times block. Never the scramble being solved.
+    pub fn open_memo(&mut self) {
+        if self.timer.on {
+            return;
+        }
+        let solve = match self.route.active_block {
+            ActiveBlock::Times => self.selected_solve(),
+            _ => self.times.times.len().checked_sub(1),
+        };
+        let Some(solve) = solve else {
+            return;
+        };
+        let scramble = self.times.times[solve].scramble.as_deref().unwrap_or("");
+        let Ok(moves) = parse_alg(scramble) else {
+            return;
+        };
+        if moves.is_empty() {
+            return;
+        }
+        let memo = bld::memo(&CubeState::from_moves(&moves), &self.letter_scheme);
+        self.memo = Some((solve, memo));
+        self.route.screen = Screen::Memo;
+    }
+
+    pub fn toggle_replay(&mut self) {
+        if let Some(replay) = &mut self.replay {
+            replay.toggle_play();
+        }
+        self.update_tick_rate();
+    }
+
+    pub fn edit_reconstruction(&mut self) {
+        if let Some(replay) = &mut self.replay {
+            let time = &self.times.times[replay.solve];
+            let text = time
+                .reconstruction
+                .as_ref()
+                .map(|r| r.with_splits(&time.splits))
+                .unwrap_or_default();
+            replay.input = Input::new(text);
+            replay.editing = true;
+            replay.playing = false;
+        }
+        self.update_tick_rate();
+    }
+
+    /// Parse the reconstruction being edited and attach it and its splits
+    /// to the solve. Without `@` times the splits it had are kept, unless
+    /// the editor showed them, in which case taking them out clears them.
+    pub fn submit_reconstruction(&mut self) {
+        let Some(replay) = &mut self.replay else {
+            return;
+        };
+        match Reconstruction::parse_with_splits(replay.input.value()) {
+            Ok((recon, splits)) => {
+                let solve = replay.solve;
+                let time = &mut self.times.times[solve];
+                // `with_splits` shows them when there is one per step
+                let shown = !time.splits.is_empty()
+                    && time
+                        .reconstruction
+                        .as_ref()
+                        .is_some_and(|r| r.steps.len() == time.splits.len());
+                if !splits.is_empty() || shown {
+                    time.splits = splits;
+                }
+                time.reconstruction = match recon.is_empty() {
+                    true => None,
+                    false => Some(recon),
+                };
+                self.replay = Some(Replay::new(solve, time));
+                self.autosave();
+            }
+            Err(e) => replay.error = Some(e),
+        }
+    }
+
+    /// Whether keys should turn the virtual cube instead of navigating
+    pub fn virtual_cube_active(&self) -> bool {
+        self.route.active_block == ActiveBlock::Main && self.active_tool == Tool::Virtual
+    }
+
+    /// Keymap context of the active block, looked up before the global one
+    pub fn context(&self) -> Option<Context> {
+        if self.trainer_active() {
+            return Some(Context::Trainer);
+        }
+        match self.route.active_block {
+            ActiveBlock::Times => Some(Context::Times),
+            ActiveBlock::Tools => Some(Context::Tools),
+            ActiveBlock::Timer => Some(Context::Timer),
+            _ => None,
+        }
+    }
+
+    pub fn trainer_active(&self) -> bool {
+        self.route.active_block == ActiveBlock::Main && self.active_tool == Tool::Trainer
+    }
+
+    pub fn trainer_press(&mut self) {
+        self.trainer.press();
+        self.update_tick_rate();
+    }
+
+    /// Turn the virtual cube. The first turn starts the timer and solving the
+    /// cube stops it and records the solve with its move count.
+    pub fn virtual_turn(&mut self, m: Move) {
+        if self.virtual_cube.moves.is_empty() && !self.timer.on {
+            self.timer_press();
+        }
+        self.virtual_cube.state.apply(m);
+        self.virtual_cube.moves.push(m);
+
+        if self.timer.on && self.virtual_cube.state.is_solved() {
+            let moves = self.virtual_cube.move_count();
+            if let Some(mut t) = self.timer.space_press() {
+                t.moves = Some(moves);
+                t.reconstruction = Some(Reconstruction {
+                    steps: vec![Step {
+                        label: None,
+                        moves: self.virtual_cube.moves.clone(),
+                    }],
+                });
+                self.record_time(t);
+            }
+            self.update_tick_rate();
+        }
+    }
+
+    pub fn on_tick(&mut self) {
+        if let Some(replay) = &mut self.replay {
+            replay.tick();
+            self.update_tick_rate();
+        }
+    }
+
+    /// Start the chat task, which needs the event stream to report to, and
+    /// dial what `:connect` queued up
+    pub fn start_chat(&mut self) {
+        let Some(events) = &self.events else {
+            return;
+        };
+        let keypair = match identity::load_or_create(&self.default_path.with_file_name("identity"))
+        {
+            Ok(keypair) => keypair,
+            Err(e) => {
+                self.chat
+                    .status(format!("chat is offline, no identity: {}", e));
+                return;
+            }
+        };
+        let rooms = self.chat.rooms.iter().map(|r| r.name.clone()).collect();
+        let outbox = Outbox::load(&self.default_path.with_file_name("outbox"), Instant::now());
+        let blocked = self.blocked.peers.iter().filter_map(|p| p.parse().ok());
+        let sender = chat::spawn(
+            self.config.networking.clone(),
+            rooms,
+            keypair,
+            outbox,
+            blocked.collect(),
+            events.clone(),
+        );
+        for addr in self.dial.drain(..).filter_map(|a| a.parse().ok()) {
+            let _ = sender.try_send(ChatCommand::Dial(addr));
+        }
+        self.chat.sender = Some(sender);
+    }
+
+    /// Whether keys should go to the chat input instead of navigating
+    pub fn chat_active(&self) -> bool {
+        self.route.active_block == ActiveBlock::Main && self.active_tool == Tool::Gnostr
+    }
+
+    /// Publish the chat input. Gossipsub does not hand our own messages
+    /// back, so they are logged here, and kept once the publish is.
+    pub fn send_chat(&mut self) {
+        let text = self.chat.input.value().trim().to_string();
+        if text.is_empty() {
+            return;
+        }
+        let status = match &self.chat.sender {
+            None => "chat is offline, message not sent".to_string(),
+            Some(sender) => {
+                let envelope = Envelope::new(&self.config.networking.nickname, Body::Chat { text });
+                let room = self.chat.room().name.clone();
+                let command = ChatCommand::Publish {
+                    room: room.clone(),
+                    envelope: envelope.clone(),
+                };
+                match sender.try_send(command) {
+                    Ok(()) => {
+                        // The chat task publishes these very bytes
+                        let id = wire::message_id(&envelope.encode());
+                        let room_view = self.chat.room_mut();
+                        room_view.push(NetworkEvent::Message {
+                            id,
+                            topic: room,
+                            source: self.chat.local.clone().unwrap_or_default(),
+                            envelope,
+                        });
+                        room_view.scroll = 0;
+                        self.chat.input = Input::default();
+                        return;
+                    }
+                    Err(e) => format!("message not sent: {}", e),
+                }
+            }
+        };
+        self.chat.status(status);
+    }
+
+    /// Whether keys should go to the direct message input
+    pub fn direct_active(&self) -> bool {
+        self.route.active_block == ActiveBlock::Main && self.active_tool == Tool::Direct
+    }
+
+    /// Send `text` to the peer of the conversation shown. It is logged
+    /// right away, and its delivery follows by its id.
+    pub fn send_direct(&mut self, text: String) -> Result<(), String> {
+        let text = text.trim().to_string();
+        if text.is_empty() {
+            return Ok(());
+        }
+        let Some(peer) = self.direct.conversation().map(|c| c.peer.clone()) else {
+            return Err("no conversation, start one with :dm <peer>".to_string());
+        };
+        let Some(sender) = &self.chat.sender else {
+            return Err("chat is offline, message not sent".to_string());
+        };
+        let message = Outgoing::new(&peer, text);
+        sender
+            .try_send(ChatCommand::Direct(message.clone()))
+            .map_err(|e| format!("message not sent: {}", e))?;
+        let record = Record {
+            id: format!("{:x}", message.id),
+            time: history::now(),
+            source: self.chat.local.clone().unwrap_or_default(),
+            item: message.text.clone(),
+        };
+        let saved = self.history.append(&history::direct_key(&peer), &record);
+        if let Some(conversation) = self.direct.conversation_mut() {
+            conversation.log.push(DirectLine {
+                id: message.id,
+                mine: true,
+                delivery: Some(Delivery::Queued(None)),
+                text: message.text,
+            });
+            conversation.scroll = 0;
+        }
+        saved
+            .map(|_| ())
+            .map_err(|e| format!("sent, but could not save the history: {}", e))
+    }
+
+    /// Recent messages of the rooms, and of every conversation there is a
+    /// history of
+    fn load_history(&mut self) {
+        for index in 0..self.chat.rooms.len() {
+            self.load_room(index);
+        }
+        for key in self.history.keys() {
+            if let Some(peer) = key.strip_prefix("dm-") {
+                let index = self.direct.open(peer);
+                self.load_direct(index);
+            }
+        }
+    }
+
+    /// Position of the first message of `key` to load when it is opened:
+    /// a page back from the newest, but none from before `RECENT`
+    fn recent(&mut self, key: &str) -> std::io::Result<(usize, usize)> {
+        let len = self.history.len(key)?;
+        let since = history::now().saturating_sub(RECENT);
+        let start = len
+            .saturating_sub(HISTORY_PAGE)
+            .max(self.history.at(key, since)?);
+        Ok((start, len))
+    }
+
+    /// Fill the new log of room `index` with its recent history
+    fn load_room(&mut self, index: usize) {
+        let key = history::room_key(&self.chat.rooms[index].name);
+        match self.recent(&key) {
+            Ok((start, len)) => {
+                self.chat.rooms[index].first = len;
+                self.room_back(index, len - start);
+            }
+            Err(e) => self.chat.rooms[index].push(NetworkEvent::Status(format!(
+                "could not read the history: {}",
+                e
+            ))),
+        }
+    }
+
+    /// Fill the new log of conversation `index` with its recent history
+    fn load_direct(&mut self, index: usize) {
+        let key = history::direct_key(&self.direct.conversations[index].peer);
+        match self.recent(&key) {
+            Ok((start, len)) => {
+                self.direct.conversations[index].first = len;
+                self.direct_back(index, len - start);
+            }
+            Err(e) => self
+                .chat
+                .status(format!("could not read the history: {}", e)),
+        }
+    }
+
+    /// Put up to `count` older messages of room `index` from the history
+    /// before its log
+    fn room_back(&mut self, index: usize, count: usize) {
+        let room = &self.chat.rooms[index];
+        let key = history::room_key(&room.name);
+        let range = room.first.saturating_sub(count)..room.first;
+        let records = match self.history.read::<Envelope>(&key, range.clone()) {
+            Ok(records) => records,
+            Err(e) => {
+                let status = format!("could not read the history: {}", e);
+                return self.chat.rooms[index].push(NetworkEvent::Status(status));
+            }
+        };
+        let room = &mut self.chat.rooms[index];
+        let messages = records.into_iter().map(|r| NetworkEvent::Message {
+            id: r.id,
+            topic: room.name.clone(),
+            source: r.source,
+            envelope: r.item,
+        });
+        room.log.splice(0..0, messages);
+        room.first = range.start;
     }
 
-    pub fn on_tick(&self) {
-        ()
+    /// Put up to `count` older messages of conversation `index` from the
+    /// history before its log
+    fn direct_back(&mut self, index: usize, count: usize) {
+        let conversation = &self.direct.conversations[index];
+        let key = history::direct_key(&conversation.peer);
+        let range = conversation.first.saturating_sub(count)..conversation.first;
+        let records = match self.history.read::<String>(&key, range.clone()) {
+            Ok(records) => records,
+            Err(e) => {
+                return self
+                    .chat
+                    .status(format!("could not read the history: {}", e))
+            }
+        };
+        let conversation = &mut self.direct.conversations[index];
+        let lines = records.into_iter().map(|r| DirectLine {
+            id: u64::from_str_radix(&r.id, 16).unwrap_or_default(),
+            mine: r.source != conversation.peer,
+            delivery: None,
+            text: r.item,
+        });
+        conversation.log.splice(0..0, lines);
+        conversation.first = range.start;
+    }
+
+    /// Index of room `name`, adding it with its recent history if it is
+    /// new
+    fn open_room(&mut self, name: &str) -> usize {
+        if let Some(index) = self.chat.find(name) {
+            return index;
+        }
+        let index = self.chat.join(name);
+        self.load_room(index);
+        index
+    }
+
+    /// Scroll the room shown `lines` back, or forward when negative. Older
+    /// history is read as the top of the log comes near.
+    pub fn scroll_chat(&mut self, lines: isize) {
+        let index = self.chat.current;
+        let room = self.chat.room_mut();
+        room.scroll = room.scroll.saturating_add_signed(lines);
+        if lines > 0 && room.first > 0 && room.scroll + HISTORY_PAGE >= room.log.len() {
+            self.room_back(index, HISTORY_PAGE);
+        }
+    }
+
+    /// Scroll the conversation shown like `scroll_chat`
+    pub fn scroll_direct(&mut self, lines: isize) {
+        let index = self.direct.current;
+        let Some(conversation) = self.direct.conversation_mut() else {
+            return;
+        };
+        conversation.scroll = conversation.scroll.saturating_add_signed(lines);
+        if lines > 0
+            && conversation.first > 0
+            && conversation.scroll + HISTORY_PAGE >= conversation.log.len()
+        {
+            self.direct_back(index, HISTORY_PAGE);
+        }
+    }
+
+    /// Scroll the room or conversation shown to the newest message before
+    /// the last match that has `text`, or before the end for new text
+    fn search(&mut self, text: &str) -> Result<String, String> {
+        if text.is_empty() {
+            return Err("search for what?".to_string());
+        }
+        let needle = text.to_lowercase();
+        let direct = self.active_tool == Tool::Direct;
+        let key = match direct {
+            true => match self.direct.conversation() {
+                Some(c) => history::direct_key(&c.peer),
+                None => return Err("no conversation to search".to_string()),
+            },
+            false => history::room_key(&self.chat.room().name),
+        };
+        let end = match &self.search {
+            Some((last, position)) if *last == needle => *position,
+            _ => usize::MAX,
+        };
+        let found = match direct {
+            true => self
+                .history
+                .search::<String>(&key, end, |r| r.item.to_lowercase().contains(&needle)),
+            false => self
+                .history
+                .search::<Envelope>(&key, end, |r| match &r.item.body {
+                    Body::Chat { text } => text.to_lowercase().contains(&needle),
+                    _ => false,
+                }),
+        }
+        .map_err(|e| format!("could not read the history: {}", e))?;
+        let Some(position) = found else {
+            self.search = None;
+            return Err(match end {
+                usize::MAX => format!("nothing has {:?}", text),
+                _ => format!("nothing older has {:?}", text),
+            });
+        };
+        self.search = Some((needle, position));
+
+        // Read back to the match and put it on the bottom line, finding it
+        // in the log by its id
+        let found = self
+            .history
+            .read::<serde_json::Value>(&key, position..position + 1)
+            .ok()
+            .and_then(|r| r.into_iter().next());
+        let Some(record) = found else {
+            return Err(format!("found {:?} but could not read it", text));
+        };
+        let shown = match direct {
+            true => {
+                let index = self.direct.current;
+                let first = self.direct.conversations[index].first;
+                self.direct_back(index, first.saturating_sub(position));
+                let conversation = &mut self.direct.conversations[index];
+                let line = conversation
+                    .log
+                    .iter()
+                    .rposition(|l| format!("{:x}", l.id) == record.id);
+                if let Some(i) = line {
+                    conversation.scroll = conversation.log.len() - 1 - i;
+                }
+                line.is_some()
+            }
+            false => {
+                let index = self.chat.current;
+                let first = self.chat.rooms[index].first;
+                self.room_back(index, first.saturating_sub(position));
+                let room = &mut self.chat.rooms[index];
+                let line = room.log.iter().rposition(|e| match e {
+                    NetworkEvent::Message { id, .. } => *id == record.id,
+                    _ => false,
+                });
+                if let Some(i) = line {
+                    room.scroll = room.log.len() - 1 - i;
+                }
+                line.is_some()
+            }
+        };
+        match shown {
+            true => Ok(format!(
+                "found {:?} from {}",
+                text,
+                format_date(record.time / 1000)
+            )),
+            false => Err(format!("found {:?} but could not show it", text)),
+        }
+    }
+
+    /// What to call the sender of a message: `me`, our nickname for the
+    /// peer, the nickname it sent, or the tail of its id
+    pub fn sender_name(&self, peer: &str, nick: &str) -> String {
+        // Our own messages may be sent before the swarm knows its id
+        if peer.is_empty() || Some(peer) == self.chat.local.as_deref() {
+            return "me".to_string();
+        }
+        match self.address_book.names.get(peer) {
+            Some(name) => name.clone(),
+            None if !nick.is_empty() => format!("~{}", nick),
+            None => identity::short_id(peer),
+        }
+    }
+
+    pub fn on_network(&mut self, event: NetworkEvent) {
+        match event {
+            NetworkEvent::Started(local) => {
+                self.chat.status(format!("started as {}", local));
+                self.chat.local = Some(local);
+            }
+            NetworkEvent::Listening(addr) => self.chat.listening.push(addr),
+            NetworkEvent::PeerJoined(peer) => {
+                if !self.chat.peers.contains(&peer) {
+                    let name = self.address_book.name(&peer);
+                    self.chat.status(format!("{} joined", name));
+                    self.chat.peers.push(peer);
+                }
+            }
+            NetworkEvent::PeerLeft(peer) => {
+                self.chat.peers.retain(|p| *p != peer);
+                let name = self.address_book.name(&peer);
+                self.chat.status(format!("{} left", name));
+            }
+            NetworkEvent::Message {
+                id,
+                topic,
+                source,
+                envelope,
+            } => self.on_message(id, topic, source, envelope),
+            NetworkEvent::Direct { peer, id, text } => self.on_direct(peer, id, text),
+            // Messages left from before a restart are not in any log
+            NetworkEvent::Delivery { peer, id, state } => {
+                if let Some(line) = self.direct.line_mut(&peer, id) {
+                    line.delivery = Some(state);
+                }
+            }
+            NetworkEvent::Published { topic, id, result } => self.on_published(topic, id, result),
+            NetworkEvent::Status(status) => self.chat.status(status),
+        }
+    }
+
+    /// Keep a message of ours in the history once it went out, or take it
+    /// back out of the log when it did not
+    fn on_published(&mut self, topic: String, id: String, result: Result<(), String>) {
+        let Some(index) = self.chat.find(&topic) else {
+            return;
+        };
+        let room = &mut self.chat.rooms[index];
+        let Some(line) = room.log.iter().rposition(|e| match e {
+            NetworkEvent::Message { id: logged, .. } => *logged == id,
+            _ => false,
+        }) else {
+            return;
+        };
+        if let Err(e) = result {
+            room.log.remove(line);
+            return room.push(NetworkEvent::Status(format!("not sent: {}", e)));
+        }
+        let NetworkEvent::Message {
+            source, envelope, ..
+        } = &room.log[line]
+        else {
+            return;
+        };
+        let record = Record {
+            id,
+            time: history::now(),
+            source: source.clone(),
+            item: envelope.clone(),
+        };
+        if let Err(e) = self.history.append(&history::room_key(&topic), &record) {
+            self.chat
+                .status(format!("could not save the history: {}", e));
+        }
+    }
+
+    /// Log and keep a room message, unless it was seen before
+    fn on_message(&mut self, id: String, topic: String, source: String, envelope: Envelope) {
+        // The swarm drops these too, this catches any already on their way
+        if self.blocked.contains(&source) {
+            return;
+        }
+        // Opened first, as its history is read in up to now
+        let index = self.open_room(&topic);
+        let record = Record {
+            id: id.clone(),
+            time: history::now(),
+            source: source.clone(),
+            item: envelope.clone(),
+        };
+        match self.history.append(&history::room_key(&topic), &record) {
+            // Gossip brings the same message again after a restart
+            Ok(false) => return,
+            Ok(true) => (),
+            Err(e) => self
+                .chat
+                .status(format!("could not save the history: {}", e)),
+        }
+        // Only the room on screen counts as read
+        let shown = matches!(self.route.screen, Screen::Default)
+            && self.active_tool == Tool::Gnostr
+            && index == self.chat.current;
+        let room = &mut self.chat.rooms[index];
+        room.push(NetworkEvent::Message {
+            id,
+            topic,
+            source,
+            envelope,
+        });
+        if !shown {
+            room.unread += 1;
+        }
+    }
+
+    /// Log and keep a direct message, unless it was seen before
+    fn on_direct(&mut self, peer: String, id: u64, text: String) {
+        if self.blocked.contains(&peer) {
+            return;
+        }
+        let index = self.direct.open(&peer);
+        let record = Record {
+            id: format!("{:x}", id),
+            time: history::now(),
+            source: peer,
+            item: text.clone(),
+        };
+        match self
+            .history
+            .append(&history::direct_key(&record.source), &record)
+        {
+            // A sender that missed our ack sends it again
+            Ok(false) => return,
+            Ok(true) => (),
+            Err(e) => self
+                .chat
+                .status(format!("could not save the history: {}", e)),
+        }
+        let shown = matches!(self.route.screen, Screen::Default)
+            && self.active_tool == Tool::Direct
+            && index == self.direct.current;
+        let conversation = &mut self.direct.conversations[index];
+        conversation.log.push(DirectLine {
+            id,
+            mine: false,
+            delivery: None,
+            text,
+        });
+        if conversation.scroll > 0 {
+            conversation.scroll += 1;
+        }
+        if !shown {
+            conversation.unread += 1;
+        }
+    }
+
+    pub fn on_task(&mut self, result: TaskResult) {
+        if result.name == "git log" {
+            self.git_log = Some(result.output);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_penalties() {
+        let mut time = Time::from(12.5);
+        time.penalty = Penalty::PlusTwo;
+        time.date = Some(1_714_588_200);
+        time.comment = "lucky pll".to_string();
+        let parsed = Time::parse_record(&time.record()).unwrap();
+        assert_eq!(parsed.penalty, Penalty::PlusTwo);
+        assert_eq!(parsed.comment, "lucky pll");
+        assert_eq!(parsed.label(2), "14.50+");
+        assert_eq!(format_date(parsed.date.unwrap()), "2024-05-01 18:30");
+
+        // One DNF is dropped as the worst solve of an ao5, two make it a DNF
+        let mut times = Times::new();
+        times.times = [10.0, 11.0, 12.0, 13.0, 14.0, 15.0]
+            .into_iter()
+            .map(Time::from)
+            .collect();
+        times.times[1].penalty = Penalty::Dnf;
+        times.recalc();
+        assert_eq!(times.times[4].ao5, Some(13.0));
+        times.times[5].penalty = Penalty::Dnf;
+        times.recalc();
+        assert_eq!(
+            times.times[5].ao5.map(|v| format_time(v, 2)),
+            Some("DNF".into())
+        );
+        assert_eq!(times.pbsingle, Some(10.0));
+        assert_eq!(times.rollingavg, Some(12.25));
     }
 }
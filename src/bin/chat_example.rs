@@ -1,5 +1,4 @@
-use cube_tui::chat::chat;
-use cube_tui::global_rt::global_rt;
+use cube_tui::handlers::theme::color_name;
 
 use crossterm::{
     terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType},
@@ -11,36 +10,6 @@ use tui::{backend::CrosstermBackend, layout::Rect, style::Color, widgets::Block,
 
 use tui::style::Style;
 
-fn color_name(color: Color) -> &'static str {
-    match color {
-        Color::Black => "Black",
-        Color::Red => "Red",
-        Color::Green => "Green",
-        Color::Yellow => "Yellow",
-        Color::Blue => "Blue",
-        Color::Magenta => "Magenta",
-        Color::Cyan => "Cyan",
-        Color::White => "White",
-        Color::Gray => "Gray",
-        Color::DarkGray => "DarkGray",
-        Color::LightRed => "LightRed",
-        Color::LightGreen => "LightGreen",
-        Color::LightYellow => "LightYellow",
-        Color::LightBlue => "LightBlue",
-        Color::LightMagenta => "LightMagenta",
-        Color::LightCyan => "LightCyan",
-        //Color::LightWhite => "LightWhite",
-        Color::Reset => todo!(),
-        Color::Rgb(r, g, b) => {
-            // Format RGB as a string
-            return Box::leak(format!("Rgb({}, {}, {})", r, g, b).into_boxed_str());
-        }
-        Color::Indexed(i) => {
-            // Format Indexed as a string
-            return Box::leak(format!("Indexed({})", i).into_boxed_str());
-        }
-    }
-}
 #[tokio::main]
 async fn main() -> Result<(), std::io::Error> {
     enable_raw_mode()?;
@@ -98,13 +67,6 @@ async fn main() -> Result<(), std::io::Error> {
         }
     })?;
 
-   let global_rt_result = global_rt().spawn(async move {
-        let _ = chat();
-        println!("global_rt async task!");
-        String::from("global_rt async task!")
-    });
-    println!("global_rt_result={:?}", global_rt_result.await);
-
     disable_raw_mode()?;
     std::io::stdout().flush()?;
     Ok(())
@@ -1,101 +1,331 @@
 use crossterm::{
-    terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType},
+    event::{self, Event, KeyCode, KeyEventKind},
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
-use std::io::{stdout, Write};
-use tui::style::Modifier;
-use tui::{backend::CrosstermBackend, layout::Rect, style::Color, widgets::Block, Terminal};
-
-use tui::style::Style;
-
-fn color_name(color: Color) -> &'static str {
-    match color {
-        Color::Black => "Black",
-        Color::Red => "Red",
-        Color::Green => "Green",
-        Color::Yellow => "Yellow",
-        Color::Blue => "Blue",
-        Color::Magenta => "Magenta",
-        Color::Cyan => "Cyan",
-        Color::White => "White",
-        Color::Gray => "Gray",
-        Color::DarkGray => "DarkGray",
-        Color::LightRed => "LightRed",
-        Color::LightGreen => "LightGreen",
-        Color::LightYellow => "LightYellow",
-        Color::LightBlue => "LightBlue",
-        Color::LightMagenta => "LightMagenta",
-        Color::LightCyan => "LightCyan",
-        //Color::LightWhite => "LightWhite",
-        Color::Reset => todo!(),
-        Color::Rgb(r, g, b) => {
-            // Format RGB as a string
-            return Box::leak(format!("Rgb({}, {}, {})", r, g, b).into_boxed_str());
-        }
-        Color::Indexed(i) => {
-            // Format Indexed as a string
-            return Box::leak(format!("Indexed({})", i).into_boxed_str());
+use std::{
+    fs::{create_dir_all, write},
+    io::stdout,
+};
+use tui::{
+    backend::{Backend, CrosstermBackend},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    symbols,
+    text::{Span, Spans},
+    widgets::{
+        Axis, Block, Borders, Cell, Chart, Dataset, GraphType, List, ListItem, ListState,
+        Paragraph, Row, Table,
+    },
+    Frame, Terminal,
+};
+
+use cube_tui::handlers::theme::{color_name, Theme, ThemeColor, BUILTIN, PALETTE, ROLES};
+
+const HINTS: &str = "j/k role   h/l color   t next built-in theme   w write   q quit";
+
+/// Theme previewer: `rat_colors [theme] [name]` starts from `theme` (a
+/// built-in, a theme name or a path) and writes the result as `name`.
+struct Previewer {
+    theme: Theme,
+    name: String,
+    builtin: usize,
+    roles: ListState,
+    message: String,
+}
+
+impl Previewer {
+    fn role(&self) -> &'static str {
+        ROLES[self.roles.selected().unwrap_or(0)]
+    }
+
+    fn mv(&mut self, by: isize) {
+        let i = self.roles.selected().unwrap_or(0) as isize + by;
+        self.roles
+            .select(Some(i.rem_euclid(ROLES.len() as isize) as usize));
+    }
+
+    fn cycle_color(&mut self, by: isize) {
+        let role = self.role();
+        let Some(color) = self.theme.role(role) else {
+            return;
+        };
+        let i = PALETTE.iter().position(|c| *c == color.0).unwrap_or(0) as isize + by;
+        let color = ThemeColor(PALETTE[i.rem_euclid(PALETTE.len() as isize) as usize]);
+        if let Some(c) = self.theme.role_mut(role) {
+            *c = color;
         }
+        self.message = format!("{} = {}", role, color);
+    }
+
+    fn next_builtin(&mut self) {
+        self.builtin = (self.builtin + 1) % BUILTIN.len();
+        self.theme = Theme::builtin(BUILTIN[self.builtin]).unwrap_or_default();
+        self.message = format!("built-in theme {}", BUILTIN[self.builtin]);
+    }
+
+    fn write(&mut self) {
+        let path = Theme::path(&self.name);
+        let result = toml::to_string(&self.theme)
+            .map_err(|e| e.to_string())
+            .and_then(|text| {
+                if let Some(dir) = path.parent() {
+                    create_dir_all(dir).map_err(|e| e.to_string())?;
+                }
+                write(&path, text).map_err(|e| e.to_string())
+            });
+        self.message = match result {
+            Ok(()) => format!(
+                "wrote {}, set frontend.theme = {:?} to use it",
+                path.display(),
+                self.name
+            ),
+            Err(e) => format!("could not write {}: {}", path.display(), e),
+        };
     }
 }
 
 fn main() -> Result<(), std::io::Error> {
+    let mut args = std::env::args().skip(1);
+    let start = args.next().unwrap_or_else(|| "dark".to_string());
+    let name = args.next().unwrap_or_else(|| "custom".to_string());
+    let theme = match Theme::load(&start) {
+        Ok(theme) => theme,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let mut roles = ListState::default();
+    roles.select(Some(0));
+    let mut previewer = Previewer {
+        theme,
+        name,
+        builtin: BUILTIN.iter().position(|b| *b == start).unwrap_or(0),
+        roles,
+        message: format!("editing {}", start),
+    };
+
     enable_raw_mode()?;
-    let mut stdout = stdout();
-    stdout.execute(Clear(ClearType::All))?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-
-    terminal.draw(|f| {
-        let colors = [
-            Color::Black,
-            Color::Red,
-            Color::Green,
-            Color::Yellow,
-            Color::Blue,
-            Color::Magenta,
-            Color::Cyan,
-            Color::White,
-            Color::Gray,
-            Color::DarkGray,
-            //Color::LightRed,
-            //Color::LightGreen,
-            //Color::LightYellow,
-            //Color::LightBlue,
-            //Color::LightMagenta,
-            //Color::LightCyan,
-            //Color::LightWhite,
-        ];
-
-        let mut x = 0;
-        let mut y = 0;
-        let width = 10;
-        let height = 2;
-
-        let style = Style::default()
-            .fg(Color::Black)
-            .bg(colors[0])
-            .add_modifier(Modifier::ITALIC | Modifier::BOLD);
-
-        for index in 1..colors.len() {
-            for color in colors {
-                let block = Block::default()
-                    .style(Style::default().fg(colors[index - 1]).bg(color))
-                    .title(color_name(color));
-                let area = Rect::new(x, y, width, height);
-
-                f.render_widget(block, area);
-
-                x += width;
-                if x + width > f.size().width {
-                    x = 0;
-                    y += height;
-                }
+    stdout().execute(EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+    let res = run(&mut terminal, &mut previewer);
+    disable_raw_mode()?;
+    stdout().execute(LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    res
+}
+
+fn run<B: Backend>(
+    terminal: &mut Terminal<B>,
+    previewer: &mut Previewer,
+) -> Result<(), std::io::Error> {
+    loop {
+        terminal.draw(|f| render(f, previewer))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Char('j') | KeyCode::Down => previewer.mv(1),
+                KeyCode::Char('k') | KeyCode::Up => previewer.mv(-1),
+                KeyCode::Char('l') | KeyCode::Right => previewer.cycle_color(1),
+                KeyCode::Char('h') | KeyCode::Left => previewer.cycle_color(-1),
+                KeyCode::Char('t') => previewer.next_builtin(),
+                KeyCode::Char('w') => previewer.write(),
+                _ => (),
             }
         }
-    })?;
+    }
+}
 
-    disable_raw_mode()?;
-    std::io::stdout().flush()?;
-    Ok(())
+fn render<B: Backend>(f: &mut Frame<B>, previewer: &mut Previewer) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(3)].as_ref())
+        .split(f.size());
+    let main_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(36), Constraint::Min(0)].as_ref())
+        .split(chunks[0]);
+
+    let items = ROLES
+        .iter()
+        .map(|role| {
+            let color = previewer.theme.role(role).map_or(Color::Reset, |c| c.0);
+            ListItem::new(Spans::from(vec![
+                Span::styled("██ ", Style::default().fg(color)),
+                Span::raw(format!("{:<20}{}", role, color_name(color))),
+            ]))
+        })
+        .collect::<Vec<ListItem>>();
+    let list = List::new(items)
+        .block(Block::default().title(" Roles ").borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    f.render_stateful_widget(list, main_chunks[0], &mut previewer.roles);
+
+    render_mock(f, &previewer.theme, main_chunks[1]);
+
+    let status = Paragraph::new(vec![Spans::from(previewer.message.clone())]).block(
+        Block::default()
+            .title(format!(" {} ", HINTS))
+            .borders(Borders::ALL),
+    );
+    f.render_widget(status, chunks[1]);
+}
+
+/// The default screen of the TUI with made up data, laid out like `ui::render_default`
+fn render_mock<B: Backend>(f: &mut Frame<B>, theme: &Theme, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(33), Constraint::Min(0)].as_ref())
+        .split(area);
+    let left = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Percentage(100),
+            ]
+            .as_ref(),
+        )
+        .split(chunks[0]);
+    let right = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Length(10),
+                Constraint::Percentage(100),
+            ]
+            .as_ref(),
+        )
+        .split(chunks[1]);
+    let block = |title: &'static str, border: Style| {
+        Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .border_style(border)
+    };
+
+    let tools = Paragraph::new("Gnostr  Relay  Commit  Virtual  Trainer")
+        .block(block(
+            " Tools ",
+            theme
+                .border
+                .active
+                .fg()
+                .add_modifier(Modifier::BOLD | Modifier::ITALIC),
+        ))
+        .style(theme.text.normal.fg());
+    f.render_widget(tools, left[0]);
+
+    let timer = Paragraph::new(Spans::from(vec![
+        Span::styled("12.345   ", theme.timer.stopped.fg()),
+        Span::styled("8.210   ", theme.timer.running.fg()),
+        Span::styled("0.000", theme.timer.idle.fg()),
+    ]))
+    .block(block(" Timer ", theme.border.selected.fg()))
+    .alignment(Alignment::Center);
+    f.render_widget(timer, left[1]);
+
+    let header = Row::new(["i", "time", "ao5", "ao12"].iter().map(|h| Cell::from(*h)))
+        .style(theme.text.dim.fg())
+        .bottom_margin(1);
+    let rows = [
+        ("3", "12.35", "13.02", "-", theme.highlight.active.fg()),
+        ("2", "13.90", "-", "-", theme.text.dim.fg()),
+        ("1", "DNF", "-", "-", theme.text.error.fg()),
+    ]
+    .into_iter()
+    .map(|(i, t, ao5, ao12, style)| Row::new(vec![i, t, ao5, ao12]).style(style));
+    let times = Table::new(rows)
+        .header(header)
+        .block(block(" Times ", theme.border.normal.fg()))
+        .widths(&[
+            Constraint::Length(4),
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Length(8),
+        ]);
+    f.render_widget(times, left[2]);
+
+    let stats = Paragraph::new(Spans::from(vec![
+        Span::styled("best 11.02   ", theme.text.normal.fg()),
+        Span::styled("ao5 13.02   ", theme.text.dim.fg()),
+        Span::styled("nick: ", theme.text.accent.fg()),
+        Span::styled("hello", theme.text.normal.fg()),
+    ]))
+    .block(block(" Stats ", theme.border.normal.fg()));
+    f.render_widget(stats, right[0]);
+
+    let sticker = |color: Color| Span::styled("██", Style::default().fg(color));
+    let cube = &theme.cube;
+    let mut lines = vec![Spans::from(Span::styled(
+        "R U R' U' F2 D L2 B' U2 R",
+        theme.text.normal.fg(),
+    ))];
+    for _ in 0..3 {
+        let mut spans = vec![Span::raw(" ".repeat(7))];
+        spans.extend((0..3).map(|_| sticker(cube.u.0)));
+        lines.push(Spans::from(spans));
+    }
+    for _ in 0..3 {
+        let mut spans = vec![];
+        for color in [cube.l, cube.f, cube.r, cube.b] {
+            spans.extend((0..3).map(|_| sticker(color.0)));
+            spans.push(Span::raw(" "));
+        }
+        lines.push(Spans::from(spans));
+    }
+    for _ in 0..3 {
+        let mut spans = vec![Span::raw(" ".repeat(7))];
+        spans.extend((0..3).map(|_| sticker(cube.d.0)));
+        lines.push(Spans::from(spans));
+    }
+    let scramble = Paragraph::new(lines).block(block(" Scramble ", theme.border.normal.fg()));
+    f.render_widget(scramble, right[1]);
+
+    let singles = [
+        (0.0, 14.0),
+        (1.0, 12.5),
+        (2.0, 13.9),
+        (3.0, 11.0),
+        (4.0, 12.3),
+    ];
+    let ao5 = [(2.0, 13.5), (3.0, 12.8), (4.0, 12.6)];
+    let ao12 = [(3.0, 13.2), (4.0, 13.0)];
+    let datasets = [
+        ("single", &singles[..], theme.chart.single),
+        ("ao5", &ao5[..], theme.chart.ao5),
+        ("ao12", &ao12[..], theme.chart.ao12),
+    ]
+    .into_iter()
+    .map(|(name, data, color)| {
+        Dataset::default()
+            .name(name)
+            .marker(symbols::Marker::Dot)
+            .graph_type(GraphType::Line)
+            .style(color.fg())
+            .data(data)
+    })
+    .collect::<Vec<Dataset>>();
+    let chart = Chart::new(datasets)
+        .block(block(" Main ", theme.border.normal.fg()))
+        .x_axis(
+            Axis::default()
+                .title("n")
+                .style(theme.chart.axis.fg())
+                .bounds([0.0, 4.0]),
+        )
+        .y_axis(
+            Axis::default()
+                .title("Time")
+                .style(theme.chart.axis.fg())
+                .bounds([10.0, 15.0])
+                .labels(vec![Span::raw("10"), Span::raw("15")]),
+        );
+    f.render_widget(chart, right[2]);
 }
@@ -0,0 +1,123 @@
+use std::time::{Duration, Instant};
+
+use libp2p::{swarm::ConnectionId, Multiaddr};
+
+/// Wait before the first redial, doubled after every failure
+pub(crate) const FIRST_BACKOFF: Duration = Duration::from_secs(1);
+pub(crate) const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Not connected, dialled again at `Peer::next`
+    Waiting,
+    Dialing(ConnectionId),
+    Connected(ConnectionId),
+}
+
+struct Peer {
+    addr: Multiaddr,
+    state: State,
+    backoff: Duration,
+    next: Instant,
+}
+
+/// Peers dialled at startup and redialled with exponential backoff whenever
+/// their connection fails or closes, so a swarm finds others without mDNS
+pub struct Bootstrap {
+    peers: Vec<Peer>,
+}
+
+impl Bootstrap {
+    /// All of `addrs` are due right away
+    pub fn new(addrs: Vec<Multiaddr>, now: Instant) -> Self {
+        let peers = addrs
+            .into_iter()
+            .map(|addr| Peer {
+                addr,
+                state: State::Waiting,
+                backoff: FIRST_BACKOFF,
+                next: now,
+            })
+            .collect();
+        Self { peers }
+    }
+
+    /// Addresses to dial now. Each dial is reported back with `dialing`.
+    pub fn due(&self, now: Instant) -> Vec<Multiaddr> {
+        self.peers
+            .iter()
+            .filter(|p| p.state == State::Waiting && p.next <= now)
+            .map(|p| p.addr.clone())
+            .collect()
+    }
+
+    /// `addr` is being dialled on connection `id`
+    pub fn dialing(&mut self, addr: &Multiaddr, id: ConnectionId) {
+        if let Some(peer) = self.peers.iter_mut().find(|p| p.addr == *addr) {
+            peer.state = State::Dialing(id);
+        }
+    }
+
+    /// Connection `id` is up. Returns whether it was a bootstrap dial.
+    pub fn established(&mut self, id: ConnectionId) -> bool {
+        match self
+            .peers
+            .iter_mut()
+            .find(|p| p.state == State::Dialing(id))
+        {
+            Some(peer) => {
+                peer.state = State::Connected(id);
+                peer.backoff = FIRST_BACKOFF;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Connection `id` failed or closed. Returns the address and how long
+    /// until it is dialled again, if it was a bootstrap connection.
+    pub fn lost(&mut self, id: ConnectionId, now: Instant) -> Option<(Multiaddr, Duration)> {
+        let peer = self
+            .peers
+            .iter_mut()
+            .find(|p| matches!(p.state, State::Dialing(i) | State::Connected(i) if i == id))?;
+        let wait = peer.backoff;
+        peer.state = State::Waiting;
+        peer.next = now + wait;
+        peer.backoff = (wait * 2).min(MAX_BACKOFF);
+        Some((peer.addr.clone(), wait))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/4001".parse().unwrap();
+        let start = Instant::now();
+        let mut bootstrap = Bootstrap::new(vec![addr.clone()], start);
+        assert_eq!(bootstrap.due(start), vec![addr.clone()]);
+
+        // Every failure doubles the wait, up to a minute
+        let mut now = start;
+        for wait in [1, 2, 4, 8, 16, 32, 60, 60] {
+            let id = ConnectionId::new_unchecked(wait);
+            bootstrap.dialing(&addr, id);
+            assert!(bootstrap.due(now).is_empty());
+            let (_, backoff) = bootstrap.lost(id, now).unwrap();
+            assert_eq!(backoff, Duration::from_secs(wait as u64));
+            assert!(bootstrap.due(now).is_empty());
+            now += backoff;
+            assert_eq!(bootstrap.due(now).len(), 1);
+        }
+
+        // Connecting resets it, and unknown connections are not ours
+        let id = ConnectionId::new_unchecked(100);
+        bootstrap.dialing(&addr, id);
+        assert!(bootstrap.established(id));
+        assert!(!bootstrap.established(ConnectionId::new_unchecked(101)));
+        assert_eq!(bootstrap.lost(id, now).unwrap().1, FIRST_BACKOFF);
+    }
+}
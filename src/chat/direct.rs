@@ -0,0 +1,618 @@
+use std::collections::{HashMap, VecDeque};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use std::{fmt, io, iter};
+
+use futures::future::BoxFuture;
+use futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use libp2p::core::{transport::PortUse, Endpoint, UpgradeInfo};
+use libp2p::swarm::dial_opts::DialOpts;
+use libp2p::swarm::handler::OneShotHandlerConfig;
+use libp2p::swarm::{
+    ConnectionDenied, ConnectionId, DialFailure, FromSwarm, NetworkBehaviour, NotifyHandler,
+    OneShotHandler, StreamUpgradeError, SubstreamProtocol, THandler, THandlerInEvent,
+    THandlerOutEvent, ToSwarm,
+};
+use libp2p::{InboundUpgrade, Multiaddr, OutboundUpgrade, PeerId, StreamProtocol};
+use tokio::time;
+
+use super::outbox::{Delivery, Outbox, Outgoing};
+
+/// Version 2 frames messages and acknowledges them, version 1 did neither
+const PROTOCOL: StreamProtocol = StreamProtocol::new("/direct/2.0");
+/// Version of the frames below, the first byte of every frame
+const VERSION: u8 = 1;
+/// Longest message text either side accepts, in bytes
+const MAX_MESSAGE_SIZE: usize = 2_097_152;
+/// How long a peer has to acknowledge a message once its stream is open
+const ACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How a peer answered a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ack {
+    Delivered = 0,
+    TooLarge = 1,
+    Unsupported = 2,
+    Malformed = 3,
+}
+
+impl Ack {
+    fn from_byte(byte: u8) -> Option<Self> {
+        [
+            Ack::Delivered,
+            Ack::TooLarge,
+            Ack::Unsupported,
+            Ack::Malformed,
+        ]
+        .into_iter()
+        .find(|ack| *ack as u8 == byte)
+    }
+}
+
+/// Why a message was not delivered or not accepted
+#[derive(Debug)]
+pub enum DmError {
+    /// Longer than `MAX_MESSAGE_SIZE`
+    TooLarge(usize),
+    /// Frames of another version
+    Version(u8),
+    Malformed,
+    /// The peer refused it
+    Rejected(Ack),
+    /// The peer does not speak `PROTOCOL`
+    Unsupported,
+    Timeout,
+    /// The connection closed before the peer acknowledged it
+    Closed,
+    Io(String),
+}
+
+impl DmError {
+    /// What to answer the sender of a message we refuse, if it can be told
+    fn ack(&self) -> Option<Ack> {
+        match self {
+            DmError::TooLarge(_) => Some(Ack::TooLarge),
+            DmError::Version(_) => Some(Ack::Unsupported),
+            DmError::Malformed => Some(Ack::Malformed),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DmError::TooLarge(n) => {
+                write!(f, "{} bytes is over the limit of {}", n, MAX_MESSAGE_SIZE)
+            }
+            DmError::Version(v) => {
+                write!(f, "unsupported frame version {}, we speak {}", v, VERSION)
+            }
+            DmError::Malformed => write!(f, "malformed frame"),
+            DmError::Rejected(ack) => write!(f, "rejected by the peer: {:?}", ack),
+            DmError::Unsupported => write!(f, "the peer does not speak {}", PROTOCOL),
+            DmError::Timeout => write!(f, "no acknowledgement within {}s", ACK_TIMEOUT.as_secs()),
+            DmError::Closed => write!(f, "connection closed before an acknowledgement"),
+            DmError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<io::Error> for DmError {
+    fn from(e: io::Error) -> Self {
+        DmError::Io(e.to_string())
+    }
+}
+
+/// Request: version, id as u64, text length as u32, then the UTF-8 text.
+/// Big endian throughout.
+async fn write_request<S: AsyncWrite + Unpin>(
+    socket: &mut S,
+    id: u64,
+    text: &str,
+) -> Result<(), DmError> {
+    if text.len() > MAX_MESSAGE_SIZE {
+        return Err(DmError::TooLarge(text.len()));
+    }
+    let mut frame = Vec::with_capacity(13 + text.len());
+    frame.push(VERSION);
+    frame.extend_from_slice(&id.to_be_bytes());
+    frame.extend_from_slice(&(text.len() as u32).to_be_bytes());
+    frame.extend_from_slice(text.as_bytes());
+    socket.write_all(&frame).await?;
+    socket.flush().await?;
+    Ok(())
+}
+
+/// Read a request. A refused one still has its id, 0 if it was unreadable.
+async fn read_request<S: AsyncRead + Unpin>(
+    socket: &mut S,
+) -> Result<(u64, String), (u64, DmError)> {
+    let mut header = [0u8; 13];
+    socket
+        .read_exact(&mut header[..1])
+        .await
+        .map_err(|e| (0, e.into()))?;
+    if header[0] != VERSION {
+        return Err((0, DmError::Version(header[0])));
+    }
+    socket
+        .read_exact(&mut header[1..])
+        .await
+        .map_err(|e| (0, e.into()))?;
+    let id = u64::from_be_bytes(header[1..9].try_into().unwrap());
+    let len = u32::from_be_bytes(header[9..].try_into().unwrap()) as usize;
+    // Refused before reading, so no more than the limit is ever allocated
+    if len > MAX_MESSAGE_SIZE {
+        return Err((id, DmError::TooLarge(len)));
+    }
+    let mut text = vec![0u8; len];
+    socket
+        .read_exact(&mut text)
+        .await
+        .map_err(|e| (id, e.into()))?;
+    String::from_utf8(text)
+        .map(|text| (id, text))
+        .map_err(|_| (id, DmError::Malformed))
+}
+
+/// Response: version, the id of the request, then the `Ack` as a byte
+async fn write_response<S: AsyncWrite + Unpin>(
+    socket: &mut S,
+    id: u64,
+    ack: Ack,
+) -> io::Result<()> {
+    let mut frame = vec![VERSION];
+    frame.extend_from_slice(&id.to_be_bytes());
+    frame.push(ack as u8);
+    socket.write_all(&frame).await?;
+    socket.flush().await
+}
+
+async fn read_response<S: AsyncRead + Unpin>(socket: &mut S) -> Result<(u64, Ack), DmError> {
+    let mut frame = [0u8; 10];
+    socket.read_exact(&mut frame[..1]).await?;
+    if frame[0] != VERSION {
+        return Err(DmError::Version(frame[0]));
+    }
+    socket.read_exact(&mut frame[1..]).await?;
+    let id = u64::from_be_bytes(frame[1..9].try_into().unwrap());
+    let ack = Ack::from_byte(frame[9]).ok_or(DmError::Malformed)?;
+    Ok((id, ack))
+}
+
+/// Send one message and wait for its acknowledgement
+async fn send<S: AsyncRead + AsyncWrite + Unpin>(
+    socket: &mut S,
+    id: u64,
+    text: &str,
+) -> Result<(), DmError> {
+    write_request(socket, id, text).await?;
+    match read_response(socket).await? {
+        (ack_id, Ack::Delivered) if ack_id == id => Ok(()),
+        (_, Ack::Delivered) => Err(DmError::Malformed),
+        (_, ack) => Err(DmError::Rejected(ack)),
+    }
+}
+
+/// What a connection's handler reports about its streams
+#[derive(Debug)]
+pub enum HandlerEvent {
+    Received {
+        id: u64,
+        result: Result<String, DmError>,
+    },
+    Sent {
+        id: u64,
+        result: Result<(), DmError>,
+    },
+}
+
+/// A message to send, one stream each
+#[derive(Clone, Debug)]
+pub struct DirectMessage {
+    id: u64,
+    text: String,
+}
+
+impl UpgradeInfo for DirectMessage {
+    type Info = StreamProtocol;
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(PROTOCOL)
+    }
+}
+
+impl<TSocket> OutboundUpgrade<TSocket> for DirectMessage
+where
+    TSocket: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    type Output = HandlerEvent;
+    type Error = io::Error;
+    type Future = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+    // Failures are part of the output rather than errors, since the handler
+    // would neither say which message an error was for nor free its slot
+    fn upgrade_outbound(self, mut socket: TSocket, _info: Self::Info) -> Self::Future {
+        Box::pin(async move {
+            let result = time::timeout(ACK_TIMEOUT, send(&mut socket, self.id, &self.text))
+                .await
+                .unwrap_or(Err(DmError::Timeout));
+            let _ = socket.close().await;
+            Ok(HandlerEvent::Sent {
+                id: self.id,
+                result,
+            })
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DirectMessageProtocol;
+
+impl UpgradeInfo for DirectMessageProtocol {
+    type Info = StreamProtocol;
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(PROTOCOL)
+    }
+}
+
+impl<TSocket> InboundUpgrade<TSocket> for DirectMessageProtocol
+where
+    TSocket: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    type Output = HandlerEvent;
+    type Error = io::Error;
+    type Future = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+    fn upgrade_inbound(self, mut socket: TSocket, _: Self::Info) -> Self::Future {
+        Box::pin(async move {
+            let (id, result) = match read_request(&mut socket).await {
+                Ok((id, text)) => (id, Ok(text)),
+                Err((id, error)) => (id, Err(error)),
+            };
+            let ack = match &result {
+                Ok(_) => Some(Ack::Delivered),
+                Err(error) => error.ack(),
+            };
+            // Only acknowledged messages are shown, so a sender that did not
+            // get one may send it again
+            if let Some(ack) = ack {
+                write_response(&mut socket, id, ack).await?;
+            }
+            let _ = socket.close().await;
+            Ok(HandlerEvent::Received { id, result })
+        })
+    }
+}
+
+/// Direct messages over `PROTOCOL`, one stream each. Outgoing messages wait
+/// in the outbox until their peer acknowledges them, dialling it as needed.
+pub struct DirectMessageBehaviour {
+    events: VecDeque<DirectMessageEvent>,
+    /// Messages waiting for their peer or their acknowledgement
+    outbox: Outbox,
+    connected_peers: HashMap<PeerId, ConnectionId>,
+    /// Where peers were reached or announced, to dial them again
+    addresses: HashMap<PeerId, Vec<Multiaddr>>,
+    /// Messages on the wire and the connection they went on
+    in_flight: HashMap<u64, (PeerId, ConnectionId)>,
+    /// Messages received lately. One whose acknowledgement got lost is
+    /// sent again, and shown only once.
+    seen: VecDeque<(PeerId, u64)>,
+    /// Wakes `poll` when a queue's backoff runs out
+    retry: time::Interval,
+}
+
+/// How many received message ids are remembered
+const SEEN: usize = 1024;
+
+#[derive(Debug)]
+pub enum DirectMessageEvent {
+    /// `peer` sent us `text`, and we acknowledged it
+    Received { peer: PeerId, id: u64, text: String },
+    /// We refused a message of `peer`
+    Refused { peer: PeerId, error: DmError },
+    /// Message `id` to `peer` moved on
+    Delivery {
+        peer: PeerId,
+        id: u64,
+        state: Delivery,
+    },
+    /// The outbox could not be written, so messages may not outlive a restart
+    Unsaved(String),
+}
+
+impl DirectMessageBehaviour {
+    pub fn new(outbox: Outbox) -> Self {
+        Self {
+            events: VecDeque::new(),
+            outbox,
+            connected_peers: HashMap::new(),
+            addresses: HashMap::new(),
+            in_flight: HashMap::new(),
+            seen: VecDeque::new(),
+            retry: time::interval(Duration::from_secs(1)),
+        }
+    }
+
+    /// Queue `message`, to be reported by its id from here on
+    pub fn send_message(&mut self, message: Outgoing) -> Result<(), DmError> {
+        if message.text.len() > MAX_MESSAGE_SIZE {
+            return Err(DmError::TooLarge(message.text.len()));
+        }
+        self.outbox.push(message, Instant::now());
+        Ok(())
+    }
+
+    /// Message `id` to `peer` was acknowledged, or failed with `result`
+    fn settle(&mut self, peer: PeerId, id: u64, result: Result<(), DmError>) {
+        self.in_flight.remove(&id);
+        let state = match result {
+            Ok(()) if self.outbox.acknowledged(&peer.to_string(), id) => Delivery::Acknowledged,
+            Ok(()) => return,
+            // The peer will refuse it again, however often it is sent
+            Err(error) => {
+                let retry = !matches!(error, DmError::Rejected(_) | DmError::TooLarge(_));
+                self.outbox.failed(
+                    &peer.to_string(),
+                    id,
+                    error.to_string(),
+                    retry,
+                    Instant::now(),
+                )
+            }
+        };
+        self.events
+            .push_back(DirectMessageEvent::Delivery { peer, id, state });
+    }
+
+    fn on_connection_established(&mut self, peer: PeerId, connection: ConnectionId) {
+        self.connected_peers.insert(peer, connection);
+        self.outbox.connected(&peer.to_string(), Instant::now());
+    }
+
+    fn on_connection_closed(&mut self, peer: &PeerId, connection: ConnectionId) {
+        if self.connected_peers.get(peer) == Some(&connection) {
+            self.connected_peers.remove(peer);
+        }
+        // Their streams went with the connection
+        let lost = self
+            .in_flight
+            .iter()
+            .filter(|(_, (_, c))| *c == connection)
+            .map(|(id, _)| *id)
+            .collect::<Vec<u64>>();
+        for id in lost {
+            self.settle(*peer, id, Err(DmError::Closed));
+        }
+    }
+
+    fn on_dial_failure(&mut self, DialFailure { peer_id, error, .. }: DialFailure) {
+        let Some(peer_id) = peer_id else {
+            return;
+        };
+
+        if self.connected_peers.contains_key(&peer_id) {
+            return;
+        }
+        // Its queue already waits from when it was dialled
+        if let Some(id) = self.outbox.head(&peer_id.to_string()) {
+            self.events.push_back(DirectMessageEvent::Delivery {
+                peer: peer_id,
+                id,
+                state: Delivery::Queued(Some(format!("could not reach the peer: {}", error))),
+            });
+        }
+    }
+}
+
+impl NetworkBehaviour for DirectMessageBehaviour {
+    type ConnectionHandler = OneShotHandler<DirectMessageProtocol, DirectMessage, HandlerEvent>;
+    type ToSwarm = DirectMessageEvent;
+
+    fn handle_pending_outbound_connection(
+        &mut self,
+        _connection_id: ConnectionId,
+        maybe_peer: Option<PeerId>,
+        _addresses: &[Multiaddr],
+        _effective_role: Endpoint,
+    ) -> Result<Vec<Multiaddr>, ConnectionDenied> {
+        Ok(maybe_peer
+            .and_then(|peer| self.addresses.get(&peer))
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        _local_addr: &Multiaddr,
+        _remote_addr: &Multiaddr,
+    ) -> Result<Self::ConnectionHandler, ConnectionDenied> {
+        self.on_connection_established(peer, connection_id);
+        Ok(handler())
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        addr: &Multiaddr,
+        _role_override: Endpoint,
+        _port_use: PortUse,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        let known = self.addresses.entry(peer).or_default();
+        if !known.contains(addr) {
+            known.push(addr.clone());
+        }
+        self.on_connection_established(peer, connection_id);
+        Ok(handler())
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm) {
+        match event {
+            FromSwarm::ConnectionClosed(info) => {
+                self.on_connection_closed(&info.peer_id, info.connection_id)
+            }
+            FromSwarm::DialFailure(info) => self.on_dial_failure(info),
+            FromSwarm::NewExternalAddrOfPeer(info) => {
+                let known = self.addresses.entry(info.peer_id).or_default();
+                if !known.contains(info.addr) {
+                    known.push(info.addr.clone());
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn on_connection_handler_event(
+        &mut self,
+        peer: PeerId,
+        connection_id: ConnectionId,
+        event: THandlerOutEvent<Self>,
+    ) {
+        match event {
+            Ok(HandlerEvent::Received {
+                id,
+                result: Ok(text),
+            }) => {
+                if self.seen.contains(&(peer, id)) {
+                    return;
+                }
+                if self.seen.len() == SEEN {
+                    self.seen.pop_front();
+                }
+                self.seen.push_back((peer, id));
+                self.events
+                    .push_back(DirectMessageEvent::Received { peer, id, text });
+            }
+            Ok(HandlerEvent::Received {
+                result: Err(error), ..
+            }) => {
+                self.events
+                    .push_back(DirectMessageEvent::Refused { peer, error });
+            }
+            Ok(HandlerEvent::Sent { id, result }) => self.settle(peer, id, result),
+            // Only one message a peer is on the wire, so a stream that
+            // failed to open was for the one on this connection
+            Err(error) => {
+                let id = self
+                    .in_flight
+                    .iter()
+                    .find(|(_, (_, c))| *c == connection_id)
+                    .map(|(id, _)| *id);
+                let error = match error {
+                    StreamUpgradeError::Timeout => DmError::Timeout,
+                    StreamUpgradeError::NegotiationFailed => DmError::Unsupported,
+                    StreamUpgradeError::Apply(e) | StreamUpgradeError::Io(e) => e.into(),
+                };
+                if let Some(id) = id {
+                    self.settle(peer, id, Err(error));
+                }
+            }
+        }
+    }
+
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        // Registers for the next tick, so waiting queues are looked at again
+        while self.retry.poll_tick(cx).is_ready() {}
+        if let Err(e) = self.outbox.save() {
+            self.events
+                .push_back(DirectMessageEvent::Unsaved(e.to_string()));
+        }
+        if let Some(event) = self.events.pop_front() {
+            return Poll::Ready(ToSwarm::GenerateEvent(event));
+        }
+        let now = Instant::now();
+        for message in self.outbox.due(now) {
+            let Ok(peer) = message.peer.parse::<PeerId>() else {
+                self.outbox.failed(
+                    &message.peer,
+                    message.id,
+                    "not a peer id".to_string(),
+                    false,
+                    now,
+                );
+                continue;
+            };
+            let Some(&connection_id) = self.connected_peers.get(&peer) else {
+                // Dialling holds the queue off until the peer connects
+                self.outbox.unreachable(&message.peer, now);
+                return Poll::Ready(ToSwarm::Dial {
+                    opts: DialOpts::peer_id(peer).build(),
+                });
+            };
+            self.outbox.sent(&message.peer);
+            self.in_flight.insert(message.id, (peer, connection_id));
+            self.events.push_back(DirectMessageEvent::Delivery {
+                peer,
+                id: message.id,
+                state: Delivery::Sent,
+            });
+            return Poll::Ready(ToSwarm::NotifyHandler {
+                peer_id: peer,
+                handler: NotifyHandler::One(connection_id),
+                event: DirectMessage {
+                    id: message.id,
+                    text: message.text,
+                },
+            });
+        }
+        Poll::Pending
+    }
+}
+
+/// A handler that gives up on streams the way `ACK_TIMEOUT` says
+fn handler() -> OneShotHandler<DirectMessageProtocol, DirectMessage, HandlerEvent> {
+    // The outbound upgrade times out by itself, and reports which message
+    let config = OneShotHandlerConfig {
+        outbound_substream_timeout: ACK_TIMEOUT * 2,
+        ..OneShotHandlerConfig::default()
+    };
+    OneShotHandler::new(
+        SubstreamProtocol::new(DirectMessageProtocol, ()).with_timeout(ACK_TIMEOUT),
+        config,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::io::Cursor;
+
+    #[tokio::test]
+    async fn test_frames() {
+        let text = "long ".repeat(100_000);
+        let mut wire = Cursor::new(Vec::new());
+        write_request(&mut wire, 7, &text).await.unwrap();
+        write_response(&mut wire, 7, Ack::TooLarge).await.unwrap();
+        wire.set_position(0);
+        // Read whole however the bytes arrive, where one `read` fell short
+        assert_eq!(read_request(&mut wire).await.unwrap(), (7, text));
+        assert_eq!(read_response(&mut wire).await.unwrap(), (7, Ack::TooLarge));
+
+        let over = "x".repeat(MAX_MESSAGE_SIZE + 1);
+        let mut wire = Cursor::new(Vec::new());
+        let sent = write_request(&mut wire, 8, &over).await;
+        assert!(matches!(sent, Err(DmError::TooLarge(_))));
+        assert!(wire.get_ref().is_empty());
+
+        // A peer claiming more than the limit is refused before its text
+        let mut frame = vec![VERSION];
+        frame.extend_from_slice(&9u64.to_be_bytes());
+        frame.extend_from_slice(&u32::MAX.to_be_bytes());
+        let read = read_request(&mut Cursor::new(frame)).await;
+        assert!(matches!(read, Err((9, DmError::TooLarge(_)))));
+        let read = read_request(&mut Cursor::new(vec![VERSION + 1])).await;
+        assert!(matches!(read, Err((0, DmError::Version(_)))));
+        assert_eq!(read.unwrap_err().1.ack(), Some(Ack::Unsupported));
+    }
+}
@@ -0,0 +1,311 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::{self, BufRead, BufReader, Seek, SeekFrom, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// A message as kept in the history
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Record<T> {
+    /// Gossipsub message id, or the id of a direct message in hex
+    pub id: String,
+    /// Milliseconds since the Unix epoch when it arrived or was sent
+    pub time: u64,
+    /// Peer id of the sender, which is empty for our own messages sent
+    /// before the swarm knew its id
+    pub source: String,
+    pub item: T,
+}
+
+/// Milliseconds since the Unix epoch, what records are stamped with
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+/// Key of the history of a room
+pub fn room_key(room: &str) -> String {
+    // Room names may hold `/` and the like, peer ids never do
+    let name = room
+        .bytes()
+        .map(|b| match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' => (b as char).to_string(),
+            b => format!("%{:02x}", b),
+        })
+        .collect::<String>();
+    format!("room-{}", name)
+}
+
+/// Key of the direct conversation with a peer
+pub fn direct_key(peer: &str) -> String {
+    format!("dm-{}", peer)
+}
+
+/// Where each record of a file starts, and the ids in it
+#[derive(Default)]
+struct Index {
+    /// Time and byte offset of every record, in the order appended. Times
+    /// never go down, so they can be searched by bisection.
+    records: Vec<(u64, u64)>,
+    ids: HashSet<String>,
+    /// Bytes in the file
+    len: u64,
+    /// The file ends in a line cut short, which the next record must not
+    /// be appended to
+    torn: bool,
+}
+
+/// Messages of every room and conversation, each appended to a file of
+/// JSON lines of its own in `dir`. A file is indexed the first time it is
+/// used, so later reads seek to just the records asked for.
+pub struct History {
+    dir: PathBuf,
+    indexes: HashMap<String, Index>,
+}
+
+impl History {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            indexes: HashMap::new(),
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.jsonl", key))
+    }
+
+    fn index(&mut self, key: &str) -> io::Result<&mut Index> {
+        if !self.indexes.contains_key(key) {
+            let index = scan(&self.path(key))?;
+            self.indexes.insert(key.to_string(), index);
+        }
+        Ok(self.indexes.get_mut(key).expect("indexed above"))
+    }
+
+    /// Keys there is a history of
+    pub fn keys(&self) -> Vec<String> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return vec![];
+        };
+        let mut keys = entries
+            .filter_map(|e| {
+                let name = e.ok()?.file_name().into_string().ok()?;
+                name.strip_suffix(".jsonl").map(str::to_string)
+            })
+            .collect::<Vec<String>>();
+        keys.sort();
+        keys
+    }
+
+    /// Number of records of `key`
+    pub fn len(&mut self, key: &str) -> io::Result<usize> {
+        Ok(self.index(key)?.records.len())
+    }
+
+    /// Position of the first record of `key` at or after `time`
+    pub fn at(&mut self, key: &str, time: u64) -> io::Result<usize> {
+        let records = &self.index(key)?.records;
+        Ok(records.partition_point(|(t, _)| *t < time))
+    }
+
+    /// Append `record` unless one with its id is there already. Returns
+    /// whether it was new. It is stamped no earlier than the record before
+    /// it, as the clock may have been set back since.
+    pub fn append<T: Serialize>(&mut self, key: &str, record: &Record<T>) -> io::Result<bool> {
+        let path = self.path(key);
+        let index = self.index(key)?;
+        if index.ids.contains(&record.id) {
+            return Ok(false);
+        }
+        let last = index.records.last().map_or(0, |(t, _)| *t);
+        let record = Record {
+            id: record.id.clone(),
+            time: record.time.max(last),
+            source: record.source.clone(),
+            item: &record.item,
+        };
+        let mut line = serde_json::to_string(&record)? + "\n";
+        if index.torn {
+            line.insert(0, '\n');
+            index.len += 1;
+        }
+        fs::create_dir_all(path.with_file_name(""))?;
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?;
+        file.write_all(line.as_bytes())?;
+        index.records.push((record.time, index.len));
+        index.ids.insert(record.id.clone());
+        index.len += line.trim_start_matches('\n').len() as u64;
+        index.torn = false;
+        Ok(true)
+    }
+
+    /// Records at positions `range` of `key`, oldest first
+    pub fn read<T: DeserializeOwned>(
+        &mut self,
+        key: &str,
+        range: Range<usize>,
+    ) -> io::Result<Vec<Record<T>>> {
+        let records = self.read_at(key, range)?;
+        Ok(records.into_iter().map(|(_, record)| record).collect())
+    }
+
+    /// Records at positions `range` of `key` with their positions. Those
+    /// that do not decode as `T`, e.g. written by a newer build, are left
+    /// out.
+    fn read_at<T: DeserializeOwned>(
+        &mut self,
+        key: &str,
+        range: Range<usize>,
+    ) -> io::Result<Vec<(usize, Record<T>)>> {
+        let path = self.path(key);
+        let index = self.index(key)?;
+        let start = range.start;
+        let Some(offsets) = index.records.get(range) else {
+            return Ok(vec![]);
+        };
+        if offsets.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut reader = BufReader::new(fs::File::open(path)?);
+        let mut records = Vec::with_capacity(offsets.len());
+        let mut line = String::new();
+        // Lines that did not parse are not indexed, so skip to each record
+        for (i, (_, offset)) in offsets.iter().enumerate() {
+            reader.seek(SeekFrom::Start(*offset))?;
+            line.clear();
+            reader.read_line(&mut line)?;
+            if let Ok(record) = serde_json::from_str(&line) {
+                records.push((start + i, record));
+            }
+        }
+        Ok(records)
+    }
+
+    /// Position of the newest record of `key` before position `end` that
+    /// `matches`
+    pub fn search<T: DeserializeOwned>(
+        &mut self,
+        key: &str,
+        end: usize,
+        matches: impl Fn(&Record<T>) -> bool,
+    ) -> io::Result<Option<usize>> {
+        let end = end.min(self.len(key)?);
+        let records = self.read_at::<T>(key, 0..end)?;
+        Ok(records
+            .iter()
+            .rev()
+            .find(|(_, record)| matches(record))
+            .map(|(position, _)| *position))
+    }
+}
+
+/// Index the file at `path`, which need not exist. A line cut short by a
+/// crash is left out, and a time earlier than the one before is taken as
+/// that one.
+fn scan(path: &Path) -> io::Result<Index> {
+    #[derive(Deserialize)]
+    struct Head {
+        id: String,
+        time: u64,
+    }
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let mut index = Index::default();
+    for line in text.split_inclusive('\n') {
+        if let Ok(head) = serde_json::from_str::<Head>(line) {
+            let last = index.records.last().map_or(0, |(t, _)| *t);
+            index.records.push((head.time.max(last), index.len));
+            index.ids.insert(head.id);
+        }
+        index.len += line.len() as u64;
+    }
+    index.torn = !text.is_empty() && !text.ends_with('\n');
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history() {
+        let dir = std::env::temp_dir().join("cube-tui-history-test");
+        let _ = fs::remove_dir_all(&dir);
+        let key = room_key("cube-tui/oh");
+        assert_eq!(key, "room-cube-tui%2foh");
+        let record = |id: usize, time: u64| Record {
+            id: id.to_string(),
+            time,
+            source: "ana".to_string(),
+            item: format!("message {id}\nover two lines"),
+        };
+
+        let mut history = History::new(&dir);
+        for id in 0..10 {
+            assert!(history
+                .append(&key, &record(id, 1000 + id as u64 * 10))
+                .unwrap());
+        }
+        // The same id again, as gossip may bring it
+        assert!(!history.append(&key, &record(3, 2000)).unwrap());
+
+        // A fresh index finds the same records, and a torn last line is
+        // not one of them
+        let path = dir.join(format!("{key}.jsonl"));
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"id":"10","ti"#).unwrap();
+        let mut history = History::new(&dir);
+        assert_eq!(history.len(&key).unwrap(), 10);
+        assert!(history.append(&key, &record(10, 1100)).unwrap());
+        let mut history = History::new(&dir);
+        assert_eq!(history.len(&key).unwrap(), 11);
+        assert_eq!(history.at(&key, 1035).unwrap(), 4);
+        let page = history.read::<String>(&key, 8..11).unwrap();
+        assert_eq!(
+            page,
+            vec![record(8, 1080), record(9, 1090), record(10, 1100)]
+        );
+        assert!(history.read::<String>(&key, 8..20).unwrap().is_empty());
+
+        let found = history.search::<String>(&key, 10, |r| r.item.starts_with("message 2"));
+        assert_eq!(found.unwrap(), Some(2));
+        let found = history.search::<String>(&key, 2, |r| r.item.starts_with("message 2"));
+        assert_eq!(found.unwrap(), None);
+        // A record that does not decode, as from a newer build, is skipped
+        let odd = Record {
+            id: "11".to_string(),
+            time: 1110,
+            source: "ana".to_string(),
+            item: 11,
+        };
+        assert!(history.append(&key, &odd).unwrap());
+        assert!(history.append(&key, &record(12, 1120)).unwrap());
+        let page = history.read::<String>(&key, 10..13).unwrap();
+        assert_eq!(page, vec![record(10, 1100), record(12, 1120)]);
+        let found = history.search::<String>(&key, 13, |r| r.item.starts_with("message 1"));
+        assert_eq!(found.unwrap(), Some(12));
+        assert_eq!(history.len(&direct_key("ana")).unwrap(), 0);
+        assert_eq!(history.keys(), vec![key]);
+
+        // A clock set back does not stamp a record before the one above it
+        assert!(history.append(&key, &record(13, 1000)).unwrap());
+        assert_eq!(history.at(&key, 1121).unwrap(), 14);
+        let page = history.read::<String>(&key, 13..14).unwrap();
+        assert_eq!(page, vec![record(13, 1120)]);
+        let mut history = History::new(&dir);
+        assert_eq!(history.at(&key, 1121).unwrap(), 14);
+    }
+}
@@ -0,0 +1,179 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use libp2p::identity::Keypair;
+
+/// The ed25519 keypair kept at `path`, created on first use so our peer id
+/// stays the same across runs. Only the owner may read the file.
+pub fn load_or_create(path: &Path) -> io::Result<Keypair> {
+    if let Ok(bytes) = fs::read(path) {
+        restrict(path)?;
+        return Keypair::from_protobuf_encoding(&bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+    }
+    let keypair = Keypair::generate_ed25519();
+    let bytes = keypair.to_protobuf_encoding().map_err(io::Error::other)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    io::Write::write_all(&mut options.open(path)?, &bytes)?;
+    Ok(keypair)
+}
+
+#[cfg(unix)]
+fn restrict(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let permissions = fs::metadata(path)?.permissions();
+    match permissions.mode() & 0o077 {
+        0 => Ok(()),
+        _ => fs::set_permissions(path, fs::Permissions::from_mode(0o600)),
+    }
+}
+
+#[cfg(not(unix))]
+fn restrict(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// The tail of a peer id, which is where ids differ
+pub fn short_id(peer: &str) -> String {
+    match peer.char_indices().rev().nth(7) {
+        Some((i, _)) if i > 0 => format!("…{}", &peer[i..]),
+        _ => peer.to_string(),
+    }
+}
+
+/// Nicknames of peer ids, one tab separated `peer nickname` line each
+pub struct AddressBook {
+    pub names: BTreeMap<String, String>,
+    path: PathBuf,
+}
+
+impl AddressBook {
+    /// The book kept at `path`, empty if there is none yet
+    pub fn load(path: &Path) -> Self {
+        let names = fs::read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .map(|(peer, name)| (peer.to_string(), name.to_string()))
+            .collect();
+        Self {
+            names,
+            path: path.to_path_buf(),
+        }
+    }
+
+    /// What to call `peer`: its nickname or the tail of its id
+    pub fn name(&self, peer: &str) -> String {
+        self.names
+            .get(peer)
+            .cloned()
+            .unwrap_or_else(|| short_id(peer))
+    }
+
+    /// The peer id we call `name`, by nickname only
+    pub fn peer(&self, name: &str) -> Option<String> {
+        self.names
+            .iter()
+            .find(|(_, n)| *n == name)
+            .map(|(p, _)| p.clone())
+    }
+
+    /// Call `peer` `name`, or forget its nickname when `name` is empty
+    pub fn set(&mut self, peer: &str, name: &str) -> io::Result<()> {
+        match name.trim() {
+            "" => self.names.remove(peer),
+            name => self.names.insert(peer.to_string(), name.to_string()),
+        };
+        let text = self
+            .names
+            .iter()
+            .map(|(peer, name)| format!("{}\t{}\n", peer, name))
+            .collect::<String>();
+        fs::write(&self.path, text)
+    }
+}
+
+/// Peer ids whose messages are dropped, one per line
+pub struct BlockList {
+    pub peers: BTreeSet<String>,
+    path: PathBuf,
+}
+
+impl BlockList {
+    /// The list kept at `path`, empty if there is none yet
+    pub fn load(path: &Path) -> Self {
+        let peers = fs::read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect();
+        Self {
+            peers,
+            path: path.to_path_buf(),
+        }
+    }
+
+    pub fn contains(&self, peer: &str) -> bool {
+        self.peers.contains(peer)
+    }
+
+    /// Block `peer`, or unblock it when `blocked` is false
+    pub fn set(&mut self, peer: &str, blocked: bool) -> io::Result<()> {
+        match blocked {
+            true => self.peers.insert(peer.to_string()),
+            false => self.peers.remove(peer),
+        };
+        let text = self
+            .peers
+            .iter()
+            .map(|peer| format!("{}\n", peer))
+            .collect::<String>();
+        fs::write(&self.path, text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identity_persists() {
+        let dir = std::env::temp_dir().join("cube-tui-identity-test");
+        let _ = fs::remove_dir_all(&dir);
+        let key = dir.join("identity");
+        let first = load_or_create(&key).unwrap();
+        let second = load_or_create(&key).unwrap();
+        assert_eq!(first.public().to_peer_id(), second.public().to_peer_id());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&key).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let peer = first.public().to_peer_id().to_string();
+        let mut book = AddressBook::load(&dir.join("peers"));
+        assert_eq!(book.name(&peer), short_id(&peer));
+        book.set(&peer, "ana").unwrap();
+        let book = AddressBook::load(&dir.join("peers"));
+        assert_eq!(book.name(&peer), "ana");
+        assert_eq!(book.peer("ana"), Some(peer.clone()));
+
+        let mut blocked = BlockList::load(&dir.join("blocked"));
+        blocked.set(&peer, true).unwrap();
+        assert!(BlockList::load(&dir.join("blocked")).contains(&peer));
+        blocked.set(&peer, false).unwrap();
+        assert!(BlockList::load(&dir.join("blocked")).peers.is_empty());
+    }
+}
@@ -1,26 +1,101 @@
+pub mod bootstrap;
+pub mod direct;
+pub mod history;
+pub mod identity;
+pub mod outbox;
+pub mod validate;
+pub mod wire;
+
 use futures::stream::StreamExt;
-use libp2p::{gossipsub, mdns, noise, swarm::NetworkBehaviour, swarm::SwarmEvent, tcp, yamux};
-use std::collections::hash_map::DefaultHasher;
+use libp2p::{
+    gossipsub,
+    identity::Keypair,
+    mdns, noise,
+    swarm::{behaviour::toggle::Toggle, dial_opts::DialOpts, NetworkBehaviour, SwarmEvent},
+    tcp, yamux, Multiaddr, PeerId,
+};
 use std::error::Error;
-use std::hash::{Hash, Hasher};
-use std::time::Duration;
-use tokio::{io, io::AsyncBufReadExt, select};
-use tracing_subscriber::EnvFilter;
+use std::time::{Duration, Instant};
+use tokio::{io, select, sync::mpsc, time};
+
+use crate::handlers::config::NetworkingConfig;
+use crate::handlers::event::{AppEvent, EventSender, NetworkEvent};
+use bootstrap::Bootstrap;
+use direct::{DirectMessageBehaviour, DirectMessageEvent};
+use identity::short_id;
+use outbox::{Delivery, Outbox, Outgoing};
+use validate::{Validator, Verdict};
+use wire::Envelope;
 
-// We create a custom network behaviour that combines Gossipsub and Mdns.
+// We create a custom network behaviour that combines Gossipsub, direct
+// messages and Mdns.
 #[derive(NetworkBehaviour)]
 struct MyBehaviour {
     gossipsub: gossipsub::Behaviour,
-    mdns: mdns::tokio::Behaviour,
+    direct_message: DirectMessageBehaviour,
+    /// Off where multicast does not reach, e.g. in containers
+    mdns: Toggle<mdns::tokio::Behaviour>,
+}
+
+/// How often bootstrap peers are checked for a redial
+const REDIAL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What the TUI asks of the chat task
+#[derive(Debug, Clone)]
+pub enum ChatCommand {
+    /// Publish an envelope in a room
+    Publish {
+        room: String,
+        envelope: Envelope,
+    },
+    /// Subscribe to the topic of a room
+    Join(String),
+    Leave(String),
+    /// Dial a peer by its multiaddr
+    Dial(Multiaddr),
+    /// Send a direct message, reported by its id as it goes
+    Direct(Outgoing),
+    /// Drop whatever a peer sends, or stop doing so
+    Block(PeerId),
+    Unblock(PeerId),
 }
 
-//#[tokio::main]
-pub async fn chat() -> Result<(), Box<dyn Error>> {
-    let _ = tracing_subscriber::fmt()
-        .with_env_filter(EnvFilter::from_default_env())
-        .try_init();
+/// Start the chat swarm as `keypair` in `rooms` on the current tokio
+/// runtime, sending the direct messages left in `outbox` and ignoring the
+/// peers in `blocked`. What it sees arrives as `AppEvent::Network` and it
+/// takes commands from the returned sender.
+pub fn spawn(
+    config: NetworkingConfig,
+    rooms: Vec<String>,
+    keypair: Keypair,
+    outbox: Outbox,
+    blocked: Vec<PeerId>,
+    events: EventSender,
+) -> mpsc::Sender<ChatCommand> {
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(async move {
+        let validator = Validator::new(blocked);
+        if let Err(e) = chat(config, rooms, keypair, outbox, validator, &events, rx).await {
+            let status = format!("chat stopped: {}", e);
+            events
+                .send(AppEvent::Network(NetworkEvent::Status(status)))
+                .await;
+        }
+    });
+    tx
+}
 
-    let mut swarm = libp2p::SwarmBuilder::with_new_identity()
+/// Run the swarm until the TUI drops its end of either channel
+async fn chat(
+    config: NetworkingConfig,
+    rooms: Vec<String>,
+    keypair: Keypair,
+    outbox: Outbox,
+    mut validator: Validator,
+    events: &EventSender,
+    mut commands: mpsc::Receiver<ChatCommand>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut swarm = libp2p::SwarmBuilder::with_existing_identity(keypair)
         .with_tokio()
         .with_tcp(
             tcp::Config::default(),
@@ -29,84 +104,458 @@ pub async fn chat() -> Result<(), Box<dyn Error>> {
         )?
         .with_quic()
         .with_behaviour(|key| {
-            // To content-address message, we can take the hash of message and use it as an ID.
-            let message_id_fn = |message: &gossipsub::Message| {
-                let mut s = DefaultHasher::new();
-                message.data.hash(&mut s);
-                gossipsub::MessageId::from(s.finish().to_string())
-            };
-
-            // Set a custom gossipsub configuration
-            let gossipsub_config = gossipsub::ConfigBuilder::default()
-                .heartbeat_interval(Duration::from_secs(10)) // This is set to aid debugging by not cluttering the log space
-                .validation_mode(gossipsub::ValidationMode::Strict) // This sets the kind of message validation. The default is Strict (enforce message signing)
-                .message_id_fn(message_id_fn) // content-address messages. No two messages of the same content will be propagated.
-                .build()
-                .map_err(|msg| io::Error::new(io::ErrorKind::Other, msg))?; // Temporary hack because `build` does not return a proper `std::error::Error`.
+            let gossipsub = gossipsub_behaviour(key, Duration::from_secs(config.heartbeat_secs))?;
 
-            // build a gossipsub network behaviour
-            let gossipsub = gossipsub::Behaviour::new(
-                gossipsub::MessageAuthenticity::Signed(key.clone()),
-                gossipsub_config,
-            )?;
-
-            let mdns =
-                mdns::tokio::Behaviour::new(mdns::Config::default(), key.public().to_peer_id())?;
-            Ok(MyBehaviour { gossipsub, mdns })
+            let mdns = match config.mdns {
+                true => Some(mdns::tokio::Behaviour::new(
+                    mdns::Config::default(),
+                    key.public().to_peer_id(),
+                )?),
+                false => None,
+            };
+            Ok(MyBehaviour {
+                gossipsub,
+                direct_message: DirectMessageBehaviour::new(outbox),
+                mdns: mdns.into(),
+            })
         })?
         .with_swarm_config(|c| c.with_idle_connection_timeout(Duration::from_secs(60)))
         .build();
 
-    // Create a Gossipsub topic
-    let topic = gossipsub::IdentTopic::new("test-net");
-    // subscribes to our topic
-    swarm.behaviour_mut().gossipsub.subscribe(&topic)?;
-
-    // Read full lines from stdin
-    let mut stdin = io::BufReader::new(io::stdin()).lines();
+    // Every room is a topic
+    for room in &rooms {
+        join(&mut swarm.behaviour_mut().gossipsub, room)?;
+    }
+    for peer in validator.blocked() {
+        swarm.behaviour_mut().gossipsub.blacklist_peer(peer);
+    }
 
     // Listen on all interfaces and whatever port the OS assigns
-    swarm.listen_on("/ip4/0.0.0.0/udp/0/quic-v1".parse()?)?;
-    swarm.listen_on("/ip4/0.0.0.0/tcp/0".parse()?)?;
+    for addr in &config.listen_addrs {
+        swarm.listen_on(addr.parse()?)?;
+    }
+
+    let started = NetworkEvent::Started(swarm.local_peer_id().to_string());
+    if !events.send(AppEvent::Network(started)).await {
+        return Ok(());
+    }
 
-    println!("Enter messages via STDIN and they will be sent to connected peers using Gossipsub");
+    // Validated with the config, so only a bad `--peer` is skipped here
+    let addrs = config
+        .bootstrap_peers
+        .iter()
+        .filter_map(|a| a.parse().ok())
+        .collect();
+    let mut bootstrap = Bootstrap::new(addrs, Instant::now());
+    let mut redial = time::interval(REDIAL_INTERVAL);
 
     // Kick it off
     loop {
-        select! {
-            Ok(Some(line)) = stdin.next_line() => {
-                if let Err(e) = swarm
-                    .behaviour_mut().gossipsub
-                    .publish(topic.clone(), line.as_bytes()) {
-                    println!("Publish error: {e:?}");
+        let event = select! {
+            _ = redial.tick() => {
+                for addr in bootstrap.due(Instant::now()) {
+                    let opts = DialOpts::from(addr.clone());
+                    let id = opts.connection_id();
+                    bootstrap.dialing(&addr, id);
+                    if swarm.dial(opts).is_err() {
+                        bootstrap.lost(id, Instant::now());
+                    }
                 }
+                None
             }
+            command = commands.recv() => match command {
+                Some(ChatCommand::Publish { room, envelope }) => {
+                    let data = envelope.encode();
+                    let id = wire::message_id(&data);
+                    let result = swarm
+                        .behaviour_mut()
+                        .gossipsub
+                        .publish(gossipsub::IdentTopic::new(&room), data)
+                        .map(|_| ())
+                        .map_err(|e| format!("{e:?}"));
+                    Some(NetworkEvent::Published { topic: room, id, result })
+                }
+                Some(ChatCommand::Join(room)) => join(&mut swarm.behaviour_mut().gossipsub, &room)
+                    .err()
+                    .map(|e| NetworkEvent::Status(format!("could not join #{room}: {e}"))),
+                Some(ChatCommand::Leave(room)) => swarm
+                    .behaviour_mut()
+                    .gossipsub
+                    .unsubscribe(&gossipsub::IdentTopic::new(&room))
+                    .err()
+                    .map(|e| NetworkEvent::Status(format!("could not leave #{room}: {e:?}"))),
+                Some(ChatCommand::Dial(addr)) => match swarm.dial(addr.clone()) {
+                    Ok(()) => Some(NetworkEvent::Status(format!("dialing {addr}"))),
+                    Err(e) => Some(NetworkEvent::Status(format!("could not dial {addr}: {e}"))),
+                },
+                Some(ChatCommand::Direct(message)) => {
+                    let (peer, id) = (message.peer.clone(), message.id);
+                    let state = match message.peer.parse::<PeerId>() {
+                        Err(e) => Delivery::Failed(format!("not a peer id: {e}")),
+                        Ok(_) => match swarm.behaviour_mut().direct_message.send_message(message) {
+                            Ok(()) => Delivery::Queued(None),
+                            Err(e) => Delivery::Failed(e.to_string()),
+                        },
+                    };
+                    Some(NetworkEvent::Delivery { peer, id, state })
+                }
+                // Gossipsub drops what a blacklisted peer sends, the
+                // validator its messages that others forward
+                Some(ChatCommand::Block(peer)) => {
+                    swarm.behaviour_mut().gossipsub.blacklist_peer(&peer);
+                    validator.block(peer);
+                    None
+                }
+                Some(ChatCommand::Unblock(peer)) => {
+                    swarm.behaviour_mut().gossipsub.remove_blacklisted_peer(&peer);
+                    validator.unblock(&peer);
+                    None
+                }
+                None => return Ok(()),
+            },
             event = swarm.select_next_some() => match event {
                 SwarmEvent::Behaviour(MyBehaviourEvent::Mdns(mdns::Event::Discovered(list))) => {
-                    for (peer_id, multiaddr) in list {
-                        println!("mDNS discovered a new peer: {peer_id}");
-                        println!("{multiaddr}");
+                    for (peer_id, _multiaddr) in list {
                         swarm.behaviour_mut().gossipsub.add_explicit_peer(&peer_id);
                     }
+                    None
                 },
                 SwarmEvent::Behaviour(MyBehaviourEvent::Mdns(mdns::Event::Expired(list))) => {
                     for (peer_id, _multiaddr) in list {
-                        println!("mDNS discover peer has expired: {peer_id}");
                         swarm.behaviour_mut().gossipsub.remove_explicit_peer(&peer_id);
                     }
+                    None
                 },
                 SwarmEvent::Behaviour(MyBehaviourEvent::Gossipsub(gossipsub::Event::Message {
                     propagation_source: peer_id,
-                    message_id: id,
+                    message_id,
                     message,
-                })) => println!(
-                        "Got message: '{}' with id: {id} from peer: {peer_id}",
-                        String::from_utf8_lossy(&message.data),
+                })) => {
+                    let source = message.source.unwrap_or(peer_id);
+                    let verdict = validator.check(
+                        &source,
+                        &peer_id,
+                        &message.data,
+                        history::now(),
+                        Instant::now(),
+                    );
+                    // Gone from the cache when it took too long, nothing to do then
+                    let _ = swarm.behaviour_mut().gossipsub.report_message_validation_result(
+                        &message_id,
+                        &peer_id,
+                        verdict.acceptance(),
+                    );
+                    match verdict {
+                        Verdict::Accept(envelope) => Some(NetworkEvent::Message {
+                            id: wire::message_id(&message.data),
+                            topic: message.topic.to_string(),
+                            source: source.to_string(),
+                            envelope,
+                        }),
+                        Verdict::Ignore(why) | Verdict::Reject(why) => why.map(NetworkEvent::Status),
+                    }
+                }
+                SwarmEvent::Behaviour(MyBehaviourEvent::DirectMessage(
+                    DirectMessageEvent::Received { peer, .. },
+                )) if validator.is_blocked(&peer) => None,
+                SwarmEvent::Behaviour(MyBehaviourEvent::DirectMessage(event)) => Some(match event {
+                    DirectMessageEvent::Received { peer, id, text } => NetworkEvent::Direct {
+                        peer: peer.to_string(),
+                        id,
+                        text,
+                    },
+                    DirectMessageEvent::Refused { peer, error } => NetworkEvent::Status(
+                        format!("refused a direct message from {}: {error}", short_id(&peer.to_string())),
                     ),
+                    DirectMessageEvent::Delivery { peer, id, state } => NetworkEvent::Delivery {
+                        peer: peer.to_string(),
+                        id,
+                        state,
+                    },
+                    DirectMessageEvent::Unsaved(e) => {
+                        NetworkEvent::Status(format!("could not save the outbox: {e}"))
+                    }
+                }),
+                SwarmEvent::ConnectionEstablished { peer_id, connection_id, num_established, .. } => {
+                    bootstrap.established(connection_id);
+                    match num_established.get() {
+                        1 => Some(NetworkEvent::PeerJoined(peer_id.to_string())),
+                        _ => None,
+                    }
+                }
+                SwarmEvent::ConnectionClosed { peer_id, connection_id, num_established, .. } => {
+                    bootstrap.lost(connection_id, Instant::now());
+                    match num_established {
+                        0 => Some(NetworkEvent::PeerLeft(peer_id.to_string())),
+                        _ => None,
+                    }
+                }
+                SwarmEvent::OutgoingConnectionError { peer_id, connection_id, error } => {
+                    match (bootstrap.lost(connection_id, Instant::now()), peer_id) {
+                        (Some((addr, wait)), _) => Some(NetworkEvent::Status(format!(
+                            "could not reach {addr}, trying again in {}s",
+                            wait.as_secs()
+                        ))),
+                        (None, None) => Some(NetworkEvent::Status(format!("dial failed: {error}"))),
+                        (None, Some(_)) => None,
+                    }
+                }
                 SwarmEvent::NewListenAddr { address, .. } => {
-                    println!("Local node is listening on {address}");
+                    Some(NetworkEvent::Listening(address.to_string()))
                 }
-                _ => {}
+                _ => None,
+            }
+        };
+        if let Some(event) = event {
+            if !events.send(AppEvent::Network(event)).await {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Gossipsub as every swarm runs it: nothing is passed on before the
+/// validator accepts it, and peers are scored by what it rejects
+fn gossipsub_behaviour(
+    key: &Keypair,
+    heartbeat: Duration,
+) -> Result<gossipsub::Behaviour, Box<dyn Error + Send + Sync>> {
+    // The envelope's nonce keeps equal texts from sharing an id
+    let message_id_fn =
+        |message: &gossipsub::Message| gossipsub::MessageId::from(wire::message_id(&message.data));
+
+    // Set a custom gossipsub configuration
+    let gossipsub_config = gossipsub::ConfigBuilder::default()
+        .heartbeat_interval(heartbeat) // This is set to aid debugging by not cluttering the log space
+        .validation_mode(gossipsub::ValidationMode::Strict) // This sets the kind of message validation. The default is Strict (enforce message signing)
+        .validate_messages() // Nothing is passed on before the validator accepts it
+        .message_id_fn(message_id_fn)
+        .build()
+        .map_err(|msg| io::Error::new(io::ErrorKind::Other, msg))?; // Temporary hack because `build` does not return a proper `std::error::Error`.
+
+    // build a gossipsub network behaviour
+    let mut gossipsub = gossipsub::Behaviour::new(
+        gossipsub::MessageAuthenticity::Signed(key.clone()),
+        gossipsub_config,
+    )?;
+    // Scored so peers whose messages are rejected get pruned
+    let (params, thresholds) = validate::score_params();
+    gossipsub.with_peer_score(params, thresholds)?;
+    Ok(gossipsub)
+}
+
+/// Subscribe to the topic of `room`, scoring the peers in it
+fn join(
+    gossipsub: &mut gossipsub::Behaviour,
+    room: &str,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let topic = gossipsub::IdentTopic::new(room);
+    gossipsub.subscribe(&topic).map_err(|e| format!("{e:?}"))?;
+    gossipsub.set_topic_params(topic, validate::topic_params())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wire::Body;
+
+    /// An outbox of its own for every swarm
+    fn outbox(name: &str) -> Outbox {
+        let path = std::env::temp_dir().join(format!("cube-tui-chat-test-{name}"));
+        let _ = std::fs::remove_file(&path);
+        Outbox::load(&path, Instant::now())
+    }
+
+    /// The next network event, waiting at most ten seconds
+    async fn next(rx: &mut mpsc::Receiver<AppEvent>) -> NetworkEvent {
+        loop {
+            match time::timeout(Duration::from_secs(10), rx.recv()).await {
+                Ok(Some(AppEvent::Network(event))) => return event,
+                Ok(Some(_)) => (),
+                Ok(None) => panic!("chat task stopped"),
+                Err(_) => panic!("no network event in 10s"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_on_localhost() {
+        let config = NetworkingConfig {
+            listen_addrs: vec!["/ip4/127.0.0.1/tcp/0".to_string()],
+            mdns: false,
+            heartbeat_secs: 1,
+            ..NetworkingConfig::default()
+        };
+        let rooms = vec!["test-net".to_string()];
+
+        let (events, mut a) = EventSender::channel(64);
+        let _a = spawn(
+            config.clone(),
+            rooms.clone(),
+            Keypair::generate_ed25519(),
+            outbox("a"),
+            vec![],
+            events,
+        );
+        let NetworkEvent::Started(id_a) = next(&mut a).await else {
+            panic!("a did not start");
+        };
+        let addr = loop {
+            if let NetworkEvent::Listening(addr) = next(&mut a).await {
+                break addr;
+            }
+        };
+
+        // B finds A only through its bootstrap list
+        let config = NetworkingConfig {
+            bootstrap_peers: vec![format!("{addr}/p2p/{id_a}")],
+            ..config
+        };
+        let (events, mut b) = EventSender::channel(64);
+        let b_commands = spawn(
+            config,
+            rooms,
+            Keypair::generate_ed25519(),
+            outbox("b"),
+            vec![],
+            events,
+        );
+        loop {
+            if let NetworkEvent::PeerJoined(peer) = next(&mut b).await {
+                assert_eq!(peer, id_a);
+                break;
+            }
+        }
+
+        // Publishing fails until the subscriptions are exchanged
+        let envelope = Envelope::new(
+            "b",
+            Body::Chat {
+                text: "hi".to_string(),
+            },
+        );
+        let received = time::timeout(Duration::from_secs(20), async {
+            loop {
+                let publish = ChatCommand::Publish {
+                    room: "test-net".to_string(),
+                    envelope: envelope.clone(),
+                };
+                b_commands.send(publish).await.unwrap();
+                while let Ok(Some(event)) =
+                    time::timeout(Duration::from_millis(500), a.recv()).await
+                {
+                    if let AppEvent::Network(NetworkEvent::Message { envelope, .. }) = event {
+                        return envelope;
+                    }
+                }
+            }
+        })
+        .await
+        .expect("a never got the message");
+        assert_eq!(received.body, envelope.body);
+
+        // Direct messages share the connection and are acknowledged
+        let message = Outgoing::new(&id_a, "psst".to_string());
+        let id = message.id;
+        b_commands.send(ChatCommand::Direct(message)).await.unwrap();
+        loop {
+            if let NetworkEvent::Direct { text, .. } = next(&mut a).await {
+                assert_eq!(text, "psst");
+                break;
+            }
+        }
+        loop {
+            if let NetworkEvent::Delivery {
+                id: acked,
+                state: Delivery::Acknowledged,
+                ..
+            } = next(&mut b).await
+            {
+                assert_eq!(acked, id);
+                break;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejections_stop_gossip() {
+        let swarm = || {
+            libp2p::SwarmBuilder::with_new_identity()
+                .with_tokio()
+                .with_tcp(
+                    tcp::Config::default(),
+                    noise::Config::new,
+                    yamux::Config::default,
+                )
+                .unwrap()
+                .with_behaviour(|key| gossipsub_behaviour(key, Duration::from_secs(1)))
+                .unwrap()
+                .with_swarm_config(|c| c.with_idle_connection_timeout(Duration::from_secs(60)))
+                .build()
+        };
+        let (mut a, mut b) = (swarm(), swarm());
+        join(a.behaviour_mut(), "test-net").unwrap();
+        join(b.behaviour_mut(), "test-net").unwrap();
+        a.listen_on("/ip4/127.0.0.1/tcp/0".parse().unwrap())
+            .unwrap();
+        let addr = loop {
+            if let SwarmEvent::NewListenAddr { address, .. } = a.select_next_some().await {
+                break address;
+            }
+        };
+        b.dial(addr).unwrap();
+
+        let id_a = *a.local_peer_id();
+        let gossip_threshold = validate::score_params().1.gossip_threshold;
+        let mut validator = Validator::default();
+        for rejected in 1..=2 {
+            // Empty texts are rejected, and the nonce gives each its own id
+            let data = Envelope::new("a", Body::Chat { text: " ".into() }).encode();
+            let mut retry = time::interval(Duration::from_millis(200));
+            let mut published = false;
+            let (source, message_id, message) = time::timeout(Duration::from_secs(20), async {
+                loop {
+                    select! {
+                        // Publishing fails until the subscriptions are exchanged
+                        _ = retry.tick(), if !published => {
+                            let topic = gossipsub::IdentTopic::new("test-net");
+                            published = a.behaviour_mut().publish(topic, data.clone()).is_ok();
+                        }
+                        _ = a.select_next_some() => (),
+                        event = b.select_next_some() => {
+                            if let SwarmEvent::Behaviour(gossipsub::Event::Message {
+                                propagation_source,
+                                message_id,
+                                message,
+                            }) = event
+                            {
+                                return (propagation_source, message_id, message);
+                            }
+                        }
+                    }
+                }
+            })
+            .await
+            .expect("b never got the message");
+
+            let verdict = validator.check(
+                &id_a,
+                &source,
+                &message.data,
+                history::now(),
+                Instant::now(),
+            );
+            assert!(matches!(verdict, Verdict::Reject(_)));
+            let _ = b.behaviour_mut().report_message_validation_result(
+                &message_id,
+                &source,
+                verdict.acceptance(),
+            );
+            let score = b.behaviour().peer_score(&id_a).unwrap();
+            match rejected {
+                1 => assert!(score >= gossip_threshold, "{score} after one rejection"),
+                _ => assert!(score < gossip_threshold, "{score} after two rejections"),
             }
         }
     }
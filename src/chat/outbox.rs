@@ -0,0 +1,275 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::bootstrap::{FIRST_BACKOFF, MAX_BACKOFF};
+
+/// Failed sends over a live connection before a message is given up on.
+/// Peers that cannot be reached are waited for however long it takes.
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Where a direct message is on its way
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Delivery {
+    /// Waiting for its turn or its peer, with why the last try failed
+    Queued(Option<String>),
+    /// On the wire, waiting for the acknowledgement
+    Sent,
+    Acknowledged,
+    /// Given up on, with why
+    Failed(String),
+}
+
+impl fmt::Display for Delivery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Delivery::Queued(None) => write!(f, "queued"),
+            Delivery::Queued(Some(why)) => write!(f, "queued, {}", why),
+            Delivery::Sent => write!(f, "sent"),
+            Delivery::Acknowledged => write!(f, "acknowledged"),
+            Delivery::Failed(why) => write!(f, "failed, {}", why),
+        }
+    }
+}
+
+/// A direct message, as kept on disk
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Outgoing {
+    /// Random, so ids stay unique across restarts
+    pub id: u64,
+    pub peer: String,
+    pub text: String,
+}
+
+impl Outgoing {
+    /// `text` for `peer` under a new id
+    pub fn new(peer: &str, text: String) -> Self {
+        Self {
+            id: rand::random(),
+            peer: peer.to_string(),
+            text,
+        }
+    }
+}
+
+struct Queue {
+    messages: VecDeque<Outgoing>,
+    /// The first message is on the wire
+    sending: bool,
+    /// Failed sends of the first message
+    attempts: u32,
+    backoff: Duration,
+    next: Instant,
+}
+
+impl Queue {
+    /// Hold off the queue, longer each time
+    fn wait(&mut self, now: Instant) -> Duration {
+        let wait = self.backoff;
+        self.next = now + wait;
+        self.backoff = (wait * 2).min(MAX_BACKOFF);
+        wait
+    }
+}
+
+/// Direct messages not acknowledged yet. Each peer has a queue sent one
+/// message at a time, in order. All of them are kept at `path`, one JSON
+/// message a line, so they outlive restarts.
+pub struct Outbox {
+    queues: BTreeMap<String, Queue>,
+    path: PathBuf,
+    /// Changed since the last `save`
+    dirty: bool,
+}
+
+impl Outbox {
+    /// The messages left at `path`, all due right away
+    pub fn load(path: &Path, now: Instant) -> Self {
+        let mut outbox = Self {
+            queues: BTreeMap::new(),
+            path: path.to_path_buf(),
+            dirty: false,
+        };
+        let text = fs::read_to_string(path).unwrap_or_default();
+        for message in text
+            .lines()
+            .filter_map(|l| serde_json::from_str::<Outgoing>(l).ok())
+        {
+            outbox.queue(&message.peer, now).messages.push_back(message);
+        }
+        outbox
+    }
+
+    fn queue(&mut self, peer: &str, now: Instant) -> &mut Queue {
+        self.queues
+            .entry(peer.to_string())
+            .or_insert_with(|| Queue {
+                messages: VecDeque::new(),
+                sending: false,
+                attempts: 0,
+                backoff: FIRST_BACKOFF,
+                next: now,
+            })
+    }
+
+    /// Queue `message` behind the others to its peer
+    pub fn push(&mut self, message: Outgoing, now: Instant) {
+        self.queue(&message.peer, now).messages.push_back(message);
+        self.dirty = true;
+    }
+
+    /// The next message of every queue that may send now
+    pub fn due(&self, now: Instant) -> Vec<Outgoing> {
+        self.queues
+            .values()
+            .filter(|q| !q.sending && q.next <= now)
+            .filter_map(|q| q.messages.front().cloned())
+            .collect()
+    }
+
+    /// The message at the front of `peer`'s queue
+    pub fn head(&self, peer: &str) -> Option<u64> {
+        self.queues.get(peer)?.messages.front().map(|m| m.id)
+    }
+
+    /// The front of `peer`'s queue went on the wire
+    pub fn sent(&mut self, peer: &str) {
+        if let Some(queue) = self.queues.get_mut(peer) {
+            queue.sending = true;
+        }
+    }
+
+    /// `peer` was dialled or could not be reached. Returns how long its
+    /// queue now waits, if it has one.
+    pub fn unreachable(&mut self, peer: &str, now: Instant) -> Option<Duration> {
+        let queue = self.queues.get_mut(peer)?;
+        Some(queue.wait(now))
+    }
+
+    /// `peer` is connected, so its queue goes now
+    pub fn connected(&mut self, peer: &str, now: Instant) {
+        if let Some(queue) = self.queues.get_mut(peer) {
+            queue.backoff = FIRST_BACKOFF;
+            queue.next = now;
+        }
+    }
+
+    /// `peer` acknowledged message `id`. Returns whether it was ours.
+    pub fn acknowledged(&mut self, peer: &str, id: u64) -> bool {
+        let Some(queue) = self.queues.get_mut(peer) else {
+            return false;
+        };
+        if queue.messages.front().map(|m| m.id) != Some(id) {
+            return false;
+        }
+        queue.messages.pop_front();
+        queue.sending = false;
+        queue.attempts = 0;
+        queue.backoff = FIRST_BACKOFF;
+        if queue.messages.is_empty() {
+            self.queues.remove(peer);
+        }
+        self.dirty = true;
+        true
+    }
+
+    /// Sending message `id` to `peer` failed. It is tried again after a
+    /// while unless `retry` is false or it failed too often. Returns where
+    /// that leaves it.
+    pub fn failed(
+        &mut self,
+        peer: &str,
+        id: u64,
+        why: String,
+        retry: bool,
+        now: Instant,
+    ) -> Delivery {
+        let Some(queue) = self.queues.get_mut(peer) else {
+            return Delivery::Failed(why);
+        };
+        if queue.messages.front().map(|m| m.id) != Some(id) {
+            return Delivery::Failed(why);
+        }
+        queue.sending = false;
+        queue.attempts += 1;
+        if retry && queue.attempts < MAX_ATTEMPTS {
+            let wait = queue.wait(now);
+            return Delivery::Queued(Some(format!(
+                "{}, trying again in {}s",
+                why,
+                wait.as_secs()
+            )));
+        }
+        queue.messages.pop_front();
+        queue.attempts = 0;
+        if queue.messages.is_empty() {
+            self.queues.remove(peer);
+        }
+        self.dirty = true;
+        Delivery::Failed(why)
+    }
+
+    /// Write the messages to disk if they changed
+    pub fn save(&mut self) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let text = self
+            .queues
+            .values()
+            .flat_map(|q| &q.messages)
+            .map(|m| serde_json::to_string(m).expect("messages always serialize") + "\n")
+            .collect::<String>();
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, text)?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outbox() {
+        let path = std::env::temp_dir().join("cube-tui-outbox-test");
+        let _ = fs::remove_file(&path);
+        let start = Instant::now();
+        let mut outbox = Outbox::load(&path, start);
+        let (first, second) = (1, 2);
+        for (id, text) in [(first, "one"), (second, "two")] {
+            let message = Outgoing::new("ana", text.to_string());
+            outbox.push(Outgoing { id, ..message }, start);
+        }
+        outbox.save().unwrap();
+
+        // One message a peer at a time, in order
+        let due = outbox.due(start);
+        assert_eq!(due.iter().map(|m| m.id).collect::<Vec<u64>>(), vec![first]);
+        outbox.sent("ana");
+        assert!(outbox.due(start).is_empty());
+        let state = outbox.failed("ana", first, "timeout".to_string(), true, start);
+        assert!(matches!(state, Delivery::Queued(Some(_))));
+        assert!(outbox.due(start).is_empty());
+        assert_eq!(outbox.due(start + FIRST_BACKOFF)[0].id, first);
+        outbox.sent("ana");
+        assert!(outbox.acknowledged("ana", first));
+
+        // What is left survives a restart
+        outbox.save().unwrap();
+        let mut outbox = Outbox::load(&path, start);
+        assert_eq!(outbox.head("ana"), Some(second));
+        let state = outbox.failed("ana", second, "rejected".to_string(), false, start);
+        assert_eq!(state, Delivery::Failed("rejected".to_string()));
+        outbox.save().unwrap();
+        assert!(Outbox::load(&path, start).due(start).is_empty());
+    }
+}
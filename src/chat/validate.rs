@@ -0,0 +1,294 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use libp2p::{
+    gossipsub::{MessageAcceptance, PeerScoreParams, PeerScoreThresholds, TopicScoreParams},
+    PeerId,
+};
+
+use super::identity::short_id;
+use super::wire::{Body, Envelope, RaceControl, WireError};
+
+/// Largest payload passed on, far above any envelope we send
+pub const MAX_PAYLOAD: usize = 4096;
+/// Longest chat text, in characters
+pub const MAX_TEXT: usize = 1000;
+pub const MAX_NICK: usize = 32;
+pub const MAX_SCRAMBLE: usize = 200;
+/// How far a timestamp may be from our clock either way
+pub const MAX_SKEW: Duration = Duration::from_secs(5 * 60);
+/// Messages a peer may send at once, after which it gets one a second
+pub const BURST: f64 = 10.0;
+pub const PER_SECOND: f64 = 1.0;
+/// A peer's drops are told of at most this often, so a flood of them does
+/// not flood the room too
+const REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What to make of a gossipsub message
+#[derive(Debug, PartialEq)]
+pub enum Verdict {
+    /// Show it and pass it on
+    Accept(Envelope),
+    /// Drop it without holding it against the peer that passed it on, e.g.
+    /// from a blocked peer or a newer build
+    Ignore(Option<String>),
+    /// Drop it and downscore the peer that passed it on
+    Reject(Option<String>),
+}
+
+impl Verdict {
+    pub fn acceptance(&self) -> MessageAcceptance {
+        match self {
+            Verdict::Accept(_) => MessageAcceptance::Accept,
+            Verdict::Ignore(_) => MessageAcceptance::Ignore,
+            Verdict::Reject(_) => MessageAcceptance::Reject,
+        }
+    }
+}
+
+/// Score parameters of every room. Only rejected messages count, for
+/// -10 × rejections²: time in a mesh and first deliveries earn no credit to
+/// outweigh them, and a quiet room must not look like a peer withholding
+/// messages. A second rejection puts a peer below the gossip threshold of
+/// `score_params`, a third below the graylist one, where all it sends is
+/// ignored. The count halves about every eleven minutes.
+pub fn topic_params() -> TopicScoreParams {
+    TopicScoreParams {
+        topic_weight: 1.0,
+        time_in_mesh_weight: 0.0,
+        first_message_deliveries_weight: 0.0,
+        mesh_message_deliveries_weight: 0.0,
+        mesh_failure_penalty_weight: 0.0,
+        invalid_message_deliveries_weight: -10.0,
+        invalid_message_deliveries_decay: 0.999,
+        ..TopicScoreParams::default()
+    }
+}
+
+/// Peer scoring, decayed every second, and the scores at which gossip,
+/// publishing to and then everything from a peer stop
+pub fn score_params() -> (PeerScoreParams, PeerScoreThresholds) {
+    let params = PeerScoreParams {
+        decay_interval: Duration::from_secs(1),
+        ..PeerScoreParams::default()
+    };
+    let thresholds = PeerScoreThresholds {
+        gossip_threshold: -10.0,
+        publish_threshold: -50.0,
+        graylist_threshold: -80.0,
+        ..PeerScoreThresholds::default()
+    };
+    (params, thresholds)
+}
+
+/// Checks every gossipsub message before it is shown or passed on: the
+/// envelope, its size and timestamp, how fast its author sends, and the
+/// block list
+#[derive(Default)]
+pub struct Validator {
+    blocked: HashSet<PeerId>,
+    /// Tokens left of each author and when they were counted
+    buckets: HashMap<PeerId, (f64, Instant)>,
+    /// When a drop of each peer was last told of
+    reported: HashMap<PeerId, Instant>,
+}
+
+impl Validator {
+    pub fn new(blocked: impl IntoIterator<Item = PeerId>) -> Self {
+        Self {
+            blocked: blocked.into_iter().collect(),
+            ..Self::default()
+        }
+    }
+
+    pub fn block(&mut self, peer: PeerId) {
+        self.blocked.insert(peer);
+    }
+
+    pub fn unblock(&mut self, peer: &PeerId) {
+        self.blocked.remove(peer);
+    }
+
+    pub fn is_blocked(&self, peer: &PeerId) -> bool {
+        self.blocked.contains(peer)
+    }
+
+    pub fn blocked(&self) -> impl Iterator<Item = &PeerId> {
+        self.blocked.iter()
+    }
+
+    /// The verdict on `data` written by `author` and passed on by
+    /// `forwarder`, at `time` milliseconds since the Unix epoch and instant
+    /// `now`
+    pub fn check(
+        &mut self,
+        author: &PeerId,
+        forwarder: &PeerId,
+        data: &[u8],
+        time: u64,
+        now: Instant,
+    ) -> Verdict {
+        if self.is_blocked(author) {
+            return Verdict::Ignore(None);
+        }
+        if !self.take(author, now) {
+            let why = self.report(author, "sending too fast".to_string(), now);
+            // Honest peers forward a flood before their own buckets run
+            // out, so only its author is held to ours
+            return match author == forwarder {
+                true => Verdict::Reject(why),
+                false => Verdict::Ignore(why),
+            };
+        }
+        if data.len() > MAX_PAYLOAD {
+            let why = format!("{} bytes is too long", data.len());
+            return Verdict::Reject(self.report(author, why, now));
+        }
+        let envelope = match Envelope::decode(data) {
+            Ok(envelope) => envelope,
+            // Not misbehaving, only newer or older than us
+            Err(e @ WireError::Version(_)) => {
+                return Verdict::Ignore(self.report(author, e.to_string(), now))
+            }
+            Err(e) => return Verdict::Reject(self.report(author, e.to_string(), now)),
+        };
+        if let Err(why) = well_formed(&envelope) {
+            return Verdict::Reject(self.report(author, why, now));
+        }
+        // Clocks drift, so this is no reason to downscore
+        if envelope.timestamp.abs_diff(time) > MAX_SKEW.as_millis() as u64 {
+            let why = "timestamp too far from our clock".to_string();
+            return Verdict::Ignore(self.report(author, why, now));
+        }
+        Verdict::Accept(envelope)
+    }
+
+    /// Take a token of `author`'s bucket, refilled at `PER_SECOND`
+    fn take(&mut self, author: &PeerId, now: Instant) -> bool {
+        // Full buckets are the same as none, so they need not be kept
+        if self.buckets.len() > 256 {
+            self.buckets.retain(|_, (tokens, at)| {
+                *tokens + now.duration_since(*at).as_secs_f64() * PER_SECOND < BURST
+            });
+        }
+        let (tokens, at) = self.buckets.entry(*author).or_insert((BURST, now));
+        *tokens = (*tokens + now.duration_since(*at).as_secs_f64() * PER_SECOND).min(BURST);
+        *at = now;
+        if *tokens < 1.0 {
+            return false;
+        }
+        *tokens -= 1.0;
+        true
+    }
+
+    /// Why a message of `author` was dropped, unless it was told recently
+    fn report(&mut self, author: &PeerId, why: String, now: Instant) -> Option<String> {
+        match self.reported.get(author) {
+            Some(at) if now.duration_since(*at) < REPORT_INTERVAL => None,
+            _ => {
+                self.reported.insert(*author, now);
+                Some(format!(
+                    "dropped a message from {}: {}",
+                    short_id(&author.to_string()),
+                    why
+                ))
+            }
+        }
+    }
+}
+
+/// Whether every field of `envelope` is within bounds
+fn well_formed(envelope: &Envelope) -> Result<(), String> {
+    let nick = &envelope.nick;
+    if nick.chars().count() > MAX_NICK || nick.chars().any(char::is_control) {
+        return Err(format!("{:?} is not a nickname", nick));
+    }
+    match &envelope.body {
+        Body::Chat { text } if text.trim().is_empty() => Err("empty text".to_string()),
+        Body::Chat { text } if text.chars().count() > MAX_TEXT => {
+            Err(format!("text over {} characters", MAX_TEXT))
+        }
+        Body::Solve { time, .. } if !time.is_finite() || *time < 0.0 => {
+            Err(format!("{} is not a time", time))
+        }
+        Body::Solve { penalty, .. } if !matches!(penalty.as_str(), "ok" | "+2" | "dnf") => {
+            Err(format!("{:?} is not a penalty", penalty))
+        }
+        Body::Solve {
+            scramble: Some(scramble),
+            ..
+        } if scramble.len() > MAX_SCRAMBLE => Err("scramble too long".to_string()),
+        Body::Race {
+            control: RaceControl::Open { scramble },
+            ..
+        } if scramble.len() > MAX_SCRAMBLE => Err("scramble too long".to_string()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validator() {
+        let ana = PeerId::random();
+        let bob = PeerId::random();
+        let mut validator = Validator::new([bob]);
+        let start = Instant::now();
+        let chat = |text: &str| Envelope::new("ana", Body::Chat { text: text.into() });
+        let time = chat("").timestamp;
+        let check = |v: &mut Validator, peer, envelope: &Envelope, at| {
+            v.check(peer, peer, &envelope.encode(), time, start + at)
+        };
+
+        let gg = chat("gg");
+        assert_eq!(
+            check(&mut validator, &ana, &gg, Duration::ZERO),
+            Verdict::Accept(gg.clone())
+        );
+        assert_eq!(
+            check(&mut validator, &bob, &gg, Duration::ZERO),
+            Verdict::Ignore(None)
+        );
+
+        // Bad envelopes are rejected, told of once a minute
+        let long = chat(&"a".repeat(MAX_TEXT + 1));
+        let verdict = check(&mut validator, &ana, &long, Duration::ZERO);
+        assert!(matches!(verdict, Verdict::Reject(Some(why)) if why.ends_with("characters")));
+        let verdict = check(&mut validator, &ana, &chat(" "), Duration::ZERO);
+        assert_eq!(verdict, Verdict::Reject(None));
+        let mut old = chat("gg");
+        old.timestamp -= 10 * 60 * 1000;
+        let verdict = check(&mut validator, &ana, &old, Duration::from_secs(61));
+        assert!(matches!(verdict, Verdict::Ignore(Some(_))));
+        let verdict = validator.check(&ana, &ana, b"gg", time, start + Duration::from_secs(61));
+        assert!(matches!(verdict.acceptance(), MessageAcceptance::Reject));
+
+        // A burst, then one a second
+        let carol = PeerId::random();
+        for _ in 0..BURST as usize {
+            assert!(matches!(
+                check(&mut validator, &carol, &gg, Duration::ZERO),
+                Verdict::Accept(_)
+            ));
+        }
+        let verdict = check(&mut validator, &carol, &gg, Duration::ZERO);
+        assert!(matches!(verdict, Verdict::Reject(Some(why)) if why.ends_with("too fast")));
+        let verdict = check(&mut validator, &carol, &gg, Duration::from_secs(1));
+        assert!(matches!(verdict, Verdict::Accept(_)));
+        // Forwarding a flood is not held against the forwarder
+        let forwarded = validator.check(&carol, &ana, &gg.encode(), time, start);
+        assert_eq!(forwarded, Verdict::Ignore(None));
+        let direct = validator.check(&carol, &carol, &gg.encode(), time, start);
+        assert_eq!(direct, Verdict::Reject(None));
+
+        validator.unblock(&bob);
+        assert!(matches!(
+            check(&mut validator, &bob, &gg, Duration::ZERO),
+            Verdict::Accept(_)
+        ));
+    }
+}
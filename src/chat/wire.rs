@@ -0,0 +1,177 @@
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Version of the envelope this build speaks. Peers drop envelopes of any
+/// other version instead of guessing at their meaning.
+pub const VERSION: u16 = 1;
+
+/// What every gossipsub payload is: JSON of one envelope
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Envelope {
+    pub version: u16,
+    /// Nickname the sender goes by, which may be empty
+    pub nick: String,
+    /// Milliseconds since the Unix epoch when it was sent
+    pub timestamp: u64,
+    /// Random, so equal bodies sent twice still differ
+    pub nonce: u64,
+    pub body: Body,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Body {
+    Chat {
+        text: String,
+    },
+    /// A solve the sender just finished
+    Solve {
+        /// Seconds before penalties
+        time: f64,
+        /// `ok`, `+2` or `dnf`
+        penalty: String,
+        scramble: Option<String>,
+    },
+    Presence {
+        status: Presence,
+    },
+    /// Coordinates a race: everyone solves `scramble` from the same start
+    Race {
+        race: u64,
+        control: RaceControl,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Presence {
+    Online,
+    Solving,
+    Away,
+    Offline,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "action", rename_all = "snake_case", deny_unknown_fields)]
+pub enum RaceControl {
+    Open { scramble: String },
+    Ready,
+    Start,
+    Abort,
+}
+
+/// Why a payload was dropped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WireError {
+    /// Sent by a newer or older build
+    Version(u16),
+    Malformed(String),
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WireError::Version(v) => write!(f, "unsupported version {}, we speak {}", v, VERSION),
+            WireError::Malformed(e) => write!(f, "malformed envelope: {}", e),
+        }
+    }
+}
+
+impl Envelope {
+    /// `body` from `nick`, stamped now
+    pub fn new(nick: &str, body: Body) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        Self {
+            version: VERSION,
+            nick: nick.to_string(),
+            timestamp,
+            nonce: rand::random(),
+            body,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("envelopes always serialize")
+    }
+
+    /// Decode a payload, rejecting other versions, unknown kinds and fields
+    pub fn decode(data: &[u8]) -> Result<Self, WireError> {
+        // Only the version is read first, since other versions may differ
+        // in everything else
+        #[derive(Deserialize)]
+        struct Versioned {
+            version: u16,
+        }
+        let versioned = serde_json::from_slice::<Versioned>(data)
+            .map_err(|e| WireError::Malformed(e.to_string()))?;
+        if versioned.version != VERSION {
+            return Err(WireError::Version(versioned.version));
+        }
+        serde_json::from_slice(data).map_err(|e| WireError::Malformed(e.to_string()))
+    }
+}
+
+/// Gossipsub message id of a payload: the first 16 bytes of the SHA-256 of
+/// the whole envelope, nonce included, in hex. Taken from the bytes as
+/// received, since another build may encode the same envelope differently,
+/// and cryptographic so no peer can make a message that takes another's id.
+pub fn message_id(data: &[u8]) -> String {
+    Sha256::digest(data)[..16]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelope() {
+        let gg = Body::Chat {
+            text: "gg".to_string(),
+        };
+        let first = Envelope::new("ana", gg.clone());
+        let second = Envelope::new("ana", gg);
+        assert_eq!(Envelope::decode(&first.encode()), Ok(first.clone()));
+        // The same text twice is two messages
+        assert_ne!(message_id(&first.encode()), message_id(&second.encode()));
+        assert_eq!(message_id(b"abc"), "ba7816bf8f01cfea414140de5dae2223");
+
+        let race = Envelope::new(
+            "",
+            Body::Race {
+                race: 7,
+                control: RaceControl::Open {
+                    scramble: "R U R'".to_string(),
+                },
+            },
+        );
+        assert_eq!(Envelope::decode(&race.encode()), Ok(race));
+
+        let decode = |json: &str| Envelope::decode(json.as_bytes());
+        assert_eq!(
+            decode(r#"{"version":2,"future":"field"}"#),
+            Err(WireError::Version(2))
+        );
+        let envelope = r#"{"version":1,"nick":"","timestamp":0,"nonce":0,"body":"#;
+        assert!(decode(&format!(r#"{envelope}{{"kind":"chat","text":"hi"}}}}"#)).is_ok());
+        for body in [
+            r#"{"kind":"chat","text":"hi","extra":1}"#,
+            r#"{"kind":"dance"}"#,
+            r#"{"kind":"presence","status":"asleep"}"#,
+        ] {
+            let result = decode(&format!("{envelope}{body}}}"));
+            assert!(matches!(result, Err(WireError::Malformed(_))), "{body}");
+        }
+        assert!(matches!(decode("gg"), Err(WireError::Malformed(_))));
+    }
+}
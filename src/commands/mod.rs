@@ -30,6 +30,44 @@ impl Command for ResetCursorShape {
     }
 }
 
+/// OSC 52 sequence that has the terminal put the text on the system clipboard
+/// <https://invisible-island.net/xterm/ctlseqs/ctlseqs.html#h3-Operating-System-Commands>
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetClipboard(pub String);
+
+impl Command for SetClipboard {
+    fn write_ansi(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        write!(f, "\x1B]52;c;{}\x07", base64(self.0.as_bytes()))
+    }
+
+    #[cfg(windows)]
+    fn execute_winapi(&self) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | u32::from(*b) << (16 - 8 * i));
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char),
+                false => out.push('='),
+            }
+        }
+    }
+    out
+}
+
+pub fn copy_to_clipboard(text: &str) -> Result<(), std::io::Error> {
+    execute!(stdout(), SetClipboard(text.to_string()))
+}
+
 pub fn reset_terminal() {
     disable_raw_mode().unwrap();
 
@@ -81,3 +119,16 @@ pub fn quit_terminal(mut terminal: Terminal<CrosstermBackend<Stdout>>) {
 
     terminal.show_cursor().unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"R"), "Ug==");
+        assert_eq!(base64(b"R U"), "UiBV");
+        assert_eq!(base64(b"R U'"), "UiBVJw==");
+    }
+}
@@ -0,0 +1,373 @@
+use super::{state::FACE_ORDER, CubeState, Face};
+use once_cell::sync::Lazy;
+use std::fmt::{self, Formatter};
+
+/// Faces in the order the Speffz scheme letters them
+const SPEFFZ_FACES: [Face; 6] = [Face::U, Face::L, Face::F, Face::R, Face::B, Face::D];
+/// Sticker of a face (in net order) for each of its four corner and edge letters
+const CORNER_STICKERS: [usize; 4] = [0, 2, 8, 6];
+const EDGE_STICKERS: [usize; 4] = [1, 5, 7, 3];
+
+pub const SPEFFZ: &str = "ABCDEFGHIJKLMNOPQRSTUVWX";
+
+/// A piece is the facelet indices of its stickers. The first sticker is the
+/// U/D one (F/B for E slice edges), corners continue clockwise.
+type Piece = Vec<usize>;
+
+fn facelet(face: Face, sticker: usize) -> usize {
+    FACE_ORDER.iter().position(|f| *f == face).unwrap() * 9 + sticker
+}
+
+// Facelets of every Speffz letter location, for corners and edges
+fn speffz_facelets(stickers: [usize; 4]) -> Vec<usize> {
+    SPEFFZ_FACES
+        .iter()
+        .flat_map(|f| stickers.iter().map(move |s| facelet(*f, *s)))
+        .collect()
+}
+
+static CORNER_FACELETS: Lazy<Vec<usize>> = Lazy::new(|| speffz_facelets(CORNER_STICKERS));
+static EDGE_FACELETS: Lazy<Vec<usize>> = Lazy::new(|| speffz_facelets(EDGE_STICKERS));
+
+// The pieces that own each group of letter locations, in Speffz letter order
+// of their first sticker
+fn pieces(facelets: &[usize]) -> Vec<Piece> {
+    let mut pieces: Vec<Piece> = vec![];
+    for f in facelets {
+        if pieces.iter().any(|p| p.contains(f)) {
+            continue;
+        }
+        let pos = CubeState::position(*f);
+        let mut piece = facelets
+            .iter()
+            .copied()
+            .filter(|g| CubeState::position(*g) == pos)
+            .collect::<Piece>();
+        let primary = |g: &usize| {
+            let n = CubeState::normal(*g);
+            match (n[1], n[2]) {
+                (0, 0) => 2,
+                (0, _) => 1,
+                _ => 0,
+            }
+        };
+        piece.sort_by_key(primary);
+        if piece.len() == 3 {
+            // Clockwise seen from outside the corner
+            let (a, b) = (CubeState::normal(piece[0]), CubeState::normal(piece[1]));
+            let cross = [
+                a[1] * b[2] - a[2] * b[1],
+                a[2] * b[0] - a[0] * b[2],
+                a[0] * b[1] - a[1] * b[0],
+            ];
+            if cross[0] * pos[0] + cross[1] * pos[1] + cross[2] * pos[2] > 0 {
+                piece.swap(1, 2);
+            }
+        }
+        pieces.push(piece);
+    }
+    pieces
+}
+
+static CORNERS: Lazy<Vec<Piece>> = Lazy::new(|| pieces(&CORNER_FACELETS));
+static EDGES: Lazy<Vec<Piece>> = Lazy::new(|| pieces(&EDGE_FACELETS));
+
+/// Find the piece named by its faces, e.g. `UBL` or `UR`
+fn piece_by_name(pieces: &[Piece], name: &str) -> Option<usize> {
+    let mut faces = name
+        .chars()
+        .map(|c| {
+            SPEFFZ_FACES
+                .iter()
+                .position(|f| f.to_string() == c.to_string())
+        })
+        .collect::<Option<Vec<usize>>>()?;
+    faces.sort();
+    pieces.iter().position(|p| {
+        let mut piece_faces = p
+            .iter()
+            .map(|f| {
+                let face = FACE_ORDER[f / 9];
+                SPEFFZ_FACES.iter().position(|s| *s == face).unwrap()
+            })
+            .collect::<Vec<usize>>();
+        piece_faces.sort();
+        piece_faces == faces
+    })
+}
+
+#[derive(Clone, Debug)]
+pub struct LetterScheme {
+    /// Letters of the 24 corner and edge locations, in Speffz order
+    pub corners: Vec<char>,
+    pub edges: Vec<char>,
+    pub corner_buffer: usize,
+    pub edge_buffer: usize,
+}
+
+impl Default for LetterScheme {
+    /// Speffz with Old Pochmann buffers (UBL corners, UR edges)
+    fn default() -> Self {
+        Self::new(SPEFFZ, SPEFFZ, "UBL", "UR").unwrap()
+    }
+}
+
+impl LetterScheme {
+    pub fn new(
+        corners: &str,
+        edges: &str,
+        corner_buffer: &str,
+        edge_buffer: &str,
+    ) -> Result<Self, String> {
+        let letters = |scheme: &str, kind: &str| {
+            let letters = scheme
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect::<Vec<char>>();
+            match letters.len() {
+                24 => Ok(letters),
+                n => Err(format!("{} scheme needs 24 letters, got {}", kind, n)),
+            }
+        };
+        Ok(Self {
+            corners: letters(corners, "corner")?,
+            edges: letters(edges, "edge")?,
+            corner_buffer: piece_by_name(&CORNERS, corner_buffer)
+                .filter(|_| corner_buffer.len() == 3)
+                .ok_or(format!("unknown corner buffer {}", corner_buffer))?,
+            edge_buffer: piece_by_name(&EDGES, edge_buffer)
+                .filter(|_| edge_buffer.len() == 2)
+                .ok_or(format!("unknown edge buffer {}", edge_buffer))?,
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Twist {
+    Clockwise,
+    CounterClockwise,
+    Flip,
+}
+
+impl fmt::Display for Twist {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Twist::Clockwise => write!(f, "cw"),
+            Twist::CounterClockwise => write!(f, "ccw"),
+            Twist::Flip => write!(f, "flip"),
+        }
+    }
+}
+
+/// Memo of one piece type
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PieceMemo {
+    /// Targets shot to from the buffer, split into cycles
+    pub cycles: Vec<Vec<char>>,
+    /// First letter of every cycle break
+    pub breaks: Vec<char>,
+    /// Pieces solved in place but twisted (corners) or flipped (edges)
+    pub twisted: Vec<(char, Twist)>,
+}
+
+impl PieceMemo {
+    pub fn letters(&self) -> Vec<char> {
+        self.cycles.iter().flatten().copied().collect()
+    }
+
+    /// Letters in pairs the way they are memorised, e.g. `BM JC D`
+    pub fn pairs(&self) -> String {
+        self.letters()
+            .chunks(2)
+            .map(|p| p.iter().collect::<String>())
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Memo {
+    pub corners: PieceMemo,
+    pub edges: PieceMemo,
+}
+
+impl Memo {
+    /// An odd number of edge targets needs a parity algorithm
+    pub fn parity(&self) -> bool {
+        self.edges.letters().len() % 2 == 1
+    }
+}
+
+impl fmt::Display for Memo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        writeln!(f, "edges:   {}", self.edges.pairs())?;
+        writeln!(f, "corners: {}", self.corners.pairs())?;
+        write!(f, "parity:  {}", if self.parity() { "yes" } else { "no" })
+    }
+}
+
+// Which location the sticker currently at `at` belongs to
+fn home(state: &CubeState, pieces: &[Piece], at: usize) -> usize {
+    let colors = state.facelets();
+    let piece = pieces.iter().find(|p| p.contains(&at)).unwrap();
+    let mut here = piece.iter().map(|f| colors[*f]).collect::<Vec<Face>>();
+    here.sort_by_key(|f| FACE_ORDER.iter().position(|o| o == f));
+    let owner = pieces
+        .iter()
+        .find(|p| {
+            let mut solved = p.iter().map(|f| FACE_ORDER[f / 9]).collect::<Vec<Face>>();
+            solved.sort_by_key(|f| FACE_ORDER.iter().position(|o| o == f));
+            solved == here
+        })
+        .unwrap();
+    *owner
+        .iter()
+        .find(|f| FACE_ORDER[**f / 9] == colors[at])
+        .unwrap()
+}
+
+fn trace(
+    state: &CubeState,
+    pieces: &[Piece],
+    letter_facelets: &[usize],
+    letters: &[char],
+    buffer: usize,
+) -> PieceMemo {
+    let colors = state.facelets();
+    let letter = |f: usize| letters[letter_facelets.iter().position(|l| *l == f).unwrap()];
+    let piece_of = |f: usize| pieces.iter().position(|p| p.contains(&f)).unwrap();
+    let in_place = |p: &Piece| p.iter().all(|f| colors[*f] == FACE_ORDER[*f / 9]);
+    let twist = |p: &Piece| {
+        // Slot holding the piece's own U/D (or F/B) sticker, none if it is elsewhere
+        let slot = (0..p.len()).find(|s| home(state, pieces, p[*s]) == p[0])?;
+        match (slot, p.len()) {
+            (0, _) => None,
+            (_, 2) => Some(Twist::Flip),
+            (1, _) => Some(Twist::Clockwise),
+            _ => Some(Twist::CounterClockwise),
+        }
+    };
+
+    let mut memo = PieceMemo::default();
+    let mut done = pieces.iter().map(in_place).collect::<Vec<bool>>();
+    for (i, p) in pieces.iter().enumerate() {
+        if i != buffer {
+            if let Some(t) = twist(p) {
+                memo.twisted.push((letter(p[0]), t));
+                done[i] = true;
+            }
+        }
+    }
+    done[buffer] = true;
+
+    let mut cycle = vec![];
+    let mut start = buffer;
+    let mut cur = pieces[buffer][0];
+    loop {
+        let target = home(state, pieces, cur);
+        if piece_of(target) == start {
+            if start != buffer {
+                cycle.push(letter(target));
+            }
+            if !cycle.is_empty() {
+                memo.cycles.push(std::mem::take(&mut cycle));
+            }
+            // Break into the next unsolved piece
+            match (0..pieces.len()).find(|p| !done[*p]) {
+                Some(p) => {
+                    done[p] = true;
+                    start = p;
+                    cur = pieces[p][0];
+                    cycle.push(letter(cur));
+                    memo.breaks.push(letter(cur));
+                }
+                None => break,
+            }
+            continue;
+        }
+        done[piece_of(target)] = true;
+        cycle.push(letter(target));
+        cur = target;
+    }
+    memo
+}
+
+/// Old Pochmann style memo of a scrambled cube, assuming white on top and
+/// green in front (centers in their solved place)
+pub fn memo(state: &CubeState, scheme: &LetterScheme) -> Memo {
+    Memo {
+        corners: trace(
+            state,
+            &CORNERS,
+            &CORNER_FACELETS,
+            &scheme.corners,
+            scheme.corner_buffer,
+        ),
+        edges: trace(
+            state,
+            &EDGES,
+            &EDGE_FACELETS,
+            &scheme.edges,
+            scheme.edge_buffer,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::parse_alg;
+
+    fn memo_of(alg: &str) -> Memo {
+        memo(
+            &CubeState::from_moves(&parse_alg(alg).unwrap()),
+            &LetterScheme::default(),
+        )
+    }
+
+    #[test]
+    fn test_solved_has_empty_memo() {
+        assert_eq!(memo_of(""), Memo::default());
+    }
+
+    #[test]
+    fn test_t_perm() {
+        // Swaps UR/UL edges and UBR/UFR corners
+        let m = memo_of("R U R' U' R' F R2 U' R' U' R U R' F'");
+        assert_eq!(m.edges.letters(), vec!['D']);
+        assert_eq!(m.corners.letters(), vec!['B', 'C', 'B']);
+        assert_eq!(m.corners.breaks, vec!['B']);
+        assert!(m.parity());
+    }
+
+    #[test]
+    fn test_cycle_break() {
+        // Two edge 2-cycles, the one without the buffer needs a cycle break
+        let m = memo_of("U2");
+        assert_eq!(m.edges.letters(), vec!['D', 'A', 'C', 'A']);
+        assert_eq!(m.edges.breaks, vec!['A']);
+        assert!(!m.parity());
+    }
+
+    #[test]
+    fn test_twists_and_flips() {
+        let m = memo_of("R' D' R D R' D' R D U R' D' R D R' D' R D R' D' R D R' D' R D U'");
+        assert!(m.corners.letters().is_empty());
+        assert_eq!(m.corners.twisted.len(), 2);
+        assert_ne!(m.corners.twisted[0].1, m.corners.twisted[1].1);
+        // Flips UF and UB
+        let m = memo_of("M' U M' U M' U M' U2 M' U M' U M' U M'");
+        assert!(m.edges.letters().is_empty());
+        assert_eq!(
+            m.edges.twisted,
+            vec![('A', Twist::Flip), ('C', Twist::Flip)]
+        );
+    }
+
+    #[test]
+    fn test_bad_scheme() {
+        assert!(LetterScheme::new("ABC", SPEFFZ, "UBL", "UR").is_err());
+        assert!(LetterScheme::new(SPEFFZ, SPEFFZ, "UB", "UR").is_err());
+        assert!(LetterScheme::new(SPEFFZ, SPEFFZ, "DFR", "DF").is_ok());
+    }
+}
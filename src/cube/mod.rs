@@ -0,0 +1,249 @@
+use rand::{
+    distributions::{Distribution, Standard},
+    Rng,
+};
+
+use std::{
+    error::Error,
+    fmt::{self, Formatter},
+    str::FromStr,
+};
+
+pub mod bld;
+pub mod reconstruction;
+pub mod state;
+
+pub use state::CubeState;
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Face {
+    U,
+    L,
+    F,
+    R,
+    B,
+    D,
+    // Slices
+    M,
+    E,
+    S,
+    // Whole cube rotations
+    X,
+    Y,
+    Z,
+    None,
+}
+
+impl std::fmt::Display for Face {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        let c = match self {
+            Face::U => 'U',
+            Face::L => 'L',
+            Face::F => 'F',
+            Face::R => 'R',
+            Face::B => 'B',
+            Face::D => 'D',
+            Face::M => 'M',
+            Face::E => 'E',
+            Face::S => 'S',
+            Face::X => 'x',
+            Face::Y => 'y',
+            Face::Z => 'z',
+            Face::None => unreachable!(),
+        };
+        write!(f, "{}", c)?;
+        Ok(())
+    }
+}
+
+impl Face {
+    fn is_outer(self) -> bool {
+        matches!(
+            self,
+            Face::U | Face::L | Face::F | Face::R | Face::B | Face::D
+        )
+    }
+
+    pub fn is_rotation(self) -> bool {
+        matches!(self, Face::X | Face::Y | Face::Z)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Move {
+    pub face: Face,
+    /// Turns the outer layer together with the adjacent slice (`r`, `Rw`)
+    pub wide: bool,
+    pub prime: bool,
+    pub half_turn: bool,
+}
+
+impl std::fmt::Display for Move {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        let primestr = match self.prime {
+            true => "\'",
+            false => "",
+        };
+        let halfstr = match self.half_turn {
+            true => "2",
+            false => "",
+        };
+        let facestr = match self.wide {
+            true => self.face.to_string().to_lowercase(),
+            false => self.face.to_string(),
+        };
+        write!(f, "{}{}{}", facestr, halfstr, primestr)?;
+        Ok(())
+    }
+}
+
+impl Move {
+    pub const fn new(face: Face, prime: bool, half_turn: bool) -> Move {
+        Move {
+            face,
+            wide: false,
+            prime,
+            half_turn,
+        }
+    }
+
+    fn random<R: Rng + ?Sized>(rng: &mut R, last: Move) -> Move {
+        let next: Move = rng.gen();
+        if next.face == last.face {
+            Move::random(rng, last)
+        } else {
+            next
+        }
+    }
+
+    pub fn inverse(self) -> Move {
+        Move {
+            prime: !self.half_turn && !self.prime,
+            ..self
+        }
+    }
+
+    /// Number of clockwise quarter turns this move is made of
+    pub fn quarter_turns(self) -> usize {
+        match (self.half_turn, self.prime) {
+            (true, _) => 2,
+            (false, true) => 3,
+            (false, false) => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseMoveError(String);
+
+impl fmt::Display for ParseMoveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "invalid move '{}'", self.0)
+    }
+}
+
+impl Error for ParseMoveError {}
+
+impl FromStr for Move {
+    type Err = ParseMoveError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseMoveError(s.to_string());
+        let mut chars = s.chars();
+        let first = chars.next().ok_or_else(err)?;
+        let (face, mut wide) = match first {
+            'U' => (Face::U, false),
+            'L' => (Face::L, false),
+            'F' => (Face::F, false),
+            'R' => (Face::R, false),
+            'B' => (Face::B, false),
+            'D' => (Face::D, false),
+            'u' => (Face::U, true),
+            'l' => (Face::L, true),
+            'f' => (Face::F, true),
+            'r' => (Face::R, true),
+            'b' => (Face::B, true),
+            'd' => (Face::D, true),
+            'M' => (Face::M, false),
+            'E' => (Face::E, false),
+            'S' => (Face::S, false),
+            'x' => (Face::X, false),
+            'y' => (Face::Y, false),
+            'z' => (Face::Z, false),
+            _ => return Err(err()),
+        };
+        let mut rest = chars.as_str();
+        if let Some(r) = rest.strip_prefix('w') {
+            if wide || !face.is_outer() {
+                return Err(err());
+            }
+            wide = true;
+            rest = r;
+        }
+        let (half_turn, prime) = match rest {
+            "" => (false, false),
+            "'" => (false, true),
+            "2" | "2'" => (true, false),
+            _ => return Err(err()),
+        };
+        Ok(Move {
+            face,
+            wide,
+            prime,
+            half_turn,
+        })
+    }
+}
+
+/// Parse a whitespace separated move sequence such as `R U R' U'`
+pub fn parse_alg(s: &str) -> Result<Vec<Move>, ParseMoveError> {
+    s.split_whitespace().map(str::parse).collect()
+}
+
+/// Format a move sequence the way `gen_scramble` does
+pub fn format_alg(moves: &[Move]) -> String {
+    moves
+        .iter()
+        .map(|m| m.to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+impl Distribution<Move> for Standard {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Move {
+        let face = match rng.gen_range(0..6) {
+            0 => Face::U,
+            1 => Face::L,
+            2 => Face::F,
+            3 => Face::R,
+            4 => Face::B,
+            5 => Face::D,
+            _ => unreachable!(),
+        };
+        let (prime, half_turn) = match rng.gen_range(0..3) {
+            0 => (false, false),
+            1 => (true, false),
+            2 => (false, true),
+            _ => unreachable!(),
+        };
+        Move::new(face, prime, half_turn)
+    }
+}
+
+pub fn gen_scramble() -> String {
+    gen_scramble_with(&mut rand::thread_rng())
+}
+
+/// A scramble drawn from `rng`, so a seeded generator repeats its scrambles
+pub fn gen_scramble_with<R: Rng + ?Sized>(rng: &mut R) -> String {
+    let mut s = String::new();
+    let mut l = Move::new(Face::None, false, false);
+    let _ = (0..21)
+        .into_iter()
+        .map(|_| {
+            l = Move::random(rng, l);
+            s += &(" ".to_owned() + &l.to_string())
+        })
+        .collect::<()>();
+    s
+}
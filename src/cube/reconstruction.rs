@@ -0,0 +1,218 @@
+use super::{format_alg, parse_alg, Move, ParseMoveError};
+use std::{
+    fmt::{self, Formatter},
+    str::FromStr,
+};
+
+/// One labelled part of a reconstruction, e.g. `y' R U' F2 // cross`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Step {
+    /// Never holds `;` or a new line, which end the step, nor `@`, which
+    /// starts its time in the editor
+    pub label: Option<String>,
+    pub moves: Vec<Move>,
+}
+
+impl Step {
+    pub fn move_count(&self) -> usize {
+        self.moves.iter().filter(|m| !m.face.is_rotation()).count()
+    }
+}
+
+/// Move sequence of a solve split into steps. Written as steps separated by
+/// `;` or new lines, each step optionally followed by a `// label` comment.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Reconstruction {
+    pub steps: Vec<Step>,
+}
+
+impl Reconstruction {
+    pub fn moves(&self) -> Vec<Move> {
+        self.steps.iter().flat_map(|s| s.moves.clone()).collect()
+    }
+
+    pub fn move_count(&self) -> usize {
+        self.steps.iter().map(Step::move_count).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.iter().all(|s| s.moves.is_empty())
+    }
+
+    /// Time spent on every step. Splits (cumulative times at the end of each
+    /// step) are used when there is one per step, otherwise the solve time is
+    /// shared out by move count.
+    pub fn step_times(&self, time: f64, splits: &[f64]) -> Vec<f64> {
+        if splits.len() == self.steps.len() {
+            let mut last = 0.0;
+            return splits
+                .iter()
+                .map(|s| {
+                    let t = s - last;
+                    last = *s;
+                    t
+                })
+                .collect();
+        }
+        let total = self.move_count();
+        self.steps
+            .iter()
+            .map(|s| match total {
+                0 => 0.0,
+                _ => time * s.move_count() as f64 / total as f64,
+            })
+            .collect()
+    }
+
+    /// Parse the text of the reconstruction editor, where a step may end in
+    /// `@ <seconds>`, the time into the solve it was done at. Those are the
+    /// splits, empty when no step has one. A label may not hold `@`, so
+    /// that the text `with_splits` gives for it parses back the same.
+    pub fn parse_with_splits(s: &str) -> Result<(Self, Vec<f64>), String> {
+        let steps = parse_steps(s).map_err(|e| e.to_string())?;
+        let mut labels = steps.iter().filter_map(|(step, _)| step.label.as_deref());
+        if let Some(label) = labels.find(|l| l.contains('@')) {
+            return Err(format!("{:?}: labels cannot hold '@'", label));
+        }
+        let splits = steps.iter().filter_map(|(_, t)| *t).collect::<Vec<f64>>();
+        if !splits.is_empty() && splits.len() != steps.len() {
+            return Err("give every step a time, or none".to_string());
+        }
+        if splits.windows(2).any(|w| w[1] < w[0]) || splits.iter().any(|t| *t < 0.0) {
+            return Err("step times must go up".to_string());
+        }
+        let steps = steps.into_iter().map(|(step, _)| step).collect();
+        Ok((Self { steps }, splits))
+    }
+
+    /// The text for the reconstruction editor, with every step ending in its
+    /// split when there is one per step, so it parses back the same
+    pub fn with_splits(&self, splits: &[f64]) -> String {
+        if splits.len() != self.steps.len() {
+            return self.to_string();
+        }
+        let steps = self
+            .steps
+            .iter()
+            .zip(splits)
+            .map(|(s, t)| format!("{} @ {}", s, t))
+            .collect::<Vec<String>>();
+        steps.join("; ")
+    }
+}
+
+/// Steps separated by `;` or new lines, each with its `@` time if it has one
+fn parse_steps(s: &str) -> Result<Vec<(Step, Option<f64>)>, ParseMoveError> {
+    let mut steps = vec![];
+    for line in s.split([';', '\n']) {
+        let (line, split) = match line.rsplit_once('@') {
+            Some((rest, t)) => match t.trim().parse::<f64>() {
+                Ok(t) if t.is_finite() => (rest, Some(t)),
+                _ => (line, None),
+            },
+            None => (line, None),
+        };
+        let (moves, label) = match line.split_once("//") {
+            Some((moves, label)) => (moves, Some(label.trim().to_string())),
+            None => (line, None),
+        };
+        let moves = parse_alg(moves)?;
+        if moves.is_empty() && label.is_none() {
+            continue;
+        }
+        let step = Step {
+            label: label.filter(|l| !l.is_empty()),
+            moves,
+        };
+        steps.push((step, split));
+    }
+    Ok(steps)
+}
+
+impl FromStr for Reconstruction {
+    type Err = ParseMoveError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let steps = parse_steps(s)?;
+        Ok(Self {
+            steps: steps.into_iter().map(|(step, _)| step).collect(),
+        })
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        match &self.label {
+            Some(label) => write!(f, "{} // {}", format_alg(&self.moves), label),
+            None => write!(f, "{}", format_alg(&self.moves)),
+        }
+    }
+}
+
+impl fmt::Display for Reconstruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        let steps = self
+            .steps
+            .iter()
+            .map(Step::to_string)
+            .collect::<Vec<String>>();
+        write!(f, "{}", steps.join("; "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display_roundtrip() {
+        let text = "y' R U' F2 // cross; U R U' R' // f2l 1; R U R' U'";
+        let recon: Reconstruction = text.parse().unwrap();
+        assert_eq!(recon.steps.len(), 3);
+        assert_eq!(recon.steps[0].label.as_deref(), Some("cross"));
+        assert_eq!(recon.steps[2].label, None);
+        assert_eq!(recon.move_count(), 11);
+        assert_eq!(recon.to_string(), text);
+        assert!("R U X".parse::<Reconstruction>().is_err());
+    }
+
+    #[test]
+    fn test_step_times() {
+        let recon: Reconstruction = "R U // a; R U R' U' // b".parse().unwrap();
+        assert_eq!(recon.step_times(6.0, &[]), vec![2.0, 4.0]);
+        assert_eq!(recon.step_times(6.0, &[1.5, 6.0]), vec![1.5, 4.5]);
+
+        // The editor takes the splits at the end of each step
+        let text = "R U // a @ 1.5; R U R' U' // b @ 6";
+        let (parsed, splits) = Reconstruction::parse_with_splits(text).unwrap();
+        assert_eq!(parsed, recon);
+        assert_eq!(splits, vec![1.5, 6.0]);
+        assert_eq!(parsed.with_splits(&splits), text);
+        assert_eq!(parsed.step_times(6.0, &splits), vec![1.5, 4.5]);
+        assert!(Reconstruction::parse_with_splits("R U @ 1.5; R U R' U'").is_err());
+        assert!(Reconstruction::parse_with_splits("R U @ 2; R U R' U' @ 1").is_err());
+    }
+
+    #[test]
+    fn test_with_splits_roundtrip() {
+        let recon: Reconstruction = "R U // a; R U R' U' // b; U2".parse().unwrap();
+        for splits in [vec![], vec![1.5], vec![1.5, 4.0, 6.25]] {
+            let text = recon.with_splits(&splits);
+            let (parsed, parsed_splits) = Reconstruction::parse_with_splits(&text).unwrap();
+            assert_eq!(parsed, recon);
+            // Splits that do not match the steps are left out of the text
+            match splits.len() == recon.steps.len() {
+                true => assert_eq!(parsed_splits, splits),
+                false => assert!(parsed_splits.is_empty()),
+            }
+        }
+
+        // `@` in a label would read as the time of its step
+        let text = "R U // x-cross @ home; U2 // b";
+        assert!(text.parse::<Reconstruction>().is_ok());
+        assert!(Reconstruction::parse_with_splits(text).is_err());
+        let (parsed, splits) = Reconstruction::parse_with_splits("R U // x-cross @ 2").unwrap();
+        assert_eq!(parsed.steps[0].label.as_deref(), Some("x-cross"));
+        assert_eq!(splits, vec![2.0]);
+    }
+}
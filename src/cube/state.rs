@@ -0,0 +1,203 @@
+use super::{Face, Move};
+use once_cell::sync::Lazy;
+
+/// Faces in facelet order, each face is stored as 9 stickers read row by row
+/// the way they appear on an unfolded net (U above F, L F R B in a row, D below F)
+pub const FACE_ORDER: [Face; 6] = [Face::U, Face::R, Face::F, Face::D, Face::L, Face::B];
+
+type Vec3 = [i8; 3];
+
+// Cubie position and outward normal of every facelet, x points to R, y to U and z to F
+static GEOMETRY: Lazy<Vec<(Vec3, Vec3)>> = Lazy::new(|| {
+    let mut g = Vec::with_capacity(54);
+    for face in FACE_ORDER {
+        for r in 0..3i8 {
+            for c in 0..3i8 {
+                g.push(match face {
+                    Face::U => ([c - 1, 1, r - 1], [0, 1, 0]),
+                    Face::R => ([1, 1 - r, 1 - c], [1, 0, 0]),
+                    Face::F => ([c - 1, 1 - r, 1], [0, 0, 1]),
+                    Face::D => ([c - 1, -1, 1 - r], [0, -1, 0]),
+                    Face::L => ([-1, 1 - r, c - 1], [-1, 0, 0]),
+                    Face::B => ([1 - c, 1 - r, -1], [0, 0, -1]),
+                    _ => unreachable!(),
+                });
+            }
+        }
+    }
+    g
+});
+
+fn dot(a: Vec3, b: Vec3) -> i8 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+// Clockwise quarter turn around `axis` as seen when looking at the face it points to
+fn rotate(axis: Vec3, v: Vec3) -> Vec3 {
+    let d = dot(axis, v);
+    let cross = [
+        axis[1] * v[2] - axis[2] * v[1],
+        axis[2] * v[0] - axis[0] * v[2],
+        axis[0] * v[1] - axis[1] * v[0],
+    ];
+    [
+        axis[0] * d - cross[0],
+        axis[1] * d - cross[1],
+        axis[2] * d - cross[2],
+    ]
+}
+
+fn facelet_index(pos: Vec3, normal: Vec3) -> usize {
+    GEOMETRY
+        .iter()
+        .position(|&(p, n)| p == pos && n == normal)
+        .unwrap()
+}
+
+/// Axis a move turns around and the range of layers (measured along that axis) it turns
+fn move_layers(m: Move) -> (Vec3, i8, i8) {
+    let axis = match m.face {
+        Face::U | Face::Y => [0, 1, 0],
+        Face::D | Face::E => [0, -1, 0],
+        Face::R | Face::X => [1, 0, 0],
+        Face::L | Face::M => [-1, 0, 0],
+        Face::F | Face::S | Face::Z => [0, 0, 1],
+        Face::B => [0, 0, -1],
+        Face::None => unreachable!(),
+    };
+    let (lo, hi) = match m.face {
+        Face::M | Face::E | Face::S => (0, 0),
+        Face::X | Face::Y | Face::Z => (-1, 1),
+        _ if m.wide => (0, 1),
+        _ => (1, 1),
+    };
+    (axis, lo, hi)
+}
+
+/// Sticker level state of a 3x3x3 cube. Every sticker holds the face it belongs to
+/// when solved, so the UI decides which color a face is drawn in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CubeState {
+    facelets: [Face; 54],
+}
+
+impl Default for CubeState {
+    fn default() -> Self {
+        Self::solved()
+    }
+}
+
+impl CubeState {
+    pub fn solved() -> Self {
+        let mut facelets = [Face::None; 54];
+        for (i, face) in FACE_ORDER.iter().enumerate() {
+            facelets[i * 9..i * 9 + 9].fill(*face);
+        }
+        Self { facelets }
+    }
+
+    pub fn from_moves(moves: &[Move]) -> Self {
+        let mut state = Self::solved();
+        state.apply_all(moves);
+        state
+    }
+
+    pub fn apply(&mut self, m: Move) {
+        let (axis, lo, hi) = move_layers(m);
+        for _ in 0..m.quarter_turns() {
+            let old = self.facelets;
+            for (i, &(pos, normal)) in GEOMETRY.iter().enumerate() {
+                let depth = dot(pos, axis);
+                if depth < lo || depth > hi {
+                    continue;
+                }
+                self.facelets[facelet_index(rotate(axis, pos), rotate(axis, normal))] = old[i];
+            }
+        }
+    }
+
+    pub fn apply_all(&mut self, moves: &[Move]) {
+        for m in moves {
+            self.apply(*m);
+        }
+    }
+
+    /// Solved means every face is a single color, regardless of orientation
+    pub fn is_solved(&self) -> bool {
+        self.facelets
+            .chunks(9)
+            .all(|face| face.iter().all(|s| *s == face[4]))
+    }
+
+    pub fn facelets(&self) -> &[Face; 54] {
+        &self.facelets
+    }
+
+    /// The 9 stickers of the face currently at `face`, in net order
+    pub fn face(&self, face: Face) -> &[Face] {
+        let i = FACE_ORDER.iter().position(|f| *f == face).unwrap();
+        &self.facelets[i * 9..i * 9 + 9]
+    }
+
+    /// Cubie position of a facelet, x points to R, y to U and z to F
+    pub fn position(facelet: usize) -> [i8; 3] {
+        GEOMETRY[facelet].0
+    }
+
+    /// Outward normal of a facelet
+    pub fn normal(facelet: usize) -> [i8; 3] {
+        GEOMETRY[facelet].1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::parse_alg;
+
+    fn state(alg: &str) -> CubeState {
+        CubeState::from_moves(&parse_alg(alg).unwrap())
+    }
+
+    #[test]
+    fn test_quarter_turn_order() {
+        for m in [
+            "U", "R", "F", "D", "L", "B", "M", "E", "S", "r", "x", "y", "z",
+        ] {
+            let s = state(&[m; 4].join(" "));
+            assert_eq!(s, CubeState::solved(), "{m}4");
+            assert_ne!(state(m), CubeState::solved(), "{m}");
+        }
+    }
+
+    #[test]
+    fn test_inverse_solves() {
+        let moves = parse_alg("R U2 F' D L2 B' r M' x y' S E2").unwrap();
+        let mut s = CubeState::from_moves(&moves);
+        for m in moves.iter().rev() {
+            s.apply(m.inverse());
+        }
+        assert_eq!(s, CubeState::solved());
+    }
+
+    #[test]
+    fn test_sexy_move_order_six() {
+        assert_eq!(state(&["R U R' U'"; 6].join(" ")), CubeState::solved());
+    }
+
+    #[test]
+    fn test_rotation_equivalences() {
+        assert_eq!(state("x"), state("R M' L'"));
+        assert_eq!(state("r"), state("R M'"));
+        assert!(state("x y z'").is_solved());
+        assert!(!state("R").is_solved());
+    }
+
+    #[test]
+    fn test_r_moves_front_to_up() {
+        let s = state("R");
+        assert_eq!(s.face(Face::U)[2], Face::F);
+        assert_eq!(s.face(Face::U)[0], Face::U);
+        assert_eq!(s.face(Face::B)[0], Face::U);
+    }
+}
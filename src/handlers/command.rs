@@ -0,0 +1,326 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use clap::{builder::PossibleValue, CommandFactory, Parser, Subcommand, ValueEnum};
+use tui_input::Input;
+
+/// Commands of the `:` command line. The same enum is the set of
+/// subcommands of the binary, so anything scriptable works in both places.
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum Cmd {
+    /// Start, switch or list sessions, each with its own times file
+    #[command(subcommand)]
+    Session(SessionCmd),
+    /// Write the solves of the current session to a file
+    Export { format: ExportFormat, path: String },
+    /// Seed the scramble generator so the scrambles can be repeated
+    Seed { seed: u64 },
+    /// Select a solve by its number
+    Goto { solve: usize },
+    /// Switch to a built-in theme, a theme name or a theme file
+    Theme { name: String },
+    /// Dial a peer by its multiaddr
+    Connect { addr: String },
+    /// List our listen multiaddrs with our peer id, for others to dial
+    Addrs,
+    /// Join, leave, switch or list chat rooms
+    #[command(subcommand)]
+    Room(RoomCmd),
+    /// Give a peer, by id or the end of it, a nickname or forget it
+    Nick { peer: String, name: Option<String> },
+    /// Open the direct conversation with a peer, sending the text if any
+    Dm { peer: String, text: Vec<String> },
+    /// Find the last message with the text in the room or conversation
+    /// shown, again for the one before it
+    Search { text: Vec<String> },
+    /// Drop all a peer sends, or list the peers blocked
+    Block { peer: Option<String> },
+    /// Take a peer off the block list
+    Unblock { peer: String },
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum SessionCmd {
+    /// Start a new session and switch to it
+    New { name: String },
+    /// Switch to a session, `default` being the configured times file
+    Switch { name: String },
+    /// List the sessions
+    List,
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum RoomCmd {
+    /// Join a room, a gossipsub topic, and show it
+    Join { room: String },
+    /// Leave a room
+    Leave { room: String },
+    /// Show a room already joined
+    Switch { room: String },
+    /// List the rooms with their unread messages
+    List,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// A header row and one row per solve with its averages
+    Csv,
+    /// The times file format, one tab separated record per solve
+    Txt,
+}
+
+impl Cmd {
+    /// Commands that print or write something and are done. Run from the
+    /// shell they exit instead of starting the TUI.
+    pub fn is_one_shot(&self) -> bool {
+        matches!(self, Cmd::Export { .. } | Cmd::Session(SessionCmd::List))
+    }
+}
+
+/// A command line as typed after `:`, which has no binary name
+#[derive(Parser, Debug)]
+#[command(no_binary_name = true, disable_help_subcommand = true)]
+struct Line {
+    #[command(subcommand)]
+    cmd: Cmd,
+}
+
+/// Parse a command line, e.g. `export csv ~/times.csv`
+pub fn parse(line: &str) -> Result<Cmd, String> {
+    Line::try_parse_from(line.split_whitespace())
+        .map(|l| l.cmd)
+        .map_err(|e| {
+            let text = e.render().to_string();
+            let first = text.lines().find(|l| !l.trim().is_empty()).unwrap_or("");
+            first.trim_start_matches("error: ").to_string()
+        })
+}
+
+/// `~/` at the start of `path` is the home directory
+pub fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => PathBuf::from(std::env::var("HOME").unwrap_or_default()).join(rest),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Argument values that depend on the app rather than on the parser
+#[derive(Default)]
+pub struct Candidates {
+    pub themes: Vec<String>,
+    pub sessions: Vec<String>,
+    pub rooms: Vec<String>,
+}
+
+/// Complete the word being typed at the end of `line`. Returns the new line,
+/// extended by the prefix all matches share, and every match.
+pub fn complete(line: &str, candidates: &Candidates) -> (String, Vec<String>) {
+    let words = line.split_whitespace().collect::<Vec<&str>>();
+    let (done, partial) = match line.ends_with(char::is_whitespace) || words.is_empty() {
+        true => (&words[..], ""),
+        false => (&words[..words.len() - 1], words[words.len() - 1]),
+    };
+
+    // Walk down the subcommands, counting the positional arguments after them
+    let mut cmd = Line::command();
+    let mut positional = 0;
+    for word in done {
+        match cmd.find_subcommand(word).cloned() {
+            Some(sub) => {
+                cmd = sub;
+                positional = 0;
+            }
+            None => positional += 1,
+        }
+    }
+    let options = match cmd.has_subcommands() {
+        true => cmd
+            .get_subcommands()
+            .map(|c| c.get_name().to_string())
+            .collect(),
+        false => match cmd.get_positionals().nth(positional) {
+            Some(arg) => match (cmd.get_name(), arg.get_id().as_str()) {
+                ("theme", "name") => candidates.themes.clone(),
+                ("switch", "name") => candidates.sessions.clone(),
+                ("leave" | "switch", "room") => candidates.rooms.clone(),
+                _ => arg
+                    .get_possible_values()
+                    .iter()
+                    .map(PossibleValue::get_name)
+                    .map(str::to_string)
+                    .collect(),
+            },
+            None => vec![],
+        },
+    };
+    let matches = options
+        .into_iter()
+        .filter(|o| o.starts_with(partial))
+        .collect::<Vec<String>>();
+
+    let head = line[..line.len() - partial.len()].to_string();
+    let line = match matches.as_slice() {
+        [] => line.to_string(),
+        [only] => format!("{}{} ", head, only),
+        [first, rest @ ..] => {
+            // Bytes the matches agree on, which end on a char boundary
+            let shared = rest.iter().fold(first.len(), |n, m| {
+                first
+                    .char_indices()
+                    .zip(m.chars())
+                    .take_while(|((i, a), b)| *i < n && a == b)
+                    .last()
+                    .map_or(0, |((i, a), _)| i + a.len_utf8())
+            });
+            format!("{}{}", head, &first[..shared])
+        }
+    };
+    (line, matches)
+}
+
+/// How many lines the command history keeps
+const HISTORY: usize = 100;
+
+/// Text, history and last output of the `:` command line
+pub struct CommandLine {
+    pub input: Input,
+    pub open: bool,
+    pub history: Vec<String>,
+    /// Entry of `history` shown while stepping through it, `history.len()`
+    /// for the line being typed
+    pos: usize,
+    /// The line being typed before stepping into the history
+    draft: String,
+    /// Output of the last command, or the matches of a completion
+    pub message: Option<Result<String, String>>,
+    path: PathBuf,
+}
+
+impl CommandLine {
+    /// History is read from and written to `path`
+    pub fn new(path: &Path) -> Self {
+        let history = fs::read_to_string(path)
+            .map(|text| text.lines().map(str::to_string).collect::<Vec<String>>())
+            .unwrap_or_default();
+        Self {
+            input: Input::default(),
+            open: false,
+            pos: history.len(),
+            history,
+            draft: String::new(),
+            message: None,
+            path: path.to_path_buf(),
+        }
+    }
+
+    pub fn open(&mut self) {
+        self.open = true;
+        self.input = Input::default();
+        self.pos = self.history.len();
+        self.message = None;
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+        self.message = None;
+    }
+
+    /// Show the previous line of the history
+    pub fn back(&mut self) {
+        if self.pos == 0 {
+            return;
+        }
+        if self.pos == self.history.len() {
+            self.draft = self.input.value().to_string();
+        }
+        self.pos -= 1;
+        self.input = Input::new(self.history[self.pos].clone());
+    }
+
+    /// Show the next line of the history, or the line being typed after it
+    pub fn forward(&mut self) {
+        if self.pos >= self.history.len() {
+            return;
+        }
+        self.pos += 1;
+        self.input = Input::new(match self.history.get(self.pos) {
+            Some(line) => line.clone(),
+            None => self.draft.clone(),
+        });
+    }
+
+    /// Close the line and remember it, returning what was typed
+    pub fn submit(&mut self) -> String {
+        let line = self.input.value().trim().to_string();
+        self.open = false;
+        if !line.is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+            let skip = self.history.len().saturating_sub(HISTORY);
+            self.history.drain(..skip);
+            // Losing the history is not worth interrupting anyone for
+            let _ = fs::write(&self.path, self.history.join("\n") + "\n");
+        }
+        self.pos = self.history.len();
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("session new oh"),
+            Ok(Cmd::Session(SessionCmd::New {
+                name: "oh".to_string()
+            }))
+        );
+        assert_eq!(
+            parse(" export  csv ~/x.csv"),
+            Ok(Cmd::Export {
+                format: ExportFormat::Csv,
+                path: "~/x.csv".to_string()
+            })
+        );
+        assert_eq!(parse("goto 1534"), Ok(Cmd::Goto { solve: 1534 }));
+        assert!(parse("seed many")
+            .unwrap_err()
+            .contains("invalid value 'many'"));
+        assert!(parse("fly").is_err());
+    }
+
+    #[test]
+    fn test_complete() {
+        let candidates = Candidates {
+            themes: vec!["dark".to_string(), "light".to_string()],
+            sessions: vec!["default".to_string(), "oh".to_string()],
+            rooms: vec![
+                "test-net".to_string(),
+                "cube-tui/oh".to_string(),
+                "ébène".to_string(),
+                "été".to_string(),
+            ],
+        };
+        let complete = |line| complete(line, &candidates);
+        assert_eq!(complete("th").0, "theme ");
+        assert_eq!(complete("theme l").0, "theme light ");
+        assert_eq!(complete("session sw").0, "session switch ");
+        assert_eq!(complete("session switch ").1, vec!["default", "oh"]);
+        assert_eq!(complete("room switch c").0, "room switch cube-tui/oh ");
+        assert_eq!(complete("export ").1, vec!["csv", "txt"]);
+        // Several matches complete as far as they agree
+        let (line, matches) = complete("se");
+        assert_eq!(line, "se");
+        assert_eq!(matches, vec!["session", "seed", "search"]);
+        assert_eq!(complete("seed 4"), ("seed 4".to_string(), vec![]));
+        // Room names are typed by users and need not be ASCII
+        let (line, matches) = complete("room switch é");
+        assert_eq!(line, "room switch é");
+        assert_eq!(matches, vec!["ébène", "été"]);
+        assert_eq!(complete("room switch ét").0, "room switch été ");
+    }
+}
@@ -1,28 +1,61 @@
 use std::{
+    env,
     fs::{create_dir_all, read_to_string, File},
     io::Write,
-    path::Path,
+    path::{Path, PathBuf},
     str::FromStr,
 };
 
-use color_eyre::eyre::{bail, Error, Result};
+use color_eyre::eyre::{bail, Error, Result, WrapErr};
+use libp2p::Multiaddr;
 use serde::{Deserialize, Serialize};
 
+use super::keymap::{self, Keymap, KeymapConfig};
+use super::theme::Theme;
+use crate::cube::bld::{LetterScheme, SPEFFZ};
+use crate::ui::layout::{self, Size, PANELS};
 use crate::utils::pathing::config_path;
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
-#[serde(default)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
 pub struct CompleteConfig {
     /// Internal functionality
     pub terminal: TerminalConfig,
     /// What everything looks like to the user
     pub frontend: FrontendConfig,
+    /// Where the panels of the default screen go
+    pub layout: LayoutConfig,
+    pub timer: TimerConfig,
+    pub stats: StatsConfig,
+    /// Where solves and other data are kept
+    pub storage: StorageConfig,
+    pub networking: NetworkingConfig,
+    /// Blindfolded memo helper
+    pub bld: BldConfig,
+    /// Keys per context and action, e.g. `[keymap.global] quit = ["q"]`
+    pub keymap: KeymapConfig,
+}
+
+impl Default for CompleteConfig {
+    fn default() -> Self {
+        Self {
+            terminal: TerminalConfig::default(),
+            frontend: FrontendConfig::default(),
+            layout: LayoutConfig::default(),
+            timer: TimerConfig::default(),
+            stats: StatsConfig::default(),
+            storage: StorageConfig::default(),
+            networking: NetworkingConfig::default(),
+            bld: BldConfig::default(),
+            keymap: keymap::default_config(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
-#[serde(default)]
+#[serde(default, deny_unknown_fields)]
 pub struct TerminalConfig {
-    /// How often the terminal will update
+    /// Seconds between redraws while nothing is running
     pub tick_delay: u64,
 }
 impl Default for TerminalConfig {
@@ -32,7 +65,7 @@ impl Default for TerminalConfig {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
-#[serde(default)]
+#[serde(default, deny_unknown_fields)]
 pub struct FrontendConfig {
     /// The margin around the main window from to the terminal border
     pub margin: u16,
@@ -40,7 +73,11 @@ pub struct FrontendConfig {
     pub cursor_shape: CursorType,
     /// If the cursor should be blinking.
     pub blinking_cursor: bool,
+    /// Shown in the chat panel while the room has no messages
     pub default_message: String,
+    /// A built-in theme (dark, light, high-contrast), the name of a file in
+    /// the `themes` directory next to this config, or a path to a theme file
+    pub theme: String,
 }
 
 impl Default for FrontendConfig {
@@ -49,16 +86,271 @@ impl Default for FrontendConfig {
             margin: 2,
             cursor_shape: CursorType::User,
             blinking_cursor: true,
-            default_message: format!(
-                "> defaults:\n> margin={}\n> Cursor::Type={:?}\n> blinking_cursor={}",
-                2,
-                CursorType::User,
-                true
-            ),
+            default_message: "no messages yet, say hi to the room".to_string(),
+            theme: "dark".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PanelConfig {
+    /// One of tools, timer, times, stats, scramble or main
+    pub panel: String,
+    /// Rows, a percentage like "33%", or "fill"
+    pub height: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ColumnConfig {
+    /// Columns, a percentage like "33%", or "fill"
+    pub width: String,
+    /// Panels from top to bottom
+    pub panels: Vec<PanelConfig>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayoutConfig {
+    /// Below this terminal width the single column `narrow` layout is used
+    pub narrow_width: u16,
+    /// Columns from left to right. Panels left out are not shown.
+    pub columns: Vec<ColumnConfig>,
+    pub narrow: Vec<PanelConfig>,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        let panels = |panels: &[(&str, &str)]| {
+            panels
+                .iter()
+                .map(|(panel, height)| PanelConfig {
+                    panel: panel.to_string(),
+                    height: height.to_string(),
+                })
+                .collect::<Vec<PanelConfig>>()
+        };
+        Self {
+            narrow_width: 100,
+            columns: vec![
+                ColumnConfig {
+                    width: "33%".to_string(),
+                    panels: panels(&[("tools", "3"), ("timer", "7"), ("times", "fill")]),
+                },
+                ColumnConfig {
+                    width: "fill".to_string(),
+                    panels: panels(&[("stats", "3"), ("scramble", "10"), ("main", "fill")]),
+                },
+            ],
+            narrow: panels(&[
+                ("tools", "3"),
+                ("timer", "3"),
+                ("scramble", "10"),
+                ("stats", "3"),
+                ("times", "fill"),
+                ("main", "fill"),
+            ]),
+        }
+    }
+}
+
+impl LayoutConfig {
+    fn validate(&self) -> Result<()> {
+        if self.columns.is_empty() {
+            bail!("layout.columns: must have at least one column");
+        }
+        let mut seen = vec![];
+        for (i, column) in self.columns.iter().enumerate() {
+            if let Err(e) = column.width.parse::<Size>() {
+                bail!("layout.columns[{i}].width: {e}");
+            }
+            let key = format!("layout.columns[{i}].panels");
+            Self::validate_panels(&key, &column.panels, &mut seen)?;
+        }
+        Self::validate_panels("layout.narrow", &self.narrow, &mut vec![])
+    }
+
+    fn validate_panels(key: &str, panels: &[PanelConfig], seen: &mut Vec<String>) -> Result<()> {
+        if panels.is_empty() {
+            bail!("{key}: must list at least one panel");
+        }
+        for (i, panel) in panels.iter().enumerate() {
+            if layout::panel(&panel.panel).is_none() {
+                bail!(
+                    "{key}[{i}].panel: unknown panel {:?}, expected one of {}",
+                    panel.panel,
+                    PANELS.map(|(name, _)| name).join(", ")
+                );
+            }
+            if seen.contains(&panel.panel) {
+                bail!("{key}[{i}].panel: {:?} is already placed", panel.panel);
+            }
+            seen.push(panel.panel.clone());
+            if let Err(e) = panel.height.parse::<Size>() {
+                bail!("{key}[{i}].height: {e}");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimerConfig {
+    /// Milliseconds between redraws while the timer runs
+    pub refresh_ms: u64,
+    /// Decimals of a finished time
+    pub decimals: usize,
+    /// Show `solving` instead of the running time
+    pub hide_running: bool,
+    /// Draw the time in big digits when the timer panel is tall enough
+    pub big_digits: bool,
+    /// Hide everything but the timer while it runs
+    pub focus: bool,
+}
+
+impl Default for TimerConfig {
+    fn default() -> Self {
+        Self {
+            refresh_ms: 100,
+            decimals: 3,
+            hide_running: false,
+            big_digits: true,
+            focus: true,
+        }
+    }
+}
+
+/// Statistics the stats boxes can show
+pub const STATS: [&str; 6] = ["best", "ao5", "ao12", "ao100", "ao1k", "mean"];
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct StatsConfig {
+    /// One box per entry, any of best, ao5, ao12, ao100, ao1k and mean
+    pub panels: Vec<String>,
+    /// Decimals of times in the stats and the times table
+    pub decimals: usize,
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        Self {
+            panels: vec!["best".to_string(), "ao5".to_string(), "ao12".to_string()],
+            decimals: 2,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// Directory for times, case sets and the library. Empty means
+    /// `~/.local/share/cube-tui`, a leading `~` is the home directory.
+    pub data_dir: String,
+    /// File name of the solves inside `data_dir`
+    pub times_file: String,
+    /// Write the times after every solve instead of only when quitting
+    pub autosave: bool,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            data_dir: String::new(),
+            times_file: "times".to_string(),
+            autosave: true,
+        }
+    }
+}
+
+impl StorageConfig {
+    pub fn times_path(&self) -> Result<PathBuf> {
+        let home = || env::var("HOME").wrap_err("HOME is not set");
+        let dir = match self.data_dir.as_str() {
+            "" => PathBuf::from(home()?).join(".local/share/cube-tui"),
+            d if d == "~" || d.starts_with("~/") => PathBuf::from(home()? + &d[1..]),
+            d => PathBuf::from(d),
+        };
+        Ok(dir.join(&self.times_file))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkingConfig {
+    /// The default chat room, a gossipsub topic
+    pub topic: String,
+    /// More rooms to join at start
+    pub rooms: Vec<String>,
+    /// Join the room `cube-tui/<session>` when switching to a session
+    pub session_rooms: bool,
+    /// Name sent along with our messages, empty to go by peer id
+    pub nickname: String,
+    /// Multiaddrs to listen on, port 0 lets the OS pick
+    pub listen_addrs: Vec<String>,
+    /// Multiaddrs dialled at start and redialled whenever the connection
+    /// drops, for networks mDNS does not reach. `--peer` adds to these.
+    pub bootstrap_peers: Vec<String>,
+    /// Find peers on the local network by multicast DNS
+    pub mdns: bool,
+    pub heartbeat_secs: u64,
+}
+
+impl Default for NetworkingConfig {
+    fn default() -> Self {
+        Self {
+            topic: "test-net".to_string(),
+            rooms: vec![],
+            session_rooms: true,
+            nickname: String::new(),
+            listen_addrs: vec![
+                "/ip4/0.0.0.0/udp/0/quic-v1".to_string(),
+                "/ip4/0.0.0.0/tcp/0".to_string(),
+            ],
+            bootstrap_peers: vec![],
+            mdns: true,
+            heartbeat_secs: 10,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct BldConfig {
+    /// Letters of the 24 corner stickers, in Speffz order (UBL UBR UFR UFL, then L F R B D)
+    pub corner_scheme: String,
+    /// Letters of the 24 edge stickers, in Speffz order (UB UR UF UL, then L F R B D)
+    pub edge_scheme: String,
+    /// Pieces the memo is traced from, named by their faces
+    pub corner_buffer: String,
+    pub edge_buffer: String,
+}
+
+impl Default for BldConfig {
+    fn default() -> Self {
+        Self {
+            corner_scheme: SPEFFZ.to_string(),
+            edge_scheme: SPEFFZ.to_string(),
+            corner_buffer: "UBL".to_string(),
+            edge_buffer: "UR".to_string(),
         }
     }
 }
 
+impl BldConfig {
+    pub fn letter_scheme(&self) -> Result<LetterScheme, String> {
+        LetterScheme::new(
+            &self.corner_scheme,
+            &self.edge_scheme,
+            &self.corner_buffer,
+            &self.edge_buffer,
+        )
+        .map_err(|e| format!("bld: {}", e))
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum CursorType {
@@ -91,25 +383,23 @@ impl CompleteConfig {
         let path_str = config_path("config.toml");
 
         let p = Path::new(&path_str);
-        //println!("path_str:\n{:?}", p);
 
         if !p.exists() {
-            create_dir_all(p.parent().unwrap()).unwrap();
-
-            let default_toml_string = toml::to_string(&Self::default()).unwrap();
-            let mut file = File::create(path_str.clone()).unwrap();
-            file.write_all(default_toml_string.as_bytes()).unwrap();
-            //println!("default_toml_string:\n{:?}", default_toml_string);
-
-            let config: Self = toml::from_str(default_toml_string.as_str()).unwrap();
+            let config = Self::default();
+            create_dir_all(p.parent().unwrap_or(Path::new(".")))
+                .wrap_err_with(|| format!("Could not create the directory of {path_str}"))?;
+            let contents = toml::to_string(&config)?;
+            File::create(&path_str)
+                .and_then(|mut file| file.write_all(contents.as_bytes()))
+                .wrap_err_with(|| format!("Could not write {path_str}"))?;
             print!("Configuration was generated at {path_str}, please fill it out with necessary information.");
             Ok(config)
         } else if let Ok(config_contents) = read_to_string(p) {
-            let config: Self = toml::from_str(config_contents.as_str()).unwrap();
-
-            // Remember to check for any important missing config items here!
-            //println!("config_contents:\n{:?}", config);
-
+            let config: Self = toml::from_str(config_contents.as_str())
+                .wrap_err_with(|| format!("Invalid configuration in {path_str}"))?;
+            config
+                .validate()
+                .wrap_err_with(|| format!("Invalid configuration in {path_str}"))?;
             Ok(config)
         } else {
             bail!(
@@ -118,4 +408,129 @@ impl CompleteConfig {
             )
         }
     }
+
+    /// Check the values serde cannot, naming the key of the first bad one
+    pub fn validate(&self) -> Result<()> {
+        if self.terminal.tick_delay == 0 {
+            bail!("terminal.tick_delay: must be at least 1 second");
+        }
+        self.layout.validate()?;
+        if !(10..=1000).contains(&self.timer.refresh_ms) {
+            bail!(
+                "timer.refresh_ms: must be between 10 and 1000, got {}",
+                self.timer.refresh_ms
+            );
+        }
+        if self.timer.decimals > 3 {
+            bail!(
+                "timer.decimals: must be at most 3, got {}",
+                self.timer.decimals
+            );
+        }
+        if self.stats.decimals > 3 {
+            bail!(
+                "stats.decimals: must be at most 3, got {}",
+                self.stats.decimals
+            );
+        }
+        if self.stats.panels.is_empty() || self.stats.panels.len() > STATS.len() {
+            bail!(
+                "stats.panels: must list between 1 and {} stats",
+                STATS.len()
+            );
+        }
+        for (i, panel) in self.stats.panels.iter().enumerate() {
+            if !STATS.contains(&panel.as_str()) {
+                bail!(
+                    "stats.panels[{i}]: unknown stat {panel:?}, expected one of {}",
+                    STATS.join(", ")
+                );
+            }
+        }
+        if self.storage.times_file.is_empty() || self.storage.times_file.contains('/') {
+            bail!("storage.times_file: must be a file name");
+        }
+        if self.networking.topic.is_empty() {
+            bail!("networking.topic: must not be empty");
+        }
+        for (i, addr) in self.networking.listen_addrs.iter().enumerate() {
+            if let Err(e) = addr.parse::<Multiaddr>() {
+                bail!("networking.listen_addrs[{i}]: {addr:?} is not a multiaddr: {e}");
+            }
+        }
+        for (i, addr) in self.networking.bootstrap_peers.iter().enumerate() {
+            if let Err(e) = addr.parse::<Multiaddr>() {
+                bail!("networking.bootstrap_peers[{i}]: {addr:?} is not a multiaddr: {e}");
+            }
+        }
+        for (i, room) in self.networking.rooms.iter().enumerate() {
+            if room.is_empty() || room.chars().any(char::is_whitespace) {
+                bail!("networking.rooms[{i}]: {room:?} is not a room name");
+            }
+        }
+        if self.networking.nickname.chars().any(char::is_control) {
+            bail!("networking.nickname: must not contain control characters");
+        }
+        if self.networking.heartbeat_secs == 0 {
+            bail!("networking.heartbeat_secs: must be at least 1");
+        }
+        if let Err(e) = self.bld.letter_scheme() {
+            bail!(e);
+        }
+        if let Err(e) = Theme::load(&self.frontend.theme) {
+            bail!("frontend.theme: {e}");
+        }
+        if let Err(e) = Keymap::new(&self.keymap) {
+            bail!(e);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config_roundtrips_and_validates() {
+        let text = toml::to_string(&CompleteConfig::default()).unwrap();
+        let config: CompleteConfig = toml::from_str(&text).unwrap();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_errors_name_the_key() {
+        let err = toml::from_str::<CompleteConfig>("[terminal]\ntick_dely = 3\n").unwrap_err();
+        assert!(err.to_string().contains("tick_dely"), "{err}");
+
+        let config: CompleteConfig =
+            toml::from_str("[stats]\npanels = [\"best\", \"ao50\"]\n").unwrap();
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.starts_with("stats.panels[1]"), "{err}");
+
+        let config: CompleteConfig = toml::from_str("[bld]\nedge_buffer = \"UX\"\n").unwrap();
+        assert!(config
+            .validate()
+            .unwrap_err()
+            .to_string()
+            .starts_with("bld:"));
+
+        let config: CompleteConfig =
+            toml::from_str("[keymap.global]\nhelp = [\"ctrl-q\"]\n").unwrap();
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.starts_with("keymap.global:"), "{err}");
+
+        let config: CompleteConfig =
+            toml::from_str("[frontend]\ntheme = \"no/such/theme.toml\"\n").unwrap();
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.starts_with("frontend.theme:"), "{err}");
+
+        let mut config = CompleteConfig::default();
+        config.layout.columns[1].panels[0].panel = "times".to_string();
+        let err = config.validate().unwrap_err().to_string();
+        assert!(
+            err.starts_with("layout.columns[1].panels[0].panel"),
+            "{err}"
+        );
+    }
 }
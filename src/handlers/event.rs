@@ -1,10 +1,21 @@
-use std::time::Duration;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
-use crossterm::event::{self, Event as CEvent, KeyCode, KeyModifiers, MouseButton, MouseEventKind};
-use tokio::{sync::mpsc, time::Instant};
+use crossterm::event::{
+    self, Event as CEvent, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent,
+    MouseEventKind,
+};
+use tokio::sync::mpsc;
+
+use crate::chat::{outbox::Delivery, wire::Envelope};
 
 #[allow(dead_code)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Key {
     // Keyboard controls
     Backspace,
@@ -37,13 +48,148 @@ pub enum Key {
     Moved,
 }
 
-pub enum Event<I> {
-    Input(I),
+impl From<KeyEvent> for Key {
+    fn from(key: KeyEvent) -> Self {
+        match key.code {
+            KeyCode::Backspace => Key::Backspace,
+            KeyCode::Esc => Key::Esc,
+            KeyCode::Up => Key::Up,
+            KeyCode::Down => Key::Down,
+            KeyCode::Left => Key::Left,
+            KeyCode::Right => Key::Right,
+            KeyCode::Home => Key::Home,
+            KeyCode::End => Key::End,
+            KeyCode::Delete => Key::Delete,
+            KeyCode::Insert => Key::Insert,
+            KeyCode::PageUp => Key::PageUp,
+            KeyCode::PageDown => Key::PageDown,
+            KeyCode::Tab => Key::Tab,
+            KeyCode::BackTab => Key::BackTab,
+            KeyCode::Enter => Key::Enter,
+            KeyCode::F(k) => Key::F(k),
+            KeyCode::Char(c) => match key.modifiers {
+                KeyModifiers::NONE | KeyModifiers::SHIFT => Key::Char(c),
+                KeyModifiers::CONTROL => Key::Ctrl(c),
+                KeyModifiers::ALT => Key::Alt(c),
+                _ => Key::Null,
+            },
+            _ => Key::Null,
+        }
+    }
+}
+
+impl From<MouseEvent> for Key {
+    fn from(mouse: MouseEvent) -> Self {
+        match mouse.kind {
+            MouseEventKind::ScrollDown => Key::ScrollDown,
+            MouseEventKind::ScrollUp => Key::ScrollUp,
+            MouseEventKind::Down(button) => Key::PressedButton(button),
+            MouseEventKind::Up(button) => Key::ReleasedButton(button),
+            MouseEventKind::Drag(button) => Key::Drag(button),
+            MouseEventKind::Moved => Key::Moved,
+        }
+    }
+}
+
+/// Something that happened on the network, e.g. a chat message
+#[derive(Debug, Clone)]
+pub enum NetworkEvent {
+    /// The swarm is up with this peer id
+    Started(String),
+    /// The swarm listens on this multiaddr
+    Listening(String),
+    PeerJoined(String),
+    PeerLeft(String),
+    /// An envelope from peer id `source`, with the gossipsub message id of
+    /// its payload
+    Message {
+        id: String,
+        topic: String,
+        source: String,
+        envelope: Envelope,
+    },
+    /// A direct message from `peer`
+    Direct {
+        peer: String,
+        id: u64,
+        text: String,
+    },
+    /// Where direct message `id` of ours to `peer` is
+    Delivery {
+        peer: String,
+        id: u64,
+        state: Delivery,
+    },
+    /// Whether our room message `id` went out to `topic`, or why not
+    Published {
+        topic: String,
+        id: String,
+        result: Result<(), String>,
+    },
+    Status(String),
+}
+
+/// Output of a background task such as a git command
+#[derive(Debug, Clone)]
+pub struct TaskResult {
+    pub name: String,
+    pub output: Result<String, String>,
+}
+
+/// Everything the TUI reacts to, merged into one stream
+#[derive(Debug, Clone)]
+pub enum AppEvent {
+    /// Raw key press, keeping the modifiers text inputs need
+    Key(KeyEvent),
+    /// Raw mouse event, keeping the position for hit testing
+    Mouse(MouseEvent),
+    Resize(u16, u16),
     Tick,
+    Network(NetworkEvent),
+    Task(TaskResult),
+}
+
+/// Handle for pushing events into the stream from other tasks
+#[derive(Clone)]
+pub struct EventSender {
+    tx: mpsc::Sender<AppEvent>,
+}
+
+impl EventSender {
+    /// A sender and the stream it feeds, with no terminal behind it
+    pub fn channel(capacity: usize) -> (Self, mpsc::Receiver<AppEvent>) {
+        let (tx, rx) = mpsc::channel(capacity);
+        (Self { tx }, rx)
+    }
+
+    pub async fn send(&self, event: AppEvent) -> bool {
+        self.tx.send(event).await.is_ok()
+    }
+
+    /// Send from synchronous code, e.g. a network thread outside the runtime
+    pub fn blocking_send(&self, event: AppEvent) -> bool {
+        self.tx.blocking_send(event).is_ok()
+    }
+
+    /// Run `task` on the blocking pool and deliver its output as
+    /// `AppEvent::Task` named `name`
+    pub fn spawn_task<F>(&self, name: &str, task: F)
+    where
+        F: FnOnce() -> Result<String, String> + Send + 'static,
+    {
+        let tx = self.tx.clone();
+        let name = name.to_string();
+        tokio::task::spawn_blocking(move || {
+            let output = task();
+            let _ = tx.blocking_send(AppEvent::Task(TaskResult { name, output }));
+        });
+    }
 }
 
 pub struct Events {
-    rx: mpsc::Receiver<Event<Key>>,
+    rx: mpsc::Receiver<AppEvent>,
+    tx: mpsc::Sender<AppEvent>,
+    tick_rate: Arc<AtomicU64>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -55,74 +201,39 @@ pub struct Config {
 impl Events {
     pub async fn with_config(config: Config) -> Self {
         let (tx, rx) = mpsc::channel(100);
+        let tick_rate = Arc::new(AtomicU64::new(config.tick_rate.as_millis() as u64));
 
-        tokio::spawn(async move {
+        // Terminal input blocks, so it is read on the blocking pool. Ticks are
+        // sent from the same loop so a changed tick rate applies right away.
+        let input_tx = tx.clone();
+        let rate = tick_rate.clone();
+        tokio::task::spawn_blocking(move || {
             let mut last_tick = Instant::now();
 
             loop {
-                let timeout = config
-                    .tick_rate
+                let tick_rate = Duration::from_millis(rate.load(Ordering::Relaxed));
+                let timeout = tick_rate
                     .checked_sub(last_tick.elapsed())
                     .unwrap_or_else(|| Duration::from_secs(0));
 
-                if event::poll(timeout).unwrap() {
-                    match event::read() {
-                        Ok(CEvent::Key(key)) => {
-                            let key = match key.code {
-                                KeyCode::Backspace => Key::Backspace,
-                                KeyCode::Esc => Key::Esc,
-                                KeyCode::Up => Key::Up,
-                                KeyCode::Down => Key::Down,
-                                KeyCode::Left => Key::Left,
-                                KeyCode::Right => Key::Right,
-                                KeyCode::Home => Key::Home,
-                                KeyCode::End => Key::End,
-                                KeyCode::Delete => Key::Delete,
-                                KeyCode::Insert => Key::Insert,
-                                KeyCode::PageUp => Key::PageUp,
-                                KeyCode::PageDown => Key::PageDown,
-                                KeyCode::Tab => Key::Tab,
-                                KeyCode::BackTab => Key::BackTab,
-                                KeyCode::Enter => Key::Enter,
-                                KeyCode::F(k) => Key::F(k),
-                                KeyCode::Char(c) => match key.modifiers {
-                                    KeyModifiers::NONE | KeyModifiers::SHIFT => Key::Char(c),
-                                    KeyModifiers::CONTROL => Key::Ctrl(c),
-                                    KeyModifiers::ALT => Key::Alt(c),
-                                    _ => Key::Null,
-                                },
-                                _ => Key::Null,
-                            };
-
-                            if let Err(err) = tx.send(Event::Input(key)).await {
-                                eprintln!("Keyboard input error: {err}");
-
-                                return;
-                            }
+                if event::poll(timeout).unwrap_or(false) {
+                    let event = match event::read() {
+                        Ok(CEvent::Key(key)) if key.kind != KeyEventKind::Release => {
+                            Some(AppEvent::Key(key))
                         }
-                        Ok(CEvent::Mouse(key)) => {
-                            let key = match key.kind {
-                                MouseEventKind::ScrollDown => Key::ScrollDown,
-                                MouseEventKind::ScrollUp => Key::ScrollUp,
-                                MouseEventKind::Down(button) => Key::PressedButton(button),
-                                MouseEventKind::Up(button) => Key::ReleasedButton(button),
-                                MouseEventKind::Drag(button) => Key::Drag(button),
-                                MouseEventKind::Moved => Key::Moved,
-                            };
-
-                            if let Err(err) = tx.send(Event::Input(key)).await {
-                                eprintln!("Mouse input error: {err}");
-
-                                return;
-                            }
+                        Ok(CEvent::Mouse(mouse)) => Some(AppEvent::Mouse(mouse)),
+                        Ok(CEvent::Resize(w, h)) => Some(AppEvent::Resize(w, h)),
+                        _ => None,
+                    };
+                    if let Some(event) = event {
+                        if input_tx.blocking_send(event).is_err() {
+                            return;
                         }
-                        _ => (),
                     }
                 }
 
-                if last_tick.elapsed() >= config.tick_rate {
-                    if let Err(err) = tx.send(Event::Tick).await {
-                        eprintln!("{err}");
+                if last_tick.elapsed() >= tick_rate {
+                    if input_tx.blocking_send(AppEvent::Tick).is_err() {
                         return;
                     }
 
@@ -131,10 +242,21 @@ impl Events {
             }
         });
 
-        Self { rx }
+        Self { rx, tx, tick_rate }
     }
 
-    pub async fn next(&mut self) -> Option<Event<Key>> {
+    pub async fn next(&mut self) -> Option<AppEvent> {
         self.rx.recv().await
     }
+
+    pub fn sender(&self) -> EventSender {
+        EventSender {
+            tx: self.tx.clone(),
+        }
+    }
+
+    pub fn set_tick_rate(&self, tick_rate: Duration) {
+        self.tick_rate
+            .store(tick_rate.as_millis() as u64, Ordering::Relaxed);
+    }
 }
@@ -0,0 +1,467 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Formatter},
+    str::FromStr,
+};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Where a binding applies. Bindings of the active context are looked up
+/// before the global ones, so a context can reuse a global key on purpose.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Context {
+    Global,
+    Times,
+    Tools,
+    Timer,
+    Trainer,
+    Replay,
+    Library,
+    Memo,
+    Solve,
+    /// Text inputs: chat, reconstructions and library edits
+    Input,
+    /// Chat and direct message panels, looked up before `Input`
+    Chat,
+}
+
+impl Context {
+    pub const ALL: [Context; 11] = [
+        Context::Global,
+        Context::Times,
+        Context::Tools,
+        Context::Timer,
+        Context::Trainer,
+        Context::Replay,
+        Context::Library,
+        Context::Memo,
+        Context::Solve,
+        Context::Input,
+        Context::Chat,
+    ];
+
+    /// Name of the context in the `[keymap]` config section
+    pub fn name(&self) -> &'static str {
+        match self {
+            Context::Global => "global",
+            Context::Times => "times",
+            Context::Tools => "tools",
+            Context::Timer => "timer",
+            Context::Trainer => "trainer",
+            Context::Replay => "replay",
+            Context::Library => "library",
+            Context::Memo => "memo",
+            Context::Solve => "solve",
+            Context::Input => "input",
+            Context::Chat => "chat",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Context::Global => "Default",
+            Context::Times => "Times block",
+            Context::Tools => "Tools block",
+            Context::Timer => "Timer block",
+            Context::Trainer => "Trainer tool",
+            Context::Replay => "Replay",
+            Context::Library => "Library",
+            Context::Memo => "Memo",
+            Context::Solve => "Solve details",
+            Context::Input => "Text input",
+            Context::Chat => "Chat panels",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    Timer,
+    Back,
+    Select,
+    Left,
+    Down,
+    Up,
+    Right,
+    Help,
+    Library,
+    Memo,
+    Write,
+    Delete,
+    Replay,
+    NextTool,
+    PreviousTool,
+    Scramble,
+    Press,
+    Skip,
+    NextSet,
+    Play,
+    StepForward,
+    StepBack,
+    Restart,
+    Edit,
+    AddAlg,
+    Notes,
+    CycleMain,
+    Submit,
+    Details,
+    Comment,
+    Penalty,
+    Copy,
+    Command,
+    Complete,
+    HistoryBack,
+    HistoryForward,
+    ScrollBack,
+    ScrollForward,
+    NextRoom,
+    PreviousRoom,
+}
+
+/// Every action a context accepts: config name, help text and default keys
+type Binding = (
+    Context,
+    Action,
+    &'static str,
+    &'static str,
+    &'static [&'static str],
+);
+
+#[rustfmt::skip]
+const DEFAULTS: &[Binding] = &[
+    (Context::Global, Action::Quit, "quit", "Quit the app", &["q", "ctrl-q"]),
+    (Context::Global, Action::Timer, "timer", "Start or stop the timer", &["space"]),
+    (Context::Global, Action::Back, "back", "Back out of the current screen or block", &["esc", "ctrl-c", ">"]),
+    (Context::Global, Action::Select, "select", "Make the selected block active", &["enter"]),
+    (Context::Global, Action::Left, "left", "Navigate left", &["h", "left"]),
+    (Context::Global, Action::Down, "down", "Navigate down", &["j", "down"]),
+    (Context::Global, Action::Up, "up", "Navigate up", &["k", "up"]),
+    (Context::Global, Action::Right, "right", "Navigate right", &["l", "right"]),
+    (Context::Global, Action::Help, "help", "Open this help", &["?", "<"]),
+    (Context::Global, Action::Library, "library", "Open the algorithm library", &["a"]),
+    (Context::Global, Action::Memo, "memo", "Blindfolded memo of the last or selected solve", &["b"]),
+    (Context::Global, Action::Write, "write", "Write and reload the times", &["ctrl-w"]),
+    (Context::Global, Action::Command, "command", "Open the command line", &[":"]),
+    (Context::Times, Action::Delete, "delete", "Delete the selected solve", &["d"]),
+    (Context::Times, Action::Replay, "replay", "Replay or edit the solve reconstruction", &["r"]),
+    (Context::Times, Action::Details, "details", "Show the solve details", &["enter"]),
+    (Context::Tools, Action::NextTool, "next_tool", "Next tool", &["tab"]),
+    (Context::Tools, Action::PreviousTool, "previous_tool", "Previous tool", &["backtab"]),
+    (Context::Timer, Action::Scramble, "scramble", "New scramble", &["n"]),
+    (Context::Trainer, Action::Press, "press", "Show the case and start/stop the drill", &["space"]),
+    (Context::Trainer, Action::Skip, "skip", "Skip the case", &["n"]),
+    (Context::Trainer, Action::NextSet, "next_set", "Switch case set", &["s"]),
+    (Context::Replay, Action::Play, "play", "Play or pause", &["space"]),
+    (Context::Replay, Action::StepForward, "step_forward", "Step forward", &["l", "right"]),
+    (Context::Replay, Action::StepBack, "step_back", "Step back", &["h", "left"]),
+    (Context::Replay, Action::Restart, "restart", "Back to the scramble", &["0", "home"]),
+    (Context::Replay, Action::Edit, "edit", "Edit the reconstruction", &["e"]),
+    (Context::Replay, Action::Back, "back", "Close the replay", &["esc", "q"]),
+    (Context::Library, Action::Down, "down", "Next case", &["j", "down"]),
+    (Context::Library, Action::Up, "up", "Previous case", &["k", "up"]),
+    (Context::Library, Action::AddAlg, "add_alg", "Add an algorithm", &["a"]),
+    (Context::Library, Action::Notes, "notes", "Edit the notes", &["e"]),
+    (Context::Library, Action::CycleMain, "cycle_main", "Next main algorithm", &["m"]),
+    (Context::Library, Action::Delete, "delete", "Delete the main algorithm", &["d"]),
+    (Context::Library, Action::NextSet, "next_set", "Switch case set", &["s"]),
+    (Context::Library, Action::Back, "back", "Close the library", &["esc", "q"]),
+    (Context::Memo, Action::Back, "back", "Close the memo", &["esc", "q", "b"]),
+    (Context::Solve, Action::Comment, "comment", "Edit the comment", &["c"]),
+    (Context::Solve, Action::Penalty, "penalty", "Edit the penalty", &["p"]),
+    (Context::Solve, Action::Copy, "copy", "Copy the scramble", &["y"]),
+    (Context::Solve, Action::Delete, "delete", "Delete the solve, press twice", &["d"]),
+    (Context::Solve, Action::Replay, "replay", "Replay the solve", &["r"]),
+    (Context::Solve, Action::Back, "back", "Close the details", &["esc", "q"]),
+    (Context::Input, Action::Submit, "submit", "Submit the text", &["enter"]),
+    (Context::Input, Action::Back, "back", "Cancel the edit", &["esc"]),
+    (Context::Input, Action::Complete, "complete", "Complete a command", &["tab"]),
+    (Context::Input, Action::HistoryBack, "history_back", "Previous command", &["up"]),
+    (Context::Input, Action::HistoryForward, "history_forward", "Next command", &["down"]),
+    (Context::Chat, Action::ScrollBack, "scroll_back", "Scroll back a page", &["pageup"]),
+    (Context::Chat, Action::ScrollForward, "scroll_forward", "Scroll forward a page", &["pagedown"]),
+    (Context::Chat, Action::NextRoom, "next_room", "Next room or conversation", &["tab"]),
+    (Context::Chat, Action::PreviousRoom, "previous_room", "Previous room or conversation", &["backtab"]),
+];
+
+/// A key with its modifiers, written like `q`, `space`, `ctrl-w` or `shift-tab`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyChord {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        // Shift is part of the character itself, e.g. `?` or `Q`
+        let ignore = match key.code {
+            KeyCode::Char(_) => KeyModifiers::SHIFT,
+            _ => KeyModifiers::NONE,
+        };
+        self.code == key.code && self.modifiers - ignore == key.modifiers - ignore
+    }
+}
+
+impl FromStr for KeyChord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        // A trailing `-` is the key itself, e.g. `ctrl--`
+        while let Some((modifier, key)) = rest.split_once('-').filter(|(_, k)| !k.is_empty()) {
+            modifiers |= match modifier {
+                "ctrl" | "c" => KeyModifiers::CONTROL,
+                "alt" | "a" => KeyModifiers::ALT,
+                "shift" | "s" => KeyModifiers::SHIFT,
+                _ => return Err(format!("unknown modifier {:?} in {:?}", modifier, s)),
+            };
+            rest = key;
+        }
+        let code = match rest {
+            "space" => KeyCode::Char(' '),
+            "esc" => KeyCode::Esc,
+            "enter" => KeyCode::Enter,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" => KeyCode::Backspace,
+            "delete" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            f if f.len() > 1 && f.starts_with('f') => match f[1..].parse::<u8>() {
+                Ok(n) if (1..=12).contains(&n) => KeyCode::F(n),
+                _ => return Err(format!("unknown key {:?}", s)),
+            },
+            c if c.chars().count() == 1 => KeyCode::Char(c.chars().next().unwrap()),
+            _ => return Err(format!("unknown key {:?}", s)),
+        };
+        // Terminals send shift-tab as its own key
+        if code == KeyCode::Tab && modifiers.contains(KeyModifiers::SHIFT) {
+            return Ok(Self {
+                code: KeyCode::BackTab,
+                modifiers: modifiers - KeyModifiers::SHIFT,
+            });
+        }
+        Ok(Self { code, modifiers })
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "ctrl-"),
+            (KeyModifiers::ALT, "alt-"),
+            (KeyModifiers::SHIFT, "shift-"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                f.write_str(name)?;
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => f.write_str("space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "f{}", n),
+            KeyCode::BackTab => f.write_str("shift-tab"),
+            code => f.write_str(&format!("{:?}", code).to_lowercase()),
+        }
+    }
+}
+
+/// Active key bindings of one action
+#[derive(Debug, Clone)]
+pub struct Bound {
+    pub context: Context,
+    pub action: Action,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub keys: Vec<KeyChord>,
+}
+
+/// The `[keymap]` config section: context name to action name to keys
+pub type KeymapConfig = BTreeMap<String, BTreeMap<String, Vec<String>>>;
+
+/// Default bindings as written to a new config file
+pub fn default_config() -> KeymapConfig {
+    let mut config = KeymapConfig::new();
+    for (context, _, name, _, keys) in DEFAULTS {
+        config
+            .entry(context.name().to_string())
+            .or_default()
+            .insert(
+                name.to_string(),
+                keys.iter().map(|k| k.to_string()).collect(),
+            );
+    }
+    config
+}
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    pub bindings: Vec<Bound>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::new(&KeymapConfig::new()).unwrap()
+    }
+}
+
+impl Keymap {
+    /// The default bindings with every action listed in `config` rebound.
+    /// Errors name the config key of an unknown context, action or key, or
+    /// of a key bound to two actions of the same context.
+    pub fn new(config: &KeymapConfig) -> Result<Self, String> {
+        let mut bindings = DEFAULTS
+            .iter()
+            .map(|(context, action, name, description, keys)| Bound {
+                context: *context,
+                action: *action,
+                name,
+                description,
+                keys: keys.iter().map(|k| k.parse().unwrap()).collect(),
+            })
+            .collect::<Vec<Bound>>();
+
+        for (context_name, actions) in config {
+            let Some(context) = Context::ALL.iter().find(|c| c.name() == context_name) else {
+                return Err(format!("keymap.{}: unknown context", context_name));
+            };
+            for (action_name, keys) in actions {
+                let key = format!("keymap.{}.{}", context_name, action_name);
+                let Some(bound) = bindings
+                    .iter_mut()
+                    .find(|b| b.context == *context && b.name == action_name)
+                else {
+                    return Err(format!("{}: unknown action", key));
+                };
+                bound.keys = keys
+                    .iter()
+                    .map(|k| k.parse::<KeyChord>())
+                    .collect::<Result<Vec<KeyChord>, String>>()
+                    .map_err(|e| format!("{}: {}", key, e))?;
+            }
+        }
+
+        for context in Context::ALL {
+            let bound = bindings.iter().filter(|b| b.context == context);
+            let mut seen: Vec<(KeyChord, &str)> = vec![];
+            for b in bound {
+                for chord in &b.keys {
+                    if let Some((_, other)) = seen.iter().find(|(k, _)| k == chord) {
+                        return Err(format!(
+                            "keymap.{}: {} is bound to both {} and {}",
+                            context.name(),
+                            chord,
+                            other,
+                            b.name
+                        ));
+                    }
+                    seen.push((*chord, b.name));
+                }
+            }
+        }
+        Ok(Self { bindings })
+    }
+
+    /// Action bound to `key` in `context`, without falling back to global
+    pub fn lookup(&self, context: Context, key: &KeyEvent) -> Option<Action> {
+        self.bindings
+            .iter()
+            .filter(|b| b.context == context)
+            .find(|b| b.keys.iter().any(|k| k.matches(key)))
+            .map(|b| b.action)
+    }
+
+    /// Keys of an action joined for hints and help, e.g. `h/left`
+    pub fn keys(&self, context: Context, action: Action) -> String {
+        self.bindings
+            .iter()
+            .find(|b| b.context == context && b.action == action)
+            .map(|b| {
+                b.keys
+                    .iter()
+                    .map(|k| k.to_string())
+                    .collect::<Vec<String>>()
+                    .join("/")
+            })
+            .unwrap_or_default()
+    }
+
+    /// `keys description` pairs for a hint line
+    pub fn hints(&self, context: Context, actions: &[(Action, &str)]) -> String {
+        actions
+            .iter()
+            .map(|(action, text)| format!("{} {}", self.keys(context, *action), text))
+            .collect::<Vec<String>>()
+            .join("   ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(text: &str) -> KeymapConfig {
+        toml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_chords_roundtrip() {
+        for chord in [
+            "q",
+            "space",
+            "ctrl-w",
+            "alt-x",
+            "shift-tab",
+            "f5",
+            "?",
+            "ctrl--",
+        ] {
+            assert_eq!(chord.parse::<KeyChord>().unwrap().to_string(), chord);
+        }
+        assert_eq!(
+            "c-w".parse::<KeyChord>().unwrap().to_string(),
+            "ctrl-w".to_string()
+        );
+        assert!("hyper-x".parse::<KeyChord>().is_err());
+        assert!("f13".parse::<KeyChord>().is_err());
+    }
+
+    #[test]
+    fn test_rebind_and_lookup() {
+        let keymap = Keymap::new(&config("[global]\nquit = [\"x\"]\n")).unwrap();
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        assert_eq!(
+            keymap.lookup(Context::Global, &key('x')),
+            Some(Action::Quit)
+        );
+        assert_eq!(keymap.lookup(Context::Global, &key('q')), None);
+        assert_eq!(
+            keymap.lookup(Context::Times, &key('d')),
+            Some(Action::Delete)
+        );
+        let shifted = KeyEvent::new(KeyCode::Char('?'), KeyModifiers::SHIFT);
+        assert_eq!(keymap.lookup(Context::Global, &shifted), Some(Action::Help));
+    }
+
+    #[test]
+    fn test_errors_name_the_key() {
+        let err = Keymap::new(&config("[global]\nhelp = [\"q\"]\n")).unwrap_err();
+        assert_eq!(err, "keymap.global: q is bound to both quit and help");
+        let err = Keymap::new(&config("[times]\nquit = [\"x\"]\n")).unwrap_err();
+        assert_eq!(err, "keymap.times.quit: unknown action");
+        let err = Keymap::new(&config("[race]\nsend = [\"x\"]\n")).unwrap_err();
+        assert_eq!(err, "keymap.race: unknown context");
+        let err = Keymap::new(&config("[chat]\nsend = [\"x\"]\n")).unwrap_err();
+        assert_eq!(err, "keymap.chat.send: unknown action");
+        // Contexts may reuse global keys
+        assert!(Keymap::new(&config("[times]\ndelete = [\"q\"]\n")).is_ok());
+    }
+}
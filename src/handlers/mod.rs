@@ -1,2 +1,5 @@
+pub mod command;
 pub mod config;
 pub mod event;
+pub mod keymap;
+pub mod theme;
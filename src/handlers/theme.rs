@@ -0,0 +1,452 @@
+use std::{
+    fmt::{self, Formatter},
+    fs::read_to_string,
+    path::PathBuf,
+    str::FromStr,
+};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use tui::style::{Color, Style};
+
+use super::command::expand_home;
+use crate::utils::pathing::config_path;
+
+/// Themes that need no file, selectable by name in `frontend.theme`
+pub const BUILTIN: [&str; 3] = ["dark", "light", "high-contrast"];
+
+/// Every semantic role a theme colors, as written in a theme file
+pub const ROLES: [&str; 23] = [
+    "border.active",
+    "border.selected",
+    "border.normal",
+    "border.input",
+    "highlight.active",
+    "highlight.selected",
+    "text.normal",
+    "text.dim",
+    "text.accent",
+    "text.error",
+    "timer.running",
+    "timer.stopped",
+    "timer.idle",
+    "chart.single",
+    "chart.ao5",
+    "chart.ao12",
+    "chart.axis",
+    "cube.u",
+    "cube.r",
+    "cube.f",
+    "cube.d",
+    "cube.l",
+    "cube.b",
+];
+
+/// Colors every terminal can show, in the order the previewer cycles them
+pub const PALETTE: [Color; 17] = [
+    Color::Reset,
+    Color::Black,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::Gray,
+    Color::DarkGray,
+    Color::LightRed,
+    Color::LightGreen,
+    Color::LightYellow,
+    Color::LightBlue,
+    Color::LightMagenta,
+    Color::LightCyan,
+    Color::White,
+];
+
+/// Name of a color as written in theme files: `magenta`, `#ff8700` or an
+/// indexed color like `208`
+pub fn color_name(color: Color) -> String {
+    match color {
+        Color::Reset => "reset".to_string(),
+        Color::Black => "black".to_string(),
+        Color::Red => "red".to_string(),
+        Color::Green => "green".to_string(),
+        Color::Yellow => "yellow".to_string(),
+        Color::Blue => "blue".to_string(),
+        Color::Magenta => "magenta".to_string(),
+        Color::Cyan => "cyan".to_string(),
+        Color::Gray => "gray".to_string(),
+        Color::DarkGray => "darkgray".to_string(),
+        Color::LightRed => "lightred".to_string(),
+        Color::LightGreen => "lightgreen".to_string(),
+        Color::LightYellow => "lightyellow".to_string(),
+        Color::LightBlue => "lightblue".to_string(),
+        Color::LightMagenta => "lightmagenta".to_string(),
+        Color::LightCyan => "lightcyan".to_string(),
+        Color::White => "white".to_string(),
+        Color::Rgb(r, g, b) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        Color::Indexed(i) => i.to_string(),
+    }
+}
+
+/// A color in a theme file, see [`color_name`] for the accepted forms
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThemeColor(pub Color);
+
+impl ThemeColor {
+    /// Plain style with this color as foreground
+    pub fn fg(&self) -> Style {
+        Style::default().fg(self.0)
+    }
+}
+
+impl FromStr for ThemeColor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.to_lowercase().replace(['_', '-', ' '], "");
+        if let Some(color) = PALETTE.iter().find(|c| color_name(**c) == name) {
+            return Ok(Self(*color));
+        }
+        match name.as_str() {
+            "grey" => return Ok(Self(Color::Gray)),
+            "darkgrey" => return Ok(Self(Color::DarkGray)),
+            _ => (),
+        }
+        if let Some(hex) = name.strip_prefix('#') {
+            let channel = |i: usize| {
+                hex.get(i..i + 2)
+                    .and_then(|c| u8::from_str_radix(c, 16).ok())
+            };
+            if let (6, Some(r), Some(g), Some(b)) = (hex.len(), channel(0), channel(2), channel(4))
+            {
+                return Ok(Self(Color::Rgb(r, g, b)));
+            }
+        }
+        if let Ok(i) = name.parse::<u8>() {
+            return Ok(Self(Color::Indexed(i)));
+        }
+        Err(format!(
+            "unknown color {:?}, expected a name like \"magenta\", \"#rrggbb\" or 0-255",
+            s
+        ))
+    }
+}
+
+impl fmt::Display for ThemeColor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        f.write_str(&color_name(self.0))
+    }
+}
+
+impl Serialize for ThemeColor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for ThemeColor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct BorderColors {
+    /// Block that takes the keys
+    pub active: ThemeColor,
+    /// Block the navigation keys point at
+    pub selected: ThemeColor,
+    pub normal: ThemeColor,
+    /// Text inputs such as the reconstruction editor
+    pub input: ThemeColor,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct HighlightColors {
+    /// Selected row of the active block
+    pub active: ThemeColor,
+    /// Selected row of any other block
+    pub selected: ThemeColor,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TextColors {
+    pub normal: ThemeColor,
+    /// Headers, hints and other secondary text
+    pub dim: ThemeColor,
+    /// Current replay move, chat nicknames
+    pub accent: ThemeColor,
+    pub error: ThemeColor,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimerColors {
+    pub running: ThemeColor,
+    /// Showing the last time
+    pub stopped: ThemeColor,
+    /// No time yet
+    pub idle: ThemeColor,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChartColors {
+    pub single: ThemeColor,
+    pub ao5: ThemeColor,
+    pub ao12: ThemeColor,
+    pub axis: ThemeColor,
+}
+
+/// Sticker colors of the cube faces
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CubeColors {
+    pub u: ThemeColor,
+    pub r: ThemeColor,
+    pub f: ThemeColor,
+    pub d: ThemeColor,
+    pub l: ThemeColor,
+    pub b: ThemeColor,
+}
+
+/// Colors of the TUI by semantic role. A theme file only needs the roles it
+/// changes, the rest come from the dark theme.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    pub border: BorderColors,
+    pub highlight: HighlightColors,
+    pub text: TextColors,
+    pub timer: TimerColors,
+    pub chart: ChartColors,
+    pub cube: CubeColors,
+}
+
+macro_rules! colors {
+    ($name:ident { $($field:ident: $color:expr),* $(,)? }) => {
+        impl Default for $name {
+            fn default() -> Self {
+                Self { $($field: ThemeColor($color)),* }
+            }
+        }
+    };
+}
+
+colors!(BorderColors {
+    active: Color::Magenta,
+    selected: Color::Magenta,
+    normal: Color::Gray,
+    input: Color::Magenta,
+});
+colors!(HighlightColors {
+    active: Color::Magenta,
+    selected: Color::Magenta,
+});
+colors!(TextColors {
+    normal: Color::White,
+    dim: Color::Gray,
+    accent: Color::Magenta,
+    error: Color::Red,
+});
+colors!(TimerColors {
+    running: Color::Magenta,
+    stopped: Color::White,
+    idle: Color::Gray,
+});
+colors!(ChartColors {
+    single: Color::Cyan,
+    ao5: Color::LightGreen,
+    ao12: Color::Magenta,
+    axis: Color::White,
+});
+colors!(CubeColors {
+    u: Color::White,
+    r: Color::Red,
+    f: Color::Green,
+    d: Color::Yellow,
+    l: Color::Rgb(255, 135, 0),
+    b: Color::Blue,
+});
+
+impl Theme {
+    /// One of the [`BUILTIN`] themes
+    pub fn builtin(name: &str) -> Option<Self> {
+        let mut theme = Self::default();
+        match name {
+            "dark" => (),
+            "light" => {
+                for (role, color) in [
+                    ("border.active", Color::Blue),
+                    ("border.selected", Color::Blue),
+                    ("border.normal", Color::DarkGray),
+                    ("border.input", Color::Blue),
+                    ("highlight.active", Color::Blue),
+                    ("highlight.selected", Color::Blue),
+                    ("text.normal", Color::Black),
+                    ("text.dim", Color::DarkGray),
+                    ("text.accent", Color::Blue),
+                    ("timer.running", Color::Blue),
+                    ("timer.stopped", Color::Black),
+                    ("timer.idle", Color::DarkGray),
+                    ("chart.single", Color::Cyan),
+                    ("chart.ao5", Color::Green),
+                    ("chart.ao12", Color::Magenta),
+                    ("chart.axis", Color::Black),
+                    ("cube.u", Color::Gray),
+                ] {
+                    *theme.role_mut(role)? = ThemeColor(color);
+                }
+            }
+            "high-contrast" => {
+                for (role, color) in [
+                    ("border.active", Color::Yellow),
+                    ("border.selected", Color::LightCyan),
+                    ("border.normal", Color::White),
+                    ("border.input", Color::Yellow),
+                    ("highlight.active", Color::Yellow),
+                    ("highlight.selected", Color::LightCyan),
+                    ("text.dim", Color::White),
+                    ("text.accent", Color::Yellow),
+                    ("text.error", Color::LightRed),
+                    ("timer.running", Color::Yellow),
+                    ("timer.idle", Color::White),
+                    ("chart.single", Color::White),
+                    ("chart.ao5", Color::LightCyan),
+                    ("chart.ao12", Color::Yellow),
+                ] {
+                    *theme.role_mut(role)? = ThemeColor(color);
+                }
+            }
+            _ => return None,
+        }
+        Some(theme)
+    }
+
+    /// A built-in theme by name, or the theme file `themes/<name>.toml` in
+    /// the config directory, or a theme file at the given path
+    pub fn load(name: &str) -> Result<Self, String> {
+        if let Some(theme) = Self::builtin(name) {
+            return Ok(theme);
+        }
+        let path = Self::path(name);
+        let text = read_to_string(&path)
+            .map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("invalid theme {}: {}", path.display(), e))
+    }
+
+    /// Where the theme called `name` is read from and written to
+    pub fn path(name: &str) -> PathBuf {
+        if name.ends_with(".toml") || name.contains('/') {
+            expand_home(name)
+        } else {
+            PathBuf::from(config_path(&format!("themes/{}.toml", name)))
+        }
+    }
+
+    /// The color of a role in [`ROLES`]
+    pub fn role(&self, role: &str) -> Option<ThemeColor> {
+        Some(match role {
+            "border.active" => self.border.active,
+            "border.selected" => self.border.selected,
+            "border.normal" => self.border.normal,
+            "border.input" => self.border.input,
+            "highlight.active" => self.highlight.active,
+            "highlight.selected" => self.highlight.selected,
+            "text.normal" => self.text.normal,
+            "text.dim" => self.text.dim,
+            "text.accent" => self.text.accent,
+            "text.error" => self.text.error,
+            "timer.running" => self.timer.running,
+            "timer.stopped" => self.timer.stopped,
+            "timer.idle" => self.timer.idle,
+            "chart.single" => self.chart.single,
+            "chart.ao5" => self.chart.ao5,
+            "chart.ao12" => self.chart.ao12,
+            "chart.axis" => self.chart.axis,
+            "cube.u" => self.cube.u,
+            "cube.r" => self.cube.r,
+            "cube.f" => self.cube.f,
+            "cube.d" => self.cube.d,
+            "cube.l" => self.cube.l,
+            "cube.b" => self.cube.b,
+            _ => return None,
+        })
+    }
+
+    /// The color of a role in [`ROLES`], to change it
+    pub fn role_mut(&mut self, role: &str) -> Option<&mut ThemeColor> {
+        Some(match role {
+            "border.active" => &mut self.border.active,
+            "border.selected" => &mut self.border.selected,
+            "border.normal" => &mut self.border.normal,
+            "border.input" => &mut self.border.input,
+            "highlight.active" => &mut self.highlight.active,
+            "highlight.selected" => &mut self.highlight.selected,
+            "text.normal" => &mut self.text.normal,
+            "text.dim" => &mut self.text.dim,
+            "text.accent" => &mut self.text.accent,
+            "text.error" => &mut self.text.error,
+            "timer.running" => &mut self.timer.running,
+            "timer.stopped" => &mut self.timer.stopped,
+            "timer.idle" => &mut self.timer.idle,
+            "chart.single" => &mut self.chart.single,
+            "chart.ao5" => &mut self.chart.ao5,
+            "chart.ao12" => &mut self.chart.ao12,
+            "chart.axis" => &mut self.chart.axis,
+            "cube.u" => &mut self.cube.u,
+            "cube.r" => &mut self.cube.r,
+            "cube.f" => &mut self.cube.f,
+            "cube.d" => &mut self.cube.d,
+            "cube.l" => &mut self.cube.l,
+            "cube.b" => &mut self.cube.b,
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_names_roundtrip() {
+        for color in PALETTE
+            .into_iter()
+            .chain([Color::Rgb(255, 135, 0), Color::Indexed(208)])
+        {
+            assert_eq!(color_name(color).parse(), Ok(ThemeColor(color)));
+        }
+        assert_eq!("Dark_Grey".parse(), Ok(ThemeColor(Color::DarkGray)));
+        assert!("#ff87".parse::<ThemeColor>().is_err());
+        assert!("mauve".parse::<ThemeColor>().is_err());
+    }
+
+    #[test]
+    fn test_builtin_themes_cover_the_roles() {
+        for name in BUILTIN {
+            let mut theme = Theme::builtin(name).unwrap();
+            for role in ROLES {
+                let color = theme.role(role);
+                assert!(color.is_some(), "{role}");
+                assert_eq!(theme.role_mut(role).copied(), color, "{role}");
+            }
+            let text = toml::to_string(&theme).unwrap();
+            assert_eq!(toml::from_str::<Theme>(&text).unwrap(), theme);
+        }
+    }
+
+    #[test]
+    fn test_partial_theme_file() {
+        let theme: Theme = toml::from_str("[timer]\nrunning = \"#00ff00\"\n").unwrap();
+        assert_eq!(theme.timer.running, ThemeColor(Color::Rgb(0, 255, 0)));
+        assert_eq!(theme.border, Theme::default().border);
+        let err = toml::from_str::<Theme>("[timer]\nrunning = \"mauve\"\n").unwrap_err();
+        assert!(err.to_string().contains("mauve"), "{err}");
+    }
+}
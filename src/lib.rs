@@ -19,6 +19,7 @@ pub mod handlers;
 pub mod local_git;
 pub mod system_command;
 pub mod terminal;
+pub mod trainer;
 pub mod ui;
 pub mod utils;
 pub use clap::parser::ValueSource;
@@ -1,32 +1,32 @@
 use std::io::{Error, ErrorKind};
 use std::process::{Command, Output};
 
-struct LocalGit {
+pub struct LocalGit {
     command: String,
     args: Vec<String>,
 }
 
 impl LocalGit {
-    fn new(command: &str) -> Self {
+    pub fn new(command: &str) -> Self {
         LocalGit {
             command: command.to_string(),
             args: Vec::new(),
         }
     }
 
-    fn arg(&mut self, arg: &str) -> &mut Self {
+    pub fn arg(&mut self, arg: &str) -> &mut Self {
         self.args.push(arg.to_string());
         self
     }
 
-    fn args(&mut self, args: &[&str]) -> &mut Self {
+    pub fn args(&mut self, args: &[&str]) -> &mut Self {
         for arg in args {
             self.args.push(arg.to_string());
         }
         self
     }
 
-    fn execute(&self) -> Result<Output, Error> {
+    pub fn execute(&self) -> Result<Output, Error> {
         let output = Command::new(&self.command).args(&self.args).output();
 
         match output {
@@ -38,7 +38,7 @@ impl LocalGit {
         }
     }
 
-    fn execute_and_get_stdout(&self) -> Result<String, Error> {
+    pub fn execute_and_get_stdout(&self) -> Result<String, Error> {
         let output = self.execute()?;
         if output.status.success() {
             Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
@@ -54,7 +54,7 @@ impl LocalGit {
         }
     }
 
-    fn execute_and_get_stderr(&self) -> Result<String, Error> {
+    pub fn execute_and_get_stderr(&self) -> Result<String, Error> {
         let output = self.execute()?;
         if output.status.success() {
             Ok(String::from_utf8_lossy(&output.stderr).trim().to_string())
@@ -70,7 +70,7 @@ impl LocalGit {
         }
     }
 
-    fn execute_and_check_status(&self) -> Result<bool, Error> {
+    pub fn execute_and_check_status(&self) -> Result<bool, Error> {
         let output = self.execute()?;
         Ok(output.status.success())
     }
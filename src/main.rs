@@ -9,159 +9,71 @@
     clippy::future_not_send
 )]
 
-mod app;
-mod cube;
-mod global_rt;
-mod system_command;
-mod ui;
+use clap::Parser;
 
-use crate::global_rt::global_rt;
-use clap::{Arg, ArgAction, Command, Parser};
-use crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-};
-
-use cube_tui::chat::chat;
-use cube_tui::system_command::system_command_test;
-use cube_tui::local_git::local_git_test;
+use cube_tui::app::App;
+use cube_tui::handlers::command::Cmd;
+use cube_tui::terminal::ui_driver;
 use cube_tui::CompleteConfig;
 use cube_tui::WrapErr;
-use std::{error::Error, io};
-use tui::{backend::CrosstermBackend, Terminal};
+use libp2p::Multiaddr;
+use std::error::Error;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// Name of the person to greet
-    #[arg(short, long, default_value = "user")]
-    name: String,
-
-    /// Number of times to greet
-    #[arg(short, long, default_value_t = 1)]
-    count: u8,
-    #[arg(short = 't', long)]
-    tui: bool,
-    #[arg(long)]
-    chat: bool,
-    #[arg(long = "cfg", default_value = "")]
-    config: String,
+    /// Dial this multiaddr at start and whenever the connection drops
+    #[arg(long = "peer", value_name = "MULTIADDR")]
+    peers: Vec<String>,
+    /// Run a command of the `:` command line
+    #[command(subcommand)]
+    command: Option<Cmd>,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
 
-    let global_rt_result = global_rt().spawn(async move {
-	let _ = chat();
-        println!("global_rt async task!");
-        String::from("global_rt async task!")
-    });
-    println!("global_rt_result={:?}", global_rt_result.await);
-    let global_rt_result = global_rt().spawn(async move {
-        system_command_test();
-        String::from("global_rt async task!")
-    });
-    println!("global_rt_result={:?}", global_rt_result.await);
-
-    for args in 0..args.count {
-        let global_rt_result = global_rt()
-            .spawn(async move {
-                println!("global_rt async task! {}", &args.clone());
-            })
-            .await;
-        println!("global_rt_result={:?}!", global_rt_result);
-    }
-
-    let cmd = Command::new("gnostr-chat")
-        .arg(
-            Arg::new("name")
-                .long("name")
-                .short('n')
-                //.required(true)
-                .action(ArgAction::Set)
-                .default_value("-"),
-        )
-        .arg(
-            Arg::new("count")
-                .long("count")
-                .short('c')
-                //.required(true)
-                .action(ArgAction::Set)
-                .default_value("0"),
-        )
-        .arg(
-            Arg::new("tui")
-                .long("tui")
-                .short('t')
-                //.required(true)
-                .action(ArgAction::SetTrue)
-                .default_value("false"),
-        )
-        .arg(
-            Arg::new("chat")
-                .long("chat")
-                //.required(true)
-                .action(ArgAction::SetTrue)
-                .default_value("false"),
-        )
-        .arg(Arg::new("config").long("cfg").action(ArgAction::Set))
-        .get_matches();
-
-    assert!(cmd.clone().contains_id("tui"));
-
-    let matches = cmd.clone();
-    assert!(matches.contains_id("tui"));
+    color_eyre::install().unwrap();
 
-    if let Some(c) = matches.get_one::<bool>("tui") {
-        if matches.get_flag("tui") {
-            println!("Value for --tui: {c}");
-            assert_eq!(matches.get_flag("tui"), true);
+    let mut config = match CompleteConfig::new().wrap_err("Configuration error.") {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err:?}");
+            std::process::exit(1);
         }
-    }
-    if let Some(c) = matches.get_one::<bool>("chat") {
-        if matches.get_flag("chat") {
-            let global_rt_result = global_rt()
-                .spawn(async move {
-                    println!("global_rt async task!");
-                    //evt_loop(input_rx, peer_tx, topic).await.unwrap();
-                    String::from("global_rt async task!");
-                })
-                .await;
-            println!("global_rt_result={:?}", global_rt_result?);
-            println!("Value for --chat: {c}");
-            assert_eq!(matches.get_flag("tui"), true);
+    };
+    add_peers(&mut config, &args.peers);
+
+    // Exports and listings print their result instead of starting the TUI
+    if let Some(command) = args.command.clone().filter(Cmd::is_one_shot) {
+        let path = config.storage.times_path()?;
+        let mut app = App::new(config, &path)?;
+        app.load_times()?;
+        match app.run_command(command) {
+            Ok(message) => println!("{message}"),
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
         }
+        return Ok(());
     }
-    color_eyre::install().unwrap();
-
-    let config = CompleteConfig::new()
-        .wrap_err("Configuration error.")
-        .unwrap();
-
-    // setup terminal
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-
-    // create app and run it
-    let res = ui::run(&mut terminal);
-
-    // restore terminal
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
 
-    if let Err(err) = res {
+    if let Err(err) = ui_driver(config, args.command).await {
         eprintln!("{:?}", err)
     }
 
     Ok(())
 }
+
+/// Dial `--peer` addresses along with the bootstrap peers of the config
+fn add_peers(config: &mut CompleteConfig, peers: &[String]) {
+    for peer in peers {
+        if let Err(err) = peer.parse::<Multiaddr>() {
+            eprintln!("--peer {peer:?} is not a multiaddr: {err}");
+            std::process::exit(1);
+        }
+    }
+    config.networking.bootstrap_peers.extend_from_slice(peers);
+}
@@ -1,15 +1,16 @@
-use std::time::Duration;
+use std::{error::Error, time::Duration};
 
 use crate::{
     commands::{init_terminal, quit_terminal, reset_terminal},
     handlers::{
+        command::Cmd,
         config::CompleteConfig,
-        event::{Config, Event, Events, Key},
+        event::{Config, Events, Key},
     },
-    //ui::draw_ui,
+    ui,
 };
 
-pub async fn ui_driver(config: CompleteConfig) {
+pub async fn ui_driver(config: CompleteConfig, command: Option<Cmd>) -> Result<(), Box<dyn Error>> {
     let original_hook = std::panic::take_hook();
 
     std::panic::set_hook(Box::new(move |panic| {
@@ -19,23 +20,17 @@ pub async fn ui_driver(config: CompleteConfig) {
 
     let mut events = Events::with_config(Config {
         exit_key: Key::Null,
-        tick_rate: Duration::from_millis(config.terminal.tick_delay),
+        tick_rate: Duration::from_millis(1000),
     })
     .await;
 
     let mut terminal = init_terminal(&config.frontend);
 
-    terminal.clear().unwrap();
+    terminal.clear()?;
 
-    //loop {
-    //    terminal.draw(|frame| draw_ui(frame, &config)).unwrap();
+    let res = ui::run(&mut terminal, &config, &mut events, command).await;
 
-    //    if matches!(events.next().await, Some(Event::Input(Key::Char('q')))) {
-    //        quit_terminal(terminal);
+    quit_terminal(terminal);
 
-    //        break;
-    //    }
-    //}
-
-    reset_terminal();
+    res
 }
@@ -0,0 +1,206 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const DAY: f64 = 24.0 * 60.0 * 60.0;
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Key of a case in the library, e.g. `PLL/T`
+pub fn case_key(set: &str, case: &str) -> String {
+    format!("{}/{}", set, case)
+}
+
+/// Grade a drill from 0 (blackout) to 5 (perfect) by comparing its time to a
+/// reference time, usually the mean of the case set
+pub fn grade(time: f64, reference: Option<f64>) -> u8 {
+    let Some(reference) = reference.filter(|r| *r > 0.0) else {
+        return 4;
+    };
+    match time / reference {
+        r if r <= 0.8 => 5,
+        r if r <= 1.0 => 4,
+        r if r <= 1.3 => 3,
+        r if r <= 1.8 => 2,
+        _ => 1,
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Entry {
+    pub algs: Vec<String>,
+    /// Index into `algs` of the algorithm being learned
+    pub main: usize,
+    pub notes: String,
+    // SM-2 scheduling state
+    pub repetitions: u32,
+    /// Days until the next review
+    pub interval: f64,
+    pub ease: f64,
+    /// Unix time of the next review, 0 if never reviewed
+    pub due: u64,
+}
+
+impl Default for Entry {
+    fn default() -> Self {
+        Self {
+            algs: vec![],
+            main: 0,
+            notes: String::new(),
+            repetitions: 0,
+            interval: 0.0,
+            ease: 2.5,
+            due: 0,
+        }
+    }
+}
+
+impl Entry {
+    pub fn main_alg(&self) -> Option<&str> {
+        self.algs.get(self.main).map(String::as_str)
+    }
+
+    /// Update the schedule after a review graded from 0 to 5 (SM-2)
+    pub fn review(&mut self, quality: u8, at: u64) {
+        let q = f64::from(quality.min(5));
+        if quality >= 3 {
+            self.interval = match self.repetitions {
+                0 => 1.0,
+                1 => 6.0,
+                _ => (self.interval * self.ease).round(),
+            };
+            self.repetitions += 1;
+        } else {
+            self.repetitions = 0;
+            self.interval = 1.0;
+        }
+        self.ease = (self.ease + (0.1 - (5.0 - q) * (0.08 + (5.0 - q) * 0.02))).max(1.3);
+        self.due = at + (self.interval * DAY) as u64;
+    }
+
+    /// Days until the entry is due, negative when overdue
+    pub fn due_in_days(&self) -> Option<f64> {
+        match self.due {
+            0 => None,
+            due => Some((due as f64 - now() as f64) / DAY),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct LibraryFile {
+    #[serde(default)]
+    cases: BTreeMap<String, Entry>,
+}
+
+/// Personal algorithms, notes and review schedule of every case
+pub struct Library {
+    pub entries: BTreeMap<String, Entry>,
+    path: PathBuf,
+}
+
+impl Library {
+    pub fn empty(data_dir: &Path) -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            path: data_dir.join("library.toml"),
+        }
+    }
+
+    pub fn load(data_dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let path = data_dir.join("library.toml");
+        let entries = match fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str::<LibraryFile>(&contents)?.cases,
+            Err(_) => BTreeMap::new(),
+        };
+        Ok(Self { entries, path })
+    }
+
+    pub fn write(&self) -> Result<(), Box<dyn Error>> {
+        let file = LibraryFile {
+            cases: self.entries.clone(),
+        };
+        fs::write(&self.path, toml::to_string(&file)?)?;
+        Ok(())
+    }
+
+    /// The entry of a case, created with `alg` as its main algorithm if missing
+    pub fn entry(&mut self, key: &str, alg: &str) -> &mut Entry {
+        self.entries
+            .entry(key.to_string())
+            .or_insert_with(|| Entry {
+                algs: vec![alg.to_string()],
+                ..Entry::default()
+            })
+    }
+
+    pub fn review(&mut self, key: &str, alg: &str, quality: u8) {
+        self.entry(key, alg).review(quality, now());
+    }
+
+    /// Which of `keys` to drill next: the most overdue case, then a case that
+    /// was never reviewed. `None` when nothing is due.
+    pub fn next_due<'k>(&self, keys: &[&'k str]) -> Option<&'k str> {
+        let now = now();
+        let overdue = keys
+            .iter()
+            .filter_map(|k| self.entries.get(*k).map(|e| (*k, e.due)))
+            .filter(|(_, due)| *due != 0 && *due <= now)
+            .min_by_key(|(_, due)| *due)
+            .map(|(k, _)| k);
+        overdue.or_else(|| {
+            keys.iter()
+                .find(|k| self.entries.get(**k).is_none_or(|e| e.due == 0))
+                .copied()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sm2_intervals() {
+        let mut e = Entry::default();
+        e.review(5, 0);
+        assert_eq!((e.repetitions, e.interval), (1, 1.0));
+        e.review(5, 0);
+        assert_eq!((e.repetitions, e.interval), (2, 6.0));
+        e.review(4, 0);
+        assert_eq!(e.interval, (6.0 * 2.7_f64).round());
+        e.review(1, 0);
+        assert_eq!((e.repetitions, e.interval), (0, 1.0));
+        assert!(e.ease >= 1.3);
+        assert_eq!(e.due, DAY as u64);
+    }
+
+    #[test]
+    fn test_next_due_prefers_overdue_then_new() {
+        let mut lib = Library::empty(Path::new(""));
+        lib.entry("PLL/T", "").review(5, 0);
+        lib.entry("PLL/Y", "").review(5, now());
+        assert_eq!(lib.next_due(&["PLL/Y", "PLL/T", "PLL/H"]), Some("PLL/T"));
+        lib.entry("PLL/T", "").review(5, now());
+        assert_eq!(lib.next_due(&["PLL/Y", "PLL/T", "PLL/H"]), Some("PLL/H"));
+        assert_eq!(lib.next_due(&["PLL/Y", "PLL/T"]), None);
+    }
+
+    #[test]
+    fn test_grade() {
+        assert_eq!(grade(1.0, None), 4);
+        assert_eq!(grade(1.0, Some(2.0)), 5);
+        assert_eq!(grade(4.0, Some(2.0)), 1);
+    }
+}
@@ -0,0 +1,446 @@
+use super::cube::{format_alg, parse_alg, CubeState, Face, Move};
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+pub mod library;
+
+use library::{case_key, grade, Library};
+
+const DEFAULT_CASE_SETS: [(&str, &str); 4] = [
+    ("oll.toml", include_str!("../text/cases/oll.toml")),
+    ("pll.toml", include_str!("../text/cases/pll.toml")),
+    ("ocll.toml", include_str!("../text/cases/ocll.toml")),
+    ("zbll_u.toml", include_str!("../text/cases/zbll_u.toml")),
+];
+
+/// A case set file as written by the user
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct CaseSetFile {
+    name: String,
+    #[serde(rename = "case", default)]
+    cases: Vec<CaseFile>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct CaseFile {
+    name: String,
+    alg: String,
+    /// Left out of the drills when false, to practise a subset of the set
+    #[serde(default = "enabled")]
+    enabled: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone)]
+pub struct Case {
+    pub name: String,
+    pub alg: Vec<Move>,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct CaseSet {
+    pub name: String,
+    pub cases: Vec<Case>,
+}
+
+impl CaseSet {
+    pub fn parse(contents: &str) -> Result<Self, Box<dyn Error>> {
+        let file: CaseSetFile = toml::from_str(contents)?;
+        let cases = file
+            .cases
+            .into_iter()
+            .map(|c| match parse_alg(&c.alg) {
+                Ok(alg) => Ok(Case {
+                    name: c.name,
+                    alg,
+                    enabled: c.enabled,
+                }),
+                Err(e) => Err(format!("case {}: {}", c.name, e)),
+            })
+            .collect::<Result<Vec<Case>, String>>()?;
+        Ok(Self {
+            name: file.name,
+            cases,
+        })
+    }
+
+    /// The cases drilled, all of them unless some are disabled
+    pub fn enabled(&self) -> impl Iterator<Item = (usize, &Case)> {
+        self.cases.iter().enumerate().filter(|(_, c)| c.enabled)
+    }
+}
+
+/// Load every `*.toml` case set in `dir`, writing the built-in sets first if
+/// the directory does not exist yet
+pub fn load_case_sets(dir: &Path) -> Result<Vec<CaseSet>, Box<dyn Error>> {
+    if !dir.exists() {
+        fs::create_dir_all(dir)?;
+        for (file, contents) in DEFAULT_CASE_SETS {
+            fs::write(dir.join(file), contents)?;
+        }
+    }
+
+    let mut paths = fs::read_dir(dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|e| e == "toml"))
+        .collect::<Vec<PathBuf>>();
+    paths.sort();
+
+    paths
+        .iter()
+        .map(|p| {
+            CaseSet::parse(&fs::read_to_string(p)?)
+                .map_err(|e| format!("{}: {}", p.display(), e).into())
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CaseStats {
+    pub count: usize,
+    pub total: f64,
+    pub best: Option<f64>,
+    pub last: f64,
+}
+
+impl CaseStats {
+    pub fn mean(&self) -> f64 {
+        match self.count {
+            0 => 0.0,
+            n => self.total / n as f64,
+        }
+    }
+
+    fn insert(&mut self, time: f64) {
+        self.count += 1;
+        self.total += time;
+        self.last = time;
+        if self.best.is_none_or(|b| time < b) {
+            self.best = Some(time);
+        }
+    }
+}
+
+/// Drills cases from a case set. Pressing once reveals the case and starts
+/// the timer, pressing again records the recognition and execution time.
+pub struct Trainer {
+    pub sets: Vec<CaseSet>,
+    pub set: usize,
+    pub case: usize,
+    pub setup: Vec<Move>,
+    pub state: CubeState,
+    pub revealed: bool,
+    pub starttime: Option<Instant>,
+    pub lasttime: Option<f64>,
+    /// Keyed by (set name, case name)
+    pub stats: HashMap<(String, String), CaseStats>,
+    pub library: Library,
+    pub error: Option<String>,
+    stats_path: PathBuf,
+}
+
+impl Trainer {
+    pub fn new(data_dir: &Path) -> Self {
+        let mut trainer = Self {
+            sets: vec![],
+            set: 0,
+            case: 0,
+            setup: vec![],
+            state: CubeState::solved(),
+            revealed: false,
+            starttime: None,
+            lasttime: None,
+            stats: HashMap::new(),
+            library: Library::empty(data_dir),
+            error: None,
+            stats_path: data_dir.join("trainer_stats"),
+        };
+        match Library::load(data_dir) {
+            Ok(library) => trainer.library = library,
+            Err(e) => trainer.error = Some(e.to_string()),
+        }
+        match load_case_sets(&data_dir.join("cases")) {
+            Ok(sets) => trainer.sets = sets,
+            Err(e) => trainer.error = Some(e.to_string()),
+        }
+        trainer.load_stats();
+        trainer.next_case();
+        trainer
+    }
+
+    pub fn current_set(&self) -> Option<&CaseSet> {
+        self.sets.get(self.set)
+    }
+
+    pub fn current_case(&self) -> Option<&Case> {
+        self.current_set().and_then(|s| s.cases.get(self.case))
+    }
+
+    pub fn running(&self) -> bool {
+        self.starttime.is_some()
+    }
+
+    pub fn setup_text(&self) -> String {
+        format_alg(&self.setup)
+    }
+
+    /// Pick the enabled case the library schedules next, or a random one if
+    /// nothing is due, and set it up by inverting its algorithm between two
+    /// random adjustments of the U face
+    pub fn next_case(&mut self) {
+        self.revealed = false;
+        let Some(set) = self.current_set() else {
+            return;
+        };
+        let (cases, keys): (Vec<usize>, Vec<String>) = set
+            .enabled()
+            .map(|(i, c)| (i, case_key(&set.name, &c.name)))
+            .unzip();
+        if cases.is_empty() {
+            self.error = Some(format!("no case of {} is enabled", set.name));
+            return;
+        }
+        let mut rng = rand::thread_rng();
+        let due = self
+            .library
+            .next_due(&keys.iter().map(String::as_str).collect::<Vec<&str>>())
+            .and_then(|k| keys.iter().position(|key| key == k));
+        let case = cases[due.unwrap_or_else(|| rng.gen_range(0..cases.len()))];
+        let auf = [
+            None,
+            Some((false, false)),
+            Some((true, false)),
+            Some((false, true)),
+        ];
+        let mut setup = vec![];
+        if let Some(Some((prime, half_turn))) = auf.choose(&mut rng) {
+            setup.push(Move::new(Face::U, *prime, *half_turn));
+        }
+        setup.extend(set.cases[case].alg.iter().rev().map(|m| m.inverse()));
+        if let Some(Some((prime, half_turn))) = auf.choose(&mut rng) {
+            setup.push(Move::new(Face::U, *prime, *half_turn));
+        }
+        self.case = case;
+        self.state = CubeState::from_moves(&setup);
+        self.setup = setup;
+    }
+
+    pub fn next_set(&mut self) {
+        if !self.sets.is_empty() {
+            self.set = (self.set + 1) % self.sets.len();
+            self.starttime = None;
+            self.next_case();
+        }
+    }
+
+    pub fn press(&mut self) {
+        match self.starttime {
+            None => {
+                self.revealed = true;
+                self.starttime = Some(Instant::now());
+            }
+            Some(start) => {
+                let time = start.elapsed().as_secs_f64();
+                self.starttime = None;
+                self.lasttime = Some(time);
+                if let (Some(set), Some(case)) = (self.current_set(), self.current_case()) {
+                    let (set, case, alg) =
+                        (set.name.clone(), case.name.clone(), format_alg(&case.alg));
+                    let quality = grade(time, self.set_mean());
+                    self.library.review(&case_key(&set, &case), &alg, quality);
+                    self.stats.entry((set, case)).or_default().insert(time);
+                }
+                if let Err(e) = self.write_stats().and_then(|_| self.library.write()) {
+                    self.error = Some(e.to_string());
+                }
+                self.next_case();
+            }
+        }
+    }
+
+    pub fn text(&self) -> String {
+        match self.starttime {
+            Some(v) => format!("{:.1}", v.elapsed().as_secs_f64()),
+            None => format!("{:.3}", self.lasttime.unwrap_or(0.0)),
+        }
+    }
+
+    /// Mean drill time over every case of the current set
+    pub fn set_mean(&self) -> Option<f64> {
+        let set = self.current_set()?;
+        let (count, total) = self
+            .stats
+            .iter()
+            .filter(|((s, _), _)| *s == set.name)
+            .fold((0, 0.0), |(n, t), (_, s)| (n + s.count, t + s.total));
+        match count {
+            0 => None,
+            n => Some(total / n as f64),
+        }
+    }
+
+    /// Stats of the cases drilled in the current set, slowest case first
+    pub fn slowest(&self) -> Vec<(&str, CaseStats)> {
+        let Some(set) = self.current_set() else {
+            return vec![];
+        };
+        let mut stats = set
+            .enabled()
+            .map(|(_, c)| {
+                let key = (set.name.clone(), c.name.clone());
+                (
+                    c.name.as_str(),
+                    self.stats.get(&key).copied().unwrap_or_default(),
+                )
+            })
+            .collect::<Vec<(&str, CaseStats)>>();
+        stats.sort_by(|a, b| b.1.mean().total_cmp(&a.1.mean()));
+        stats
+    }
+
+    fn load_stats(&mut self) {
+        let Ok(contents) = fs::read_to_string(&self.stats_path) else {
+            return;
+        };
+        for line in contents.lines() {
+            let fields = line.split('\t').collect::<Vec<&str>>();
+            let [set, case, count, total, best, last] = fields[..] else {
+                continue;
+            };
+            let stats = CaseStats {
+                count: count.parse().unwrap_or(0),
+                total: total.parse().unwrap_or(0.0),
+                best: best.parse().ok(),
+                last: last.parse().unwrap_or(0.0),
+            };
+            self.stats
+                .insert((set.to_string(), case.to_string()), stats);
+        }
+    }
+
+    fn write_stats(&self) -> Result<(), Box<dyn Error>> {
+        let data = self
+            .stats
+            .iter()
+            .map(|((set, case), s)| {
+                let best = s.best.map(|v| v.to_string()).unwrap_or_default();
+                format!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\n",
+                    set, case, s.count, s.total, best, s.last
+                )
+            })
+            .collect::<String>();
+        fs::write(&self.stats_path, data)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // First two layers solved and the D face is a single color
+    fn f2l_solved(state: &CubeState) -> bool {
+        let d = state.face(Face::D);
+        let sides_solved = [Face::F, Face::R, Face::B, Face::L].iter().all(|f| {
+            let face = state.face(*f);
+            face[3..].iter().all(|s| *s == face[4])
+        });
+        d.iter().all(|s| *s == d[4]) && sides_solved
+    }
+
+    fn default_set(file: &str) -> CaseSet {
+        let (_, contents) = DEFAULT_CASE_SETS.iter().find(|(f, _)| *f == file).unwrap();
+        CaseSet::parse(contents).unwrap()
+    }
+
+    #[test]
+    fn test_pll_algs_only_permute_last_layer() {
+        let set = default_set("pll.toml");
+        assert_eq!(set.cases.len(), 21);
+        for case in set.cases {
+            let state = CubeState::from_moves(&case.alg);
+            let u = state.face(Face::U);
+            assert!(f2l_solved(&state), "{}", case.name);
+            assert!(u.iter().all(|s| *s == u[4]), "{}", case.name);
+            assert!(!state.is_solved(), "{}", case.name);
+        }
+    }
+
+    #[test]
+    fn test_ocll_algs_keep_edges_oriented() {
+        for case in default_set("ocll.toml").cases {
+            let state = CubeState::from_moves(&case.alg);
+            let u = state.face(Face::U);
+            assert!(f2l_solved(&state), "{}", case.name);
+            assert!([1, 3, 5, 7].iter().all(|i| u[*i] == u[4]), "{}", case.name);
+            assert!([0, 2, 6, 8].iter().any(|i| u[*i] != u[4]), "{}", case.name);
+        }
+    }
+
+    #[test]
+    fn test_oll_algs_orient_last_layer() {
+        let set = default_set("oll.toml");
+        assert_eq!(set.cases.len(), 57);
+        for case in set.cases {
+            let state = CubeState::from_moves(&case.alg);
+            let u = state.face(Face::U);
+            assert!(f2l_solved(&state), "{}", case.name);
+            assert!(u.iter().any(|s| *s != u[4]), "{}", case.name);
+        }
+    }
+
+    #[test]
+    fn test_zbll_algs_keep_edges_oriented() {
+        let set = default_set("zbll_u.toml");
+        assert_eq!(set.cases.len(), 72);
+        for case in set.cases {
+            let state = CubeState::from_moves(&case.alg);
+            let u = state.face(Face::U);
+            assert!(f2l_solved(&state), "{}", case.name);
+            assert!([1, 3, 5, 7].iter().all(|i| u[*i] == u[4]), "{}", case.name);
+            assert!(!state.is_solved(), "{}", case.name);
+        }
+    }
+
+    #[test]
+    fn test_next_case_is_enabled() {
+        let dir = std::env::temp_dir().join("cube-tui-trainer-enabled");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let mut trainer = Trainer::new(&dir);
+        let text = "name = \"x\"\n\
+            [[case]]\nname = \"a\"\nalg = \"R U R'\"\nenabled = false\n\
+            [[case]]\nname = \"b\"\nalg = \"F R F'\"\n";
+        trainer.sets = vec![CaseSet::parse(text).unwrap()];
+        trainer.set = 0;
+        for _ in 0..20 {
+            trainer.next_case();
+            assert_eq!(trainer.current_case().unwrap().name, "b");
+        }
+        assert_eq!(trainer.slowest().len(), 1);
+
+        trainer.sets[0].cases[1].enabled = false;
+        trainer.next_case();
+        assert_eq!(trainer.error.as_deref(), Some("no case of x is enabled"));
+    }
+
+    #[test]
+    fn test_bad_alg_names_case() {
+        let err = CaseSet::parse("name = \"x\"\n[[case]]\nname = \"bad\"\nalg = \"R Q\"\n")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("bad"), "{err}");
+    }
+}
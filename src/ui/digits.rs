@@ -0,0 +1,60 @@
+/// Rows of a big glyph
+pub const HEIGHT: u16 = 5;
+
+/// Seven-segment style glyphs of the characters a time is made of. Anything
+/// else is drawn as a blank the width of a digit.
+#[rustfmt::skip]
+const FONT: [(char, [&str; HEIGHT as usize]); 14] = [
+    ('0', ["███", "█ █", "█ █", "█ █", "███"]),
+    ('1', ["  █", "  █", "  █", "  █", "  █"]),
+    ('2', ["███", "  █", "███", "█  ", "███"]),
+    ('3', ["███", "  █", "███", "  █", "███"]),
+    ('4', ["█ █", "█ █", "███", "  █", "  █"]),
+    ('5', ["███", "█  ", "███", "  █", "███"]),
+    ('6', ["███", "█  ", "███", "█ █", "███"]),
+    ('7', ["███", "  █", "  █", "  █", "  █"]),
+    ('8', ["███", "█ █", "███", "█ █", "███"]),
+    ('9', ["███", "█ █", "███", "  █", "███"]),
+    ('.', [" ", " ", " ", " ", "█"]),
+    (':', [" ", "█", " ", "█", " "]),
+    ('-', ["   ", "   ", "███", "   ", "   "]),
+    (' ', ["   ", "   ", "   ", "   ", "   "]),
+];
+
+/// `text` in big glyphs, one string per row, with a column between glyphs
+pub fn big_text(text: &str) -> Vec<String> {
+    (0..HEIGHT as usize)
+        .map(|row| {
+            text.chars()
+                .map(|c| {
+                    FONT.iter()
+                        .find(|(g, _)| *g == c)
+                        .map_or("   ", |(_, rows)| rows[row])
+                })
+                .collect::<Vec<&str>>()
+                .join(" ")
+        })
+        .collect()
+}
+
+/// Columns `text` takes in big glyphs
+pub fn width(text: &str) -> u16 {
+    big_text(text)
+        .first()
+        .map_or(0, |row| row.chars().count() as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_big_text() {
+        let rows = big_text("1.07");
+        assert_eq!(rows.len(), HEIGHT as usize);
+        assert_eq!(rows[0], "  █   ███ ███");
+        assert_eq!(rows[4], "  █ █ ███   █");
+        assert_eq!(width("1.07"), 13);
+        assert_eq!(width(""), 0);
+    }
+}
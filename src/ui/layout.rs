@@ -0,0 +1,260 @@
+use std::str::FromStr;
+
+use tui::layout::Rect;
+
+use crate::app::{ActiveBlock, Dir};
+use crate::handlers::config::{LayoutConfig, PanelConfig};
+
+/// Panels of the default screen by their name in the `[layout]` config
+pub const PANELS: [(&str, ActiveBlock); 6] = [
+    ("tools", ActiveBlock::Tools),
+    ("timer", ActiveBlock::Timer),
+    ("times", ActiveBlock::Times),
+    ("stats", ActiveBlock::Stats),
+    ("scramble", ActiveBlock::Scramble),
+    ("main", ActiveBlock::Main),
+];
+
+pub fn panel(name: &str) -> Option<ActiveBlock> {
+    PANELS.iter().find(|(n, _)| *n == name).map(|(_, b)| *b)
+}
+
+/// Width of a column or height of a panel: `3` cells, `33%` of the screen,
+/// or `fill` for an equal share of what is left
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Size {
+    Length(u16),
+    Percent(u16),
+    Fill,
+}
+
+impl FromStr for Size {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s == "fill" {
+            return Ok(Size::Fill);
+        }
+        match s.strip_suffix('%') {
+            Some(p) => match p.trim().parse::<u16>() {
+                Ok(p) if p <= 100 => Ok(Size::Percent(p)),
+                _ => Err(format!("{:?} is not a percentage", s)),
+            },
+            None => s
+                .parse::<u16>()
+                .map(Size::Length)
+                .map_err(|_| format!("{:?} is not a size, expected e.g. 3, 33% or fill", s)),
+        }
+    }
+}
+
+/// Split `area` along one axis. Fixed sizes are taken in order while space
+/// lasts, and the fills share the rest, so the parts always tile `area`.
+pub fn split(area: Rect, sizes: &[Size], horizontal: bool) -> Vec<Rect> {
+    let total = match horizontal {
+        true => area.width,
+        false => area.height,
+    };
+    let fills = sizes.iter().filter(|s| **s == Size::Fill).count() as u16;
+    let fixed = sizes
+        .iter()
+        .map(|s| match s {
+            Size::Length(l) => *l,
+            Size::Percent(p) => (u32::from(total) * u32::from(*p) / 100) as u16,
+            Size::Fill => 0,
+        })
+        .collect::<Vec<u16>>();
+    let mut left = total;
+    let mut lengths = fixed
+        .iter()
+        .map(|l| {
+            let l = (*l).min(left);
+            left -= l;
+            l
+        })
+        .collect::<Vec<u16>>();
+    let mut fill_left = left;
+    let mut fills_left = fills;
+    for (length, size) in lengths.iter_mut().zip(sizes) {
+        if *size == Size::Fill {
+            *length = fill_left / fills_left;
+            fill_left -= *length;
+            fills_left -= 1;
+        }
+    }
+    // Without a fill the last part takes the slack
+    if fills == 0 {
+        if let Some(last) = lengths.last_mut() {
+            *last += left;
+        }
+    }
+
+    let mut offset = 0;
+    lengths
+        .into_iter()
+        .map(|length| {
+            let rect = match horizontal {
+                true => Rect::new(area.x + offset, area.y, length, area.height),
+                false => Rect::new(area.x, area.y + offset, area.width, length),
+            };
+            offset += length;
+            rect
+        })
+        .collect()
+}
+
+fn column(panels: &[PanelConfig], area: Rect) -> Vec<(ActiveBlock, Rect)> {
+    let sizes = panels
+        .iter()
+        .map(|p| p.height.parse().unwrap_or(Size::Fill))
+        .collect::<Vec<Size>>();
+    panels
+        .iter()
+        .zip(split(area, &sizes, false))
+        .filter_map(|(p, rect)| panel(&p.panel).map(|b| (b, rect)))
+        .collect()
+}
+
+/// Where each panel of the default screen goes, using the single column
+/// `narrow` layout when `area` is narrower than `narrow_width`
+pub fn panel_rects(config: &LayoutConfig, area: Rect) -> Vec<(ActiveBlock, Rect)> {
+    if area.width < config.narrow_width {
+        return column(&config.narrow, area);
+    }
+    let sizes = config
+        .columns
+        .iter()
+        .map(|c| c.width.parse().unwrap_or(Size::Fill))
+        .collect::<Vec<Size>>();
+    config
+        .columns
+        .iter()
+        .zip(split(area, &sizes, true))
+        .flat_map(|(c, rect)| column(&c.panels, rect))
+        .collect()
+}
+
+/// The panel visually next to `from` in direction `dir`: the closest one on
+/// that side, preferring panels that overlap `from` across the direction
+pub fn adjacent(
+    panels: &[(ActiveBlock, Rect)],
+    from: ActiveBlock,
+    dir: Dir,
+) -> Option<ActiveBlock> {
+    let Some((_, cur)) = panels.iter().find(|(b, _)| *b == from) else {
+        return panels.first().map(|(b, _)| *b);
+    };
+    let (cur_x, cur_y) = center(cur);
+    panels
+        .iter()
+        .filter(|(b, r)| *b != from && r.width > 0 && r.height > 0)
+        .filter_map(|(b, r)| {
+            let (gap, overlap) = match dir {
+                Dir::Left if r.right() <= cur.x => (
+                    cur.x - r.right(),
+                    overlaps(r.y, r.bottom(), cur.y, cur.bottom()),
+                ),
+                Dir::Right if r.x >= cur.right() => (
+                    r.x - cur.right(),
+                    overlaps(r.y, r.bottom(), cur.y, cur.bottom()),
+                ),
+                Dir::Up if r.bottom() <= cur.y => (
+                    cur.y - r.bottom(),
+                    overlaps(r.x, r.right(), cur.x, cur.right()),
+                ),
+                Dir::Down if r.y >= cur.bottom() => (
+                    r.y - cur.bottom(),
+                    overlaps(r.x, r.right(), cur.x, cur.right()),
+                ),
+                _ => return None,
+            };
+            let (x, y) = center(r);
+            let off_axis = match dir {
+                Dir::Left | Dir::Right => y.abs_diff(cur_y),
+                Dir::Up | Dir::Down => x.abs_diff(cur_x),
+            };
+            Some(((!overlap, gap, off_axis), *b))
+        })
+        .min_by_key(|(key, _)| *key)
+        .map(|(_, b)| b)
+}
+
+/// The block drawn at a terminal cell, preferring rects pushed later
+pub fn hit(rects: &[(ActiveBlock, Rect)], column: u16, row: u16) -> Option<(ActiveBlock, Rect)> {
+    rects
+        .iter()
+        .rev()
+        .find(|(_, r)| column >= r.x && column < r.right() && row >= r.y && row < r.bottom())
+        .copied()
+}
+
+/// First row to show of a list with `visible` rows so `selected` stays in
+/// view, moving as little as possible from the last `offset`
+pub fn scroll_offset(offset: usize, selected: Option<usize>, visible: usize) -> usize {
+    match selected {
+        Some(i) if visible == 0 => i,
+        Some(i) if i < offset => i,
+        Some(i) if i >= offset + visible => i + 1 - visible,
+        _ => offset,
+    }
+}
+
+fn center(r: &Rect) -> (u16, u16) {
+    (r.x + r.width / 2, r.y + r.height / 2)
+}
+
+fn overlaps(a_start: u16, a_end: u16, b_start: u16, b_end: u16) -> bool {
+    a_start < b_end && b_start < a_end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_tiles_the_area() {
+        let area = Rect::new(0, 0, 100, 40);
+        let sizes = [Size::Length(3), Size::Percent(25), Size::Fill, Size::Fill];
+        let rects = split(area, &sizes, false);
+        let heights = rects.iter().map(|r| r.height).collect::<Vec<u16>>();
+        assert_eq!(heights, vec![3, 10, 13, 14]);
+        assert_eq!(rects[3].bottom(), 40);
+
+        // Fixed sizes that do not fit are cut off instead of overflowing
+        let rects = split(
+            Rect::new(0, 0, 10, 5),
+            &[Size::Length(8), Size::Length(8)],
+            true,
+        );
+        assert_eq!(rects[1], Rect::new(8, 0, 2, 5));
+    }
+
+    #[test]
+    fn test_scroll_offset_keeps_selection_visible() {
+        assert_eq!(scroll_offset(0, Some(3), 5), 0);
+        assert_eq!(scroll_offset(0, Some(7), 5), 3);
+        assert_eq!(scroll_offset(3, Some(1), 5), 1);
+        assert_eq!(scroll_offset(3, None, 5), 3);
+    }
+
+    #[test]
+    fn test_default_layout_navigation() {
+        let config = LayoutConfig::default();
+        let wide = panel_rects(&config, Rect::new(0, 0, 200, 50));
+        assert_eq!(wide.len(), PANELS.len());
+        let mv = |from, dir| adjacent(&wide, from, dir);
+        assert_eq!(mv(ActiveBlock::Tools, Dir::Down), Some(ActiveBlock::Timer));
+        assert_eq!(mv(ActiveBlock::Tools, Dir::Right), Some(ActiveBlock::Stats));
+        assert_eq!(mv(ActiveBlock::Times, Dir::Right), Some(ActiveBlock::Main));
+        assert_eq!(mv(ActiveBlock::Main, Dir::Up), Some(ActiveBlock::Scramble));
+        assert_eq!(mv(ActiveBlock::Stats, Dir::Right), None);
+
+        // Narrow terminals stack every panel in one column
+        let narrow = panel_rects(&config, Rect::new(0, 0, 60, 80));
+        assert!(narrow.iter().all(|(_, r)| r.x == 0 && r.width == 60));
+        let first = narrow[0].0;
+        assert_eq!(adjacent(&narrow, first, Dir::Down), Some(narrow[1].0));
+        assert_eq!(adjacent(&narrow, first, Dir::Right), None);
+    }
+}
@@ -1,156 +1,1047 @@
+pub mod digits;
+pub mod layout;
+
 use super::app::*;
-use crossterm::event::{self, Event, KeyCode, KeyModifiers};
-use std::{
-    env,
-    error::Error,
-    path::Path,
-    time::{Duration, Instant},
+use super::chat::{
+    outbox::Delivery,
+    wire::{Body, Presence, RaceControl},
 };
+use super::cube::{bld::PieceMemo, format_alg, parse_alg, CubeState, Face};
+use super::handlers::command::Cmd;
+use super::handlers::config::CompleteConfig;
+use super::handlers::event::{AppEvent, Events, Key, NetworkEvent};
+use super::handlers::keymap::{Action, Context};
+use super::handlers::theme::Theme;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent};
+use std::error::Error;
 use tui::{
     backend::Backend,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
     symbols,
-    text::Span,
+    text::{Span, Spans},
     widgets::{
-        Axis, Block, Borders, Cell, Chart, Dataset, GraphType, List, ListItem, Paragraph, Row,
-        Table, Wrap,
+        Axis, Block, Borders, Cell, Chart, Clear, Dataset, GraphType, List, ListItem, ListState,
+        Paragraph, Row, Table, TableState, Wrap,
     },
     Frame, Terminal,
 };
+use tui_input::{Input, InputRequest};
 
-const HELP_TEXT: &'static str = include_str!("../text/help.txt");
 const WELCOME_TEXT: &'static str = include_str!("../text/welcome.txt");
 const CUBE_TEXT: &'static str = include_str!("../text/cube.txt");
 
-pub fn run<B: Backend>(terminal: &mut Terminal<B>) -> Result<(), Box<dyn Error>> {
+pub async fn run<B: Backend>(
+    terminal: &mut Terminal<B>,
+    config: &CompleteConfig,
+    events: &mut Events,
+    command: Option<Cmd>,
+) -> Result<(), Box<dyn Error>> {
     // Create app and load times
-    let pathstr = env::var("HOME")? + "/.local/share/cube-tui/times";
-    let path = Path::new(&pathstr);
-    let mut app = App::new(Duration::from_millis(1000), path)?;
+    let path = config.storage.times_path()?;
+    let mut app = App::new(config.clone(), &path)?;
+    app.events = Some(events.sender());
     app.load_times()?;
+    app.start_chat();
+    // A subcommand given on the command line runs as if typed after `:`
+    if let Some(command) = command {
+        app.command_line.message = Some(app.run_command(command));
+    }
 
-    // Main loop and tick logic
-    let mut last_tick = Instant::now();
+    // Every input, tick, network message and task result arrives on one stream
     loop {
-        terminal.draw(|f| match app.route.screen {
-            Screen::Default => render_default(f, &mut app),
-            Screen::Help => render_help(f),
-        })?;
-
-        // Non-blocking key detection
-        let timeout = app
-            .tick_rate
-            .checked_sub(last_tick.elapsed())
-            .unwrap_or(Duration::from_secs(0));
-        if event::poll(timeout)? {
-            if handle_input(&mut app)? {
-                return Ok(());
+        events.set_tick_rate(app.tick_rate);
+        terminal.draw(|f| draw(f, &mut app))?;
+
+        match events.next().await {
+            Some(AppEvent::Key(key)) => {
+                if handle_input(&mut app, key)? {
+                    return Ok(());
+                }
             }
+            Some(AppEvent::Tick) => app.on_tick(),
+            Some(AppEvent::Network(event)) => app.on_network(event),
+            Some(AppEvent::Task(result)) => app.on_task(result),
+            Some(AppEvent::Mouse(mouse)) => handle_mouse(&mut app, mouse),
+            Some(AppEvent::Resize(..)) => (),
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Draw the current screen of `app`. Kept apart from the terminal and event
+/// loop so any backend, e.g. tui's `TestBackend`, can render it.
+pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    match app.route.screen {
+        // A virtual solve is done on the cube drawn in the panel
+        Screen::Default
+            if app.timer.on && app.config.timer.focus && app.active_tool != Tool::Virtual =>
+        {
+            render_focus(f, app)
+        }
+        Screen::Default => render_default(f, app),
+        Screen::Help => render_help(f, app),
+        Screen::Library => render_library(f, app),
+        Screen::Replay => render_replay(f, app),
+        Screen::Memo => render_memo(f, app),
+        Screen::Solve => render_solve(f, app),
+    }
+}
+
+/// Map a key press to an edit of a `tui_input::Input`
+fn input_request(key: KeyEvent) -> Option<InputRequest> {
+    match (key.code, key.modifiers) {
+        (KeyCode::Backspace, KeyModifiers::CONTROL) => Some(InputRequest::DeletePrevWord),
+        (KeyCode::Backspace, _) => Some(InputRequest::DeletePrevChar),
+        (KeyCode::Delete, _) => Some(InputRequest::DeleteNextChar),
+        (KeyCode::Left, KeyModifiers::CONTROL) => Some(InputRequest::GoToPrevWord),
+        (KeyCode::Left, _) => Some(InputRequest::GoToPrevChar),
+        (KeyCode::Right, KeyModifiers::CONTROL) => Some(InputRequest::GoToNextWord),
+        (KeyCode::Right, _) => Some(InputRequest::GoToNextChar),
+        (KeyCode::Home, _) | (KeyCode::Char('a'), KeyModifiers::CONTROL) => {
+            Some(InputRequest::GoToStart)
+        }
+        (KeyCode::End, _) | (KeyCode::Char('e'), KeyModifiers::CONTROL) => {
+            Some(InputRequest::GoToEnd)
         }
-        if last_tick.elapsed() >= app.tick_rate {
-            app.on_tick();
-            last_tick = Instant::now();
+        (KeyCode::Char('u'), KeyModifiers::CONTROL) => Some(InputRequest::DeleteLine),
+        (KeyCode::Char('w'), KeyModifiers::CONTROL) => Some(InputRequest::DeletePrevWord),
+        (KeyCode::Char('k'), KeyModifiers::CONTROL) => Some(InputRequest::DeleteTillEnd),
+        (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+            Some(InputRequest::InsertChar(c))
         }
+        _ => None,
     }
 }
 
-fn handle_input(app: &mut App) -> Result<bool, Box<dyn Error>> {
-    if let Event::Key(key) = event::read()? {
-        match key.modifiers {
-            KeyModifiers::NONE => match key.code {
-                KeyCode::Char('q') => {
-                    app.write_times()?;
-                    return Ok(true);
+fn handle_replay_input(app: &mut App, key: KeyEvent) {
+    let editing = app.replay.as_ref().is_some_and(|r| r.editing);
+    if editing {
+        match app.keymap.lookup(Context::Input, &key) {
+            Some(Action::Back) => app.esc(),
+            Some(Action::Submit) => app.submit_reconstruction(),
+            _ => {
+                if let (Some(replay), Some(req)) = (&mut app.replay, input_request(key)) {
+                    replay.input.handle(req);
+                    replay.error = None;
                 }
-                KeyCode::Char(' ') => match app.timer.space_press() {
-                    Some(mut t) => {
-                        t.gen_stats(&app.times.times);
-                        app.times.insert(t);
-                        app.tick_rate = Duration::from_millis(1000);
-                        app.new_scramble();
-                    }
-                    None => app.tick_rate = Duration::from_millis(100),
-                },
-                KeyCode::Esc => app.esc(),
-                KeyCode::Enter => app.route.enter(),
-                KeyCode::Char('h') | KeyCode::Left => app.mv(Dir::Left),
-                KeyCode::Char('j') | KeyCode::Down => app.mv(Dir::Down),
-                KeyCode::Char('k') | KeyCode::Up => app.mv(Dir::Up),
-                KeyCode::Char('l') | KeyCode::Right => app.mv(Dir::Right),
-                KeyCode::Char('d') => app.del(),
-                KeyCode::Char('?') => app.help(),
-                KeyCode::Char('<') => app.help(),
-                KeyCode::Char('>') => app.esc(),
-                _ => (),
-            },
-            KeyModifiers::CONTROL => match key.code {
-                KeyCode::Char('w') => {
-                    app.write_times()?;
-                    app.load_times()?;
+            }
+        }
+        return;
+    }
+    match app.keymap.lookup(Context::Replay, &key) {
+        Some(Action::Back) => app.esc(),
+        Some(Action::Play) => app.toggle_replay(),
+        Some(Action::Edit) => app.edit_reconstruction(),
+        Some(action) => {
+            if let Some(replay) = &mut app.replay {
+                match action {
+                    Action::StepForward => replay.step_forward(),
+                    Action::StepBack => replay.step_back(),
+                    Action::Restart => replay.restart(),
+                    _ => (),
                 }
-                KeyCode::Char('c') => app.esc(),
-                KeyCode::Char('q') => {
-                    app.write_times()?;
-                    return Ok(true);
+            }
+        }
+        None => (),
+    }
+}
+
+fn handle_library_input(app: &mut App, key: KeyEvent) {
+    if app.library_view.edit.is_some() {
+        match app.keymap.lookup(Context::Input, &key) {
+            Some(Action::Back) => app.esc(),
+            Some(Action::Submit) => app.submit_library_edit(),
+            _ => {
+                if let Some(req) = input_request(key) {
+                    app.library_view.input.handle(req);
                 }
-                _ => (),
-            },
+            }
+        }
+        return;
+    }
+    match app.keymap.lookup(Context::Library, &key) {
+        Some(Action::Back) => app.esc(),
+        Some(Action::Down) => app.library_mv(Dir::Down),
+        Some(Action::Up) => app.library_mv(Dir::Up),
+        Some(Action::AddAlg) => app.library_edit(LibraryEdit::AddAlg),
+        Some(Action::Notes) => app.library_edit(LibraryEdit::Notes),
+        Some(Action::CycleMain) => app.library_cycle_main(),
+        Some(Action::Delete) => app.library_delete_alg(),
+        Some(Action::NextSet) => app.trainer.next_set(),
+        _ => (),
+    }
+}
+
+fn handle_solve_input(app: &mut App, key: KeyEvent) {
+    let editing = app.solve_view.as_ref().is_some_and(|v| v.edit.is_some());
+    if editing {
+        match app.keymap.lookup(Context::Input, &key) {
+            Some(Action::Back) => app.esc(),
+            Some(Action::Submit) => app.submit_solve_edit(),
+            _ => {
+                if let (Some(view), Some(req)) = (&mut app.solve_view, input_request(key)) {
+                    view.input.handle(req);
+                    view.message = None;
+                }
+            }
+        }
+        return;
+    }
+    let action = app.keymap.lookup(Context::Solve, &key);
+    // Anything but a second delete cancels the first
+    if action != Some(Action::Delete) {
+        if let Some(view) = &mut app.solve_view {
+            view.confirm_delete = false;
+        }
+    }
+    match action {
+        Some(Action::Back) => app.esc(),
+        Some(Action::Comment) => app.solve_edit(SolveEdit::Comment),
+        Some(Action::Penalty) => app.solve_edit(SolveEdit::Penalty),
+        Some(Action::Copy) => app.copy_scramble(),
+        Some(Action::Delete) => app.solve_delete(),
+        Some(Action::Replay) => {
+            app.esc();
+            app.open_replay();
+        }
+        _ => (),
+    }
+}
+
+/// Lines page up and page down scroll the chat by
+const CHAT_PAGE: isize = 10;
+
+fn handle_chat_input(app: &mut App, key: KeyEvent) {
+    match app.keymap.lookup(Context::Chat, &key) {
+        Some(Action::ScrollBack) => return app.scroll_chat(CHAT_PAGE),
+        Some(Action::ScrollForward) => return app.scroll_chat(-CHAT_PAGE),
+        Some(Action::NextRoom) => return app.chat.cycle(true),
+        Some(Action::PreviousRoom) => return app.chat.cycle(false),
+        _ => (),
+    }
+    match app.keymap.lookup(Context::Input, &key) {
+        Some(Action::Back) => app.esc(),
+        Some(Action::Submit) => app.send_chat(),
+        _ => {
+            if let Some(req) = input_request(key) {
+                app.chat.input.handle(req);
+            }
+        }
+    }
+}
+
+fn handle_direct_input(app: &mut App, key: KeyEvent) {
+    match app.keymap.lookup(Context::Chat, &key) {
+        Some(Action::ScrollBack) => return app.scroll_direct(CHAT_PAGE),
+        Some(Action::ScrollForward) => return app.scroll_direct(-CHAT_PAGE),
+        Some(Action::NextRoom) => return app.direct.cycle(true),
+        Some(Action::PreviousRoom) => return app.direct.cycle(false),
+        _ => (),
+    }
+    match app.keymap.lookup(Context::Input, &key) {
+        Some(Action::Back) => app.esc(),
+        Some(Action::Submit) => {
+            let text = app.direct.input.value().to_string();
+            match app.send_direct(text) {
+                Ok(()) => app.direct.input = Input::default(),
+                Err(e) => app.command_line.message = Some(Err(e)),
+            }
+        }
+        _ => {
+            if let Some(req) = input_request(key) {
+                app.direct.input.handle(req);
+            }
+        }
+    }
+}
+
+/// Apply a key press, returning whether the app should quit
+pub fn handle_input(app: &mut App, key: KeyEvent) -> Result<bool, Box<dyn Error>> {
+    match app.route.screen {
+        Screen::Replay => {
+            handle_replay_input(app, key);
+            return Ok(false);
+        }
+        Screen::Library => {
+            handle_library_input(app, key);
+            return Ok(false);
+        }
+        Screen::Memo => {
+            if app.keymap.lookup(Context::Memo, &key) == Some(Action::Back) {
+                app.esc();
+            }
+            return Ok(false);
+        }
+        Screen::Solve => {
+            handle_solve_input(app, key);
+            return Ok(false);
+        }
+        _ => (),
+    }
+    if app.command_line.open {
+        match app.keymap.lookup(Context::Input, &key) {
+            Some(Action::Back) => app.command_line.close(),
+            Some(Action::Submit) => app.submit_command(),
+            Some(Action::Complete) => app.complete_command(),
+            Some(Action::HistoryBack) => app.command_line.back(),
+            Some(Action::HistoryForward) => app.command_line.forward(),
+            _ => {
+                if let Some(req) = input_request(key) {
+                    app.command_line.input.handle(req);
+                }
+            }
+        }
+        return Ok(false);
+    }
+    // The output of the last command stays until the next key
+    app.command_line.message = None;
+    if app.chat_active() {
+        handle_chat_input(app, key);
+        return Ok(false);
+    }
+    if app.direct_active() {
+        handle_direct_input(app, key);
+        return Ok(false);
+    }
+    // The virtual cube takes the csTimer keys, which are not rebindable
+    if app.virtual_cube_active() && key.modifiers == KeyModifiers::NONE {
+        match key.code {
+            KeyCode::Char(c) => {
+                if let Some(m) = virtual_key_move(c) {
+                    app.virtual_turn(m);
+                }
+                return Ok(false);
+            }
+            KeyCode::Esc => {
+                app.esc();
+                return Ok(false);
+            }
             _ => (),
         }
     }
+    let action = app
+        .context()
+        .and_then(|context| app.keymap.lookup(context, &key))
+        .or_else(|| app.keymap.lookup(Context::Global, &key));
+    match action {
+        Some(Action::Quit) => {
+            app.write_times()?;
+            return Ok(true);
+        }
+        Some(Action::Timer) => app.timer_press(),
+        Some(Action::Back) => app.esc(),
+        Some(Action::Select) => app.route.enter(),
+        Some(Action::Left) => app.mv(Dir::Left),
+        Some(Action::Down) => app.mv(Dir::Down),
+        Some(Action::Up) => app.mv(Dir::Up),
+        Some(Action::Right) => app.mv(Dir::Right),
+        Some(Action::Help) => app.help(),
+        Some(Action::Library) => app.library(),
+        Some(Action::Memo) => app.open_memo(),
+        Some(Action::Write) => {
+            app.write_times()?;
+            app.load_times()?;
+        }
+        Some(Action::Delete) => app.del(),
+        Some(Action::Replay) => app.open_replay(),
+        Some(Action::Details) => app.open_solve(),
+        Some(Action::Command) => app.command_line.open(),
+        Some(Action::NextTool) => app.next_tool(),
+        Some(Action::PreviousTool) => app.previous_tool(),
+        Some(Action::Scramble) if !app.timer.on => app.new_scramble(),
+        Some(Action::Press) => app.trainer_press(),
+        Some(Action::Skip) => app.trainer.next_case(),
+        Some(Action::NextSet) => app.trainer.next_set(),
+        _ => (),
+    }
     Ok(false)
 }
 
+/// Clicks focus the panel under the pointer, the wheel scrolls it and
+/// dragging pans the relay chart, all hit tested against the last frame
+pub fn handle_mouse(app: &mut App, mouse: MouseEvent) {
+    let key = Key::from(mouse);
+    match app.route.screen {
+        Screen::Default => (),
+        Screen::Library => {
+            match key {
+                Key::ScrollDown => app.library_mv(Dir::Down),
+                Key::ScrollUp => app.library_mv(Dir::Up),
+                _ => (),
+            }
+            return;
+        }
+        _ => return,
+    }
+    if let Key::ReleasedButton(_) = key {
+        app.chart.drag = None;
+        return;
+    }
+    let Some((block, rect)) = layout::hit(&app.hit_rects, mouse.column, mouse.row) else {
+        return;
+    };
+    // Row inside the top border
+    let row = (mouse.row - rect.y).checked_sub(1).map(usize::from);
+    let len = app.times.times.len();
+    let chart = block == ActiveBlock::Main && app.active_tool == Tool::Relay;
+    match key {
+        Key::PressedButton(MouseButton::Left) => match block {
+            ActiveBlock::Help => app.help(),
+            ActiveBlock::Tools => {
+                app.focus(block);
+                if let Some(row) = row {
+                    app.select_tool(app.tools_offset + row);
+                }
+            }
+            ActiveBlock::Times => {
+                app.focus(block);
+                // Below the header and its margin
+                if let Some(row) = row.and_then(|r| r.checked_sub(2)) {
+                    app.select_time(app.times_offset + row);
+                }
+            }
+            _ => {
+                app.focus(block);
+                if chart {
+                    app.chart.drag = Some(mouse.column);
+                }
+            }
+        },
+        Key::Drag(MouseButton::Left) if chart => {
+            if let Some(from) = app.chart.drag {
+                let (start, end) = app.chart.window(len);
+                let width = rect.width.saturating_sub(2).max(1) as isize;
+                // Dragging right brings older solves into view
+                let solves =
+                    (mouse.column as isize - from as isize) * (end - start) as isize / width;
+                if solves != 0 {
+                    app.chart.pan_by(len, solves);
+                    app.chart.drag = Some(mouse.column);
+                }
+            }
+        }
+        Key::ScrollDown | Key::ScrollUp => {
+            let down = key == Key::ScrollDown;
+            match block {
+                ActiveBlock::Tools if down => app.next_tool(),
+                ActiveBlock::Tools => app.previous_tool(),
+                ActiveBlock::Times if down => app.next_time(),
+                ActiveBlock::Times => app.previous_time(),
+                ActiveBlock::Main => match app.active_tool {
+                    Tool::Relay => app.chart.zoom(len, !down),
+                    Tool::Gnostr if down => app.scroll_chat(-1),
+                    Tool::Gnostr => app.scroll_chat(1),
+                    Tool::Direct if down => app.scroll_direct(-1),
+                    Tool::Direct => app.scroll_direct(1),
+                    _ => (),
+                },
+                _ => (),
+            }
+        }
+        _ => (),
+    }
+}
+
 fn render_default<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let area = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(app.config.frontend.margin)
+        .constraints([Constraint::Percentage(100)].as_ref())
+        .split(f.size())[0];
+
+    app.panels = layout::panel_rects(&app.config.layout, area);
+    app.hit_rects = app.panels.clone();
+    for (block, rect) in app.panels.clone() {
+        match block {
+            ActiveBlock::Tools => render_help_and_tools(f, app, rect),
+            ActiveBlock::Timer => render_timer(f, app, rect),
+            ActiveBlock::Times => render_times(f, app, rect),
+            ActiveBlock::Stats => render_bests(f, app, rect),
+            ActiveBlock::Scramble => render_topic(f, app, rect),
+            ActiveBlock::Main => render_main(f, app, rect),
+            _ => (),
+        }
+    }
+    render_command_line(f, app, area);
+}
+
+/// The `:` command line, or the output of the last command, over the
+/// bottom of `area`
+fn render_command_line<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let line = &app.command_line;
+    if area.height < 3 {
+        return;
+    }
+    let area = Rect::new(area.x, area.bottom() - 3, area.width, 3);
+    if line.open {
+        // Several completions are listed in the title
+        let title = match &line.message {
+            Some(Ok(matches)) => format!(" {} ", matches),
+            _ => " : ".to_string(),
+        };
+        f.render_widget(Clear, area);
+        render_input(f, &app.theme, &line.input, &title, area);
+    } else if let Some(message) = &line.message {
+        let (text, style) = match message {
+            Ok(text) => (text, app.theme.text.normal.fg()),
+            Err(text) => (text, app.theme.text.error.fg()),
+        };
+        let paragraph = Paragraph::new(text.as_str()).style(style).block(
+            Block::default()
+                .title(" : ")
+                .borders(Borders::ALL)
+                .border_style(app.theme.border.input.fg()),
+        );
+        f.render_widget(Clear, area);
+        f.render_widget(paragraph, area);
+    }
+}
+
+fn render_help<B: Backend>(f: &mut Frame<B>, app: &App) {
     let chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(33), Constraint::Min(100)].as_ref())
+        .direction(Direction::Vertical)
+        .margin(app.config.frontend.margin)
+        .constraints([Constraint::Percentage(100)].as_ref())
         .split(f.size());
 
-    let left_chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints(
+    // Generated from the active keymap so rebound keys show up here
+    let mut rows = app
+        .keymap
+        .bindings
+        .iter()
+        .map(|b| {
+            Row::new(vec![
+                b.description.to_string(),
+                app.keymap.keys(b.context, b.action),
+                b.context.description().to_string(),
+            ])
+        })
+        .collect::<Vec<Row>>();
+    rows.push(Row::new(vec![
+        "Turn the virtual cube (csTimer keys)",
+        "i k j f h g ...",
+        "Virtual tool",
+    ]));
+    let table = Table::new(rows)
+        .header(
+            Row::new(vec!["Description", "Key", "Context"])
+                .style(Style::default().add_modifier(Modifier::BOLD))
+                .bottom_margin(1),
+        )
+        .block(Block::default().title(" help ").borders(Borders::ALL))
+        .widths(
             [
-                Constraint::Length(3),//help and tools height
-                Constraint::Length(3),//timer
-                Constraint::Percentage(100),//table
+                Constraint::Length(48),
+                Constraint::Length(20),
+                Constraint::Length(14),
             ]
             .as_ref(),
-        )
+        );
+    f.render_widget(table, chunks[0]);
+}
+
+fn render_replay<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let Some(replay) = &app.replay else {
+        return;
+    };
+    let time = &app.times.times[replay.solve];
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(app.config.frontend.margin)
+        .constraints([Constraint::Min(0), Constraint::Length(3)].as_ref())
+        .split(f.size());
+    let main_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
         .split(chunks[0]);
+    let info_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(7), Constraint::Min(0)].as_ref())
+        .split(main_chunks[1]);
 
-    let right_chunks = Layout::default()
+    // Cube and move list
+    let mut lines = vec![Spans::from("")];
+    lines.extend(cube_net(&app.theme, &replay.state));
+    lines.push(Spans::from(""));
+    let moves = replay
+        .moves
+        .iter()
+        .enumerate()
+        .map(|(i, m)| {
+            let style = match i + 1 == replay.pos {
+                true => app.theme.text.accent.fg().add_modifier(Modifier::BOLD),
+                false if i < replay.pos => app.theme.text.normal.fg(),
+                false => app.theme.text.dim.fg(),
+            };
+            Span::styled(format!("{} ", m), style)
+        })
+        .collect::<Vec<Span>>();
+    lines.push(Spans::from(moves));
+    let title = format!(
+        " Replay {}/{} {} ",
+        replay.pos,
+        replay.moves.len(),
+        match replay.playing {
+            true => "playing",
+            false => "paused",
+        }
+    );
+    let paragraph = Paragraph::new(lines)
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .alignment(Alignment::Left)
+        .wrap(Wrap { trim: false });
+    f.render_widget(paragraph, main_chunks[0]);
+
+    // Solve summary
+    let recon = time.reconstruction.clone().unwrap_or_default();
+    let move_count = recon.move_count();
+    let tps = match time.time > 0.0 {
+        true => format!("{:.2}", move_count as f64 / time.time),
+        false => "-".to_string(),
+    };
+    let summary = vec![
+        Spans::from(format!("solve   #{}", replay.solve + 1)),
+        Spans::from(format!("time    {}", time.label(2))),
+        Spans::from(format!("moves   {}", move_count)),
+        Spans::from(format!("tps     {}", tps)),
+        Spans::from(format!(
+            "scramble {}",
+            time.scramble.clone().unwrap_or_else(|| "-".to_string())
+        )),
+    ];
+    let paragraph = Paragraph::new(summary)
+        .block(Block::default().title(" Solve ").borders(Borders::ALL))
+        .alignment(Alignment::Left)
+        .wrap(Wrap { trim: true });
+    f.render_widget(paragraph, info_chunks[0]);
+
+    // Per step breakdown
+    let header = Row::new(
+        ["step", "moves", "time", "tps"]
+            .iter()
+            .map(|h| Cell::from(*h)),
+    )
+    .style(app.theme.text.dim.fg())
+    .bottom_margin(1);
+    let step_times = recon.step_times(time.time, &time.splits);
+    let rows = recon
+        .steps
+        .iter()
+        .zip(step_times)
+        .enumerate()
+        .map(|(i, (step, t))| {
+            let tps = match t > 0.0 {
+                true => format!("{:.2}", step.move_count() as f64 / t),
+                false => "-".to_string(),
+            };
+            Row::new(vec![
+                step.label.clone().unwrap_or_else(|| (i + 1).to_string()),
+                step.move_count().to_string(),
+                format!("{:.2}", t),
+                tps,
+            ])
+        });
+    let table = Table::new(rows)
+        .header(header)
+        .block(Block::default().title(" Steps ").borders(Borders::ALL))
+        .widths(&[
+            Constraint::Ratio(4, 10),
+            Constraint::Ratio(2, 10),
+            Constraint::Ratio(2, 10),
+            Constraint::Ratio(2, 10),
+        ]);
+    f.render_widget(table, info_chunks[1]);
+
+    // Editor or key hints
+    if replay.editing {
+        let title = match &replay.error {
+            Some(e) => format!(" Reconstruction: {} ", e),
+            None => " Reconstruction (moves // label @ time; ...) ".to_string(),
+        };
+        render_input(f, &app.theme, &replay.input, &title, chunks[1]);
+    } else {
+        let hints = Paragraph::new(app.keymap.hints(
+            Context::Replay,
+            &[
+                (Action::Play, "play/pause"),
+                (Action::StepBack, "step back"),
+                (Action::StepForward, "step"),
+                (Action::Restart, "restart"),
+                (Action::Edit, "edit reconstruction"),
+                (Action::Back, "back"),
+            ],
+        ))
+        .block(Block::default().borders(Borders::ALL))
+        .style(app.theme.text.dim.fg());
+        f.render_widget(hints, chunks[1]);
+    }
+}
+
+// Memo letters in pairs, followed by cycle breaks and twisted pieces
+fn memo_lines(title: &str, memo: &PieceMemo, twist_title: &str) -> Vec<Spans<'static>> {
+    let list = |letters: Vec<String>| match letters.is_empty() {
+        true => "-".to_string(),
+        false => letters.join(" "),
+    };
+    let pairs = memo.pairs();
+    vec![
+        Spans::from(Span::styled(
+            format!(
+                "{:<9}{}",
+                title,
+                list(pairs.split_whitespace().map(String::from).collect())
+            ),
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Spans::from(format!(
+            "  breaks {}",
+            list(memo.breaks.iter().map(char::to_string).collect())
+        )),
+        Spans::from(format!(
+            "  {:<7}{}",
+            twist_title,
+            list(
+                memo.twisted
+                    .iter()
+                    .map(|(c, t)| format!("{}({})", c, t))
+                    .collect()
+            )
+        )),
+        Spans::from(""),
+    ]
+}
+
+fn render_memo<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let Some((solve, memo)) = &app.memo else {
+        return;
+    };
+    let scramble = app.times.times[*solve].scramble.clone().unwrap_or_default();
+    let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints(
-            [
-                Constraint::Length(3),//topic
-                Constraint::Length(10),//squares
-                Constraint::Percentage(100),//tools view
-            ]
-            .as_ref(),
+        .margin(app.config.frontend.margin)
+        .constraints([Constraint::Min(0), Constraint::Length(3)].as_ref())
+        .split(f.size());
+    let main_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(chunks[0]);
+
+    let state = CubeState::from_moves(&parse_alg(&scramble).unwrap_or_default());
+    let mut lines = vec![Spans::from("")];
+    lines.extend(cube_net(&app.theme, &state));
+    lines.push(Spans::from(""));
+    lines.push(Spans::from(scramble));
+    let paragraph = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title(format!(" Scramble #{} ", solve + 1))
+                .borders(Borders::ALL),
         )
-        .split(chunks[1]);
+        .alignment(Alignment::Left)
+        .wrap(Wrap { trim: false });
+    f.render_widget(paragraph, main_chunks[0]);
 
-    render_help_and_tools(f, app, left_chunks[0]);
-    render_timer(f, app, left_chunks[1]);
-    render_times(f, app, left_chunks[2]);
+    let mut lines = vec![Spans::from("")];
+    lines.extend(memo_lines("edges", &memo.edges, "flips"));
+    lines.extend(memo_lines("corners", &memo.corners, "twists"));
+    lines.push(Spans::from(format!(
+        "parity   {}",
+        match memo.parity() {
+            true => "yes",
+            false => "no",
+        }
+    )));
+    let paragraph = Paragraph::new(lines)
+        .block(Block::default().title(" Memo ").borders(Borders::ALL))
+        .alignment(Alignment::Left)
+        .wrap(Wrap { trim: false });
+    f.render_widget(paragraph, main_chunks[1]);
 
-    render_bests(f, app, right_chunks[0]);
-    render_topic(f, app, right_chunks[1]);
-    render_main(f, app, right_chunks[2]);
+    let hints = Paragraph::new(format!(
+        "letters from the bld section of the config   {}",
+        app.keymap.hints(Context::Memo, &[(Action::Back, "back")])
+    ))
+    .block(Block::default().borders(Borders::ALL))
+    .style(app.theme.text.dim.fg());
+    f.render_widget(hints, chunks[1]);
 }
 
-fn render_help<B: Backend>(f: &mut Frame<B>) {
+/// The solve detail popup over the default screen
+fn render_solve<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    render_default(f, app);
+    let Some(view) = &app.solve_view else {
+        return;
+    };
+    let time = &app.times.times[view.solve];
+    let decimals = app.config.stats.decimals;
+    let size = f.size();
+    let width = size.width.min(90);
+    let height = size.height.min(24);
+    let area = Rect::new(
+        size.x + (size.width - width) / 2,
+        size.y + (size.height - height) / 2,
+        width,
+        height,
+    );
+    // Clicks on the popup go nowhere below it
+    app.hit_rects.clear();
+    f.render_widget(Clear, area);
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Percentage(100)].as_ref())
-        .split(f.size());
+        .constraints([Constraint::Min(0), Constraint::Length(3)].as_ref())
+        .split(area);
 
-    let paragraph = Paragraph::new(HELP_TEXT)
-        .block(Block::default().title(" > ").borders(Borders::ALL))
-        .alignment(Alignment::Left);
+    let field = |name: &str, value: String| {
+        let value = match value.is_empty() {
+            true => "-".to_string(),
+            false => value,
+        };
+        Spans::from(vec![
+            Span::styled(format!("{:<10}", name), app.theme.text.dim.fg()),
+            Span::styled(value, app.theme.text.normal.fg()),
+        ])
+    };
+    let mut lines = vec![
+        field("result", time.label(decimals)),
+        field("time", format!("{:.*}", decimals, time.time)),
+        field("penalty", time.penalty.to_string()),
+        field("date", time.date.map(format_date).unwrap_or_default()),
+        field("comment", time.comment.clone()),
+        field("scramble", time.scramble.clone().unwrap_or_default()),
+    ];
+    if let Some(moves) = time.moves {
+        lines.push(field("moves", moves.to_string()));
+    }
+    if !time.splits.is_empty() {
+        let splits = time
+            .splits
+            .iter()
+            .map(|s| format!("{:.*}", decimals, s))
+            .collect::<Vec<String>>();
+        lines.push(field("splits", splits.join(" ")));
+    }
+    if let Some(recon) = &time.reconstruction {
+        for (i, step) in recon.steps.iter().enumerate() {
+            let name = match i {
+                0 => "recon",
+                _ => "",
+            };
+            let label = step
+                .label
+                .as_ref()
+                .map(|l| format!(" // {}", l))
+                .unwrap_or_default();
+            lines.push(field(name, format!("{}{}", format_alg(&step.moves), label)));
+        }
+    }
+    // The averages of n that end at this solve or one of the next n - 1
+    lines.push(Spans::from(""));
+    for (name, n) in [("ao5", 5), ("ao12", 12)] {
+        let averages = app.times.times[view.solve..]
+            .iter()
+            .take(n)
+            .enumerate()
+            .filter_map(|(i, t)| {
+                let aon = match n {
+                    5 => t.ao5,
+                    _ => t.ao12,
+                };
+                aon.map(|v| format!("#{} {}", view.solve + i + 1, format_time(v, decimals)))
+            })
+            .collect::<Vec<String>>();
+        lines.push(field(name, averages.join("  ")));
+    }
+    let paragraph = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title(format!(" Solve #{} ", view.solve + 1))
+                .borders(Borders::ALL)
+                .border_style(app.theme.border.active.fg()),
+        )
+        .wrap(Wrap { trim: false });
     f.render_widget(paragraph, chunks[0]);
+
+    match view.edit {
+        Some(edit) => {
+            let title = match edit {
+                SolveEdit::Comment => " Comment ",
+                SolveEdit::Penalty => " Penalty: ok, +2 or dnf ",
+            };
+            render_input(f, &app.theme, &view.input, title, chunks[1]);
+        }
+        None => {
+            let text = match (&view.message, view.confirm_delete) {
+                (_, true) => format!(
+                    "press {} again to delete the solve",
+                    app.keymap.keys(Context::Solve, Action::Delete)
+                ),
+                (Some(message), _) => message.clone(),
+                (None, _) => app.keymap.hints(
+                    Context::Solve,
+                    &[
+                        (Action::Comment, "comment"),
+                        (Action::Penalty, "penalty"),
+                        (Action::Copy, "copy scramble"),
+                        (Action::Delete, "delete"),
+                        (Action::Replay, "replay"),
+                        (Action::Back, "close"),
+                    ],
+                ),
+            };
+            let hints = Paragraph::new(text)
+                .block(Block::default().borders(Borders::ALL))
+                .style(app.theme.text.dim.fg());
+            f.render_widget(hints, chunks[1]);
+        }
+    }
+}
+
+fn render_library<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(app.config.frontend.margin)
+        .constraints(
+            [
+                Constraint::Min(0),
+                Constraint::Length(8),
+                Constraint::Length(3),
+            ]
+            .as_ref(),
+        )
+        .split(f.size());
+    let set_name = app
+        .trainer
+        .current_set()
+        .map(|s| s.name.clone())
+        .unwrap_or_default();
+    let cases = app.library_cases();
+    let library = &app.trainer.library;
+
+    let header = Row::new(
+        ["case", "main alg", "algs", "due", "ease", "notes"]
+            .iter()
+            .map(|h| Cell::from(*h)),
+    )
+    .style(app.theme.text.dim.fg())
+    .bottom_margin(1);
+    let rows = cases.iter().map(|(key, alg)| {
+        let name = key.rsplit('/').next().unwrap_or(key).to_string();
+        match library.entries.get(key) {
+            Some(e) => Row::new(vec![
+                name,
+                e.main_alg().unwrap_or_default().to_string(),
+                e.algs.len().to_string(),
+                match e.due_in_days() {
+                    Some(d) if d <= 0.0 => "now".to_string(),
+                    Some(d) => format!("{:.1}d", d),
+                    None => "new".to_string(),
+                },
+                format!("{:.2}", e.ease),
+                e.notes.clone(),
+            ]),
+            None => Row::new(vec![
+                name,
+                alg.clone(),
+                "1".to_string(),
+                "new".to_string(),
+                "-".to_string(),
+                String::new(),
+            ]),
+        }
+    });
+    let table = Table::new(rows)
+        .header(header)
+        .block(
+            Block::default()
+                .title(format!(" Library: {} ", set_name))
+                .borders(Borders::ALL),
+        )
+        .highlight_style(app.theme.highlight.active.fg())
+        .widths(&[
+            Constraint::Length(12),
+            Constraint::Percentage(45),
+            Constraint::Length(5),
+            Constraint::Length(7),
+            Constraint::Length(5),
+            Constraint::Percentage(30),
+        ]);
+    f.render_stateful_widget(table, chunks[0], &mut app.library_view.state);
+
+    // Every algorithm of the selected case
+    let selected = app.library_view.state.selected().and_then(|i| cases.get(i));
+    let mut lines = vec![];
+    if let Some((key, alg)) = selected {
+        match library.entries.get(key) {
+            Some(e) => {
+                for (i, a) in e.algs.iter().enumerate() {
+                    let marker = if i == e.main { "* " } else { "  " };
+                    lines.push(Spans::from(format!("{}{}", marker, a)));
+                }
+                if !e.notes.is_empty() {
+                    lines.push(Spans::from(format!("notes: {}", e.notes)));
+                }
+            }
+            None => lines.push(Spans::from(format!("* {}", alg))),
+        }
+    }
+    let paragraph = Paragraph::new(lines)
+        .block(Block::default().title(" Algorithms ").borders(Borders::ALL))
+        .wrap(Wrap { trim: true });
+    f.render_widget(paragraph, chunks[1]);
+
+    match app.library_view.edit {
+        Some(edit) => {
+            let title = match edit {
+                LibraryEdit::AddAlg => " New algorithm ",
+                LibraryEdit::Notes => " Notes ",
+            };
+            render_input(f, &app.theme, &app.library_view.input, title, chunks[2]);
+        }
+        None => {
+            let hints = Paragraph::new(app.keymap.hints(
+                Context::Library,
+                &[
+                    (Action::Down, "next"),
+                    (Action::Up, "previous"),
+                    (Action::AddAlg, "add alg"),
+                    (Action::CycleMain, "next main alg"),
+                    (Action::Delete, "delete main alg"),
+                    (Action::Notes, "notes"),
+                    (Action::NextSet, "next set"),
+                    (Action::Back, "back"),
+                ],
+            ))
+            .block(Block::default().borders(Borders::ALL))
+            .style(app.theme.text.dim.fg());
+            f.render_widget(hints, chunks[2]);
+        }
+    }
+}
+
+/// Draw a one line `tui_input::Input` with its cursor
+fn render_input<B: Backend>(
+    f: &mut Frame<B>,
+    theme: &Theme,
+    input: &Input,
+    title: &str,
+    area: Rect,
+) {
+    let width = area.width.max(3) - 3;
+    let scroll = input.visual_scroll(width as usize);
+    let paragraph = Paragraph::new(input.value())
+        .scroll((0, scroll as u16))
+        .block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .border_style(theme.border.input.fg()),
+        );
+    f.render_widget(paragraph, area);
+    f.set_cursor(
+        area.x + (input.visual_cursor().max(scroll) - scroll) as u16 + 1,
+        area.y + 1,
+    );
 }
 
 fn render_help_and_tools<B: Backend>(f: &mut Frame<B>, app: &mut App, layout_chunk: Rect) {
@@ -167,18 +1058,28 @@ fn render_help_and_tools<B: Backend>(f: &mut Frame<B>, app: &mut App, layout_chu
                 .borders(Borders::ALL)
                 .border_style(border_style),
         )
-        .style(Style::default().fg(Color::White))
+        .style(app.theme.text.normal.fg())
         .alignment(Alignment::Center)
         .wrap(Wrap { trim: true });
     f.render_widget(paragraph, chunks[0]);
+    app.hit_rects.push((ActiveBlock::Help, chunks[0]));
+    app.hit_rects.push((ActiveBlock::Tools, chunks[1]));
 
     let border_style = app.get_border_style_from_id(ActiveBlock::Tools);
     let selected_style = app.get_highlight_style_from_id(ActiveBlock::Tools);
-    let items = [
-        ListItem::new(Tool::Gnostr.to_string()),
-        ListItem::new(Tool::Relay.to_string()),
-        ListItem::new(Tool::Commit.to_string()),
-    ];
+    // Scrolled here rather than by the widget so clicks can find the rows
+    let visible = chunks[1].height.saturating_sub(2) as usize;
+    let selected = app.tools_state.selected();
+    app.tools_offset = layout::scroll_offset(app.tools_offset, selected, visible)
+        .min(app.tools.len().saturating_sub(visible));
+    let items = app
+        .tools
+        .iter()
+        .skip(app.tools_offset)
+        .map(|tool| ListItem::new(tool.to_string()))
+        .collect::<Vec<ListItem>>();
+    let mut state = ListState::default();
+    state.select(selected.map(|i| i - app.tools_offset));
     let list = List::new(items)
         .block(
             Block::default()
@@ -186,62 +1087,134 @@ fn render_help_and_tools<B: Backend>(f: &mut Frame<B>, app: &mut App, layout_chu
                 .borders(Borders::ALL)
                 .border_style(border_style),
         )
-        .style(Style::default().fg(Color::White))
+        .style(app.theme.text.normal.fg())
         .highlight_style(selected_style);
 
-    f.render_stateful_widget(list, chunks[1], &mut app.tools_state);
+    f.render_stateful_widget(list, chunks[1], &mut state);
 }
 
-fn render_timer<B: Backend>(f: &mut Frame<B>, app: &mut App, layout_chunk: Rect) {
-    let text = format!("{}", app.timer.text());
-    let borderstyle = app.get_border_style_from_id(ActiveBlock::Timer);
-    let mut paragraphstyle = Style::default();
-    paragraphstyle = match app.timer.on {
+/// Only the running timer, so nothing else draws the eye during a solve
+fn render_focus<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let area = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(app.config.frontend.margin)
+        .constraints([Constraint::Percentage(100)].as_ref())
+        .split(f.size())[0];
+    // Nothing on screen to click
+    app.hit_rects.clear();
+
+    let text = timer_text(app);
+    let lines = big_digits(app, &text, area).unwrap_or_else(|| {
+        let mut lines = vec![Spans::from(""); (area.height / 2) as usize];
+        lines.push(Spans::from(text));
+        lines
+    });
+    let paragraph = Paragraph::new(lines)
+        .style(timer_style(app))
+        .alignment(Alignment::Center);
+    f.render_widget(paragraph, area);
+}
+
+fn timer_text(app: &App) -> String {
+    match app.timer.on && app.config.timer.hide_running {
+        true => "solving".to_string(),
+        false => app.timer.text(app.config.timer.decimals),
+    }
+}
+
+fn timer_style(app: &App) -> Style {
+    match app.timer.on {
         false => match app.timer.lasttime {
-            Some(_) => paragraphstyle.fg(Color::White),
-            None => paragraphstyle.fg(Color::Gray),
+            Some(_) => app.theme.timer.stopped.fg(),
+            None => app.theme.timer.idle.fg(),
         },
-        true => paragraphstyle.fg(Color::Magenta),
+        true => app.theme.timer.running.fg(),
+    }
+}
+
+/// `text` in big digits, vertically centered in `area`, if they are enabled
+/// and fit. Never for a hidden running time.
+fn big_digits(app: &App, text: &str, area: Rect) -> Option<Vec<Spans<'static>>> {
+    let hidden = app.timer.on && app.config.timer.hide_running;
+    if hidden
+        || !app.config.timer.big_digits
+        || area.height < digits::HEIGHT
+        || area.width < digits::width(text)
+    {
+        return None;
+    }
+    let mut lines = vec![Spans::from(""); ((area.height - digits::HEIGHT) / 2) as usize];
+    lines.extend(digits::big_text(text).into_iter().map(Spans::from));
+    Some(lines)
+}
+
+fn render_timer<B: Backend>(f: &mut Frame<B>, app: &mut App, layout_chunk: Rect) {
+    let text = timer_text(app);
+    let borderstyle = app.get_border_style_from_id(ActiveBlock::Timer);
+    let block = Block::default()
+        .title(" Start/Stop ")
+        .borders(Borders::ALL)
+        .border_style(borderstyle);
+    let paragraph = match big_digits(app, &text, block.inner(layout_chunk)) {
+        Some(lines) => Paragraph::new(lines).alignment(Alignment::Center),
+        None => Paragraph::new(text)
+            .alignment(Alignment::Left)
+            .wrap(Wrap { trim: true }),
     };
-    let paragraph = Paragraph::new(text)
-        .block(
-            Block::default()
-                .title(" Start/Stop ")
-                .borders(Borders::ALL)
-                .border_style(borderstyle),
-        )
-        .style(paragraphstyle)
-        .alignment(Alignment::Left)
-        .wrap(Wrap { trim: true });
-    f.render_widget(paragraph, layout_chunk);
+    f.render_widget(paragraph.block(block).style(timer_style(app)), layout_chunk);
 }
 
 fn render_times<B: Backend>(f: &mut Frame<B>, app: &mut App, layout_chunk: Rect) {
     let selected_style = app.get_highlight_style_from_id(ActiveBlock::Times);
-    let normal_style = Style::default().fg(Color::Gray);
-    let header_cells = ["i", "time", "ao5", "ao12"].iter().map(|h| Cell::from(*h));
+    let normal_style = app.theme.text.dim.fg();
+    let header_cells = ["i", "time", "ao5", "ao12", "tps"]
+        .iter()
+        .map(|h| Cell::from(*h));
     let header = Row::new(header_cells)
         .style(normal_style)
         .height(1)
         .bottom_margin(1);
     let numrows = app.times.times.len();
-    let rows = app.times.times.iter().rev().enumerate().map(|(i, t)| {
-        let ao5 = match t.ao5 {
-            Some(v) => format!("{:.2}", v),
-            None => "-".to_string(),
-        };
-        let ao12 = match t.ao12 {
-            Some(v) => format!("{:.2}", v),
-            None => "-".to_string(),
-        };
-        let cells = vec![
-            (numrows - i).to_string(),
-            format!("{:.2}", t.time),
-            format!("{}", ao5),
-            format!("{}", ao12),
-        ];
-        Row::new(cells)
-    });
+    let decimals = app.config.stats.decimals;
+    // Borders, header and its margin take four rows
+    let visible = layout_chunk.height.saturating_sub(4) as usize;
+    let selected = app.times_state.selected();
+    app.times_offset = layout::scroll_offset(app.times_offset, selected, visible)
+        .min(numrows.saturating_sub(visible));
+    let offset = app.times_offset;
+    let rows =
+        app.times
+            .times
+            .iter()
+            .rev()
+            .enumerate()
+            .skip(offset)
+            .map(|(i, t)| {
+                let ao5 = match t.ao5 {
+                    Some(v) => format!("{:.*}", decimals, v),
+                    None => "-".to_string(),
+                };
+                let ao12 = match t.ao12 {
+                    Some(v) => format!("{:.*}", decimals, v),
+                    None => "-".to_string(),
+                };
+                let tps = match t.tps() {
+                    Some(v) => format!("{:.2}", v),
+                    None => "-".to_string(),
+                };
+                let cells = vec![
+                    (numrows - i).to_string(),
+                    t.label(decimals),
+                    format!("{}", ao5),
+                    format!("{}", ao12),
+                    tps,
+                ];
+                match i == 0 && app.new_result {
+                    true => Row::new(cells)
+                        .style(app.theme.text.accent.fg().add_modifier(Modifier::BOLD)),
+                    false => Row::new(cells),
+                }
+            });
     let border_style = app.get_border_style_from_id(ActiveBlock::Times);
     let table = Table::new(rows)
         .header(header)
@@ -253,12 +1226,15 @@ fn render_times<B: Backend>(f: &mut Frame<B>, app: &mut App, layout_chunk: Rect)
         )
         .highlight_style(selected_style)
         .widths(&[
-            Constraint::Ratio(1, 10),
-            Constraint::Ratio(3, 10),
-            Constraint::Ratio(3, 10),
-            Constraint::Ratio(3, 10),
+            Constraint::Ratio(1, 9),
+            Constraint::Ratio(2, 9),
+            Constraint::Ratio(2, 9),
+            Constraint::Ratio(2, 9),
+            Constraint::Ratio(2, 9),
         ]);
-    f.render_stateful_widget(table, layout_chunk, &mut app.times_state);
+    let mut state = TableState::default();
+    state.select(selected.map(|i| i - offset));
+    f.render_stateful_widget(table, layout_chunk, &mut state);
 }
 
 fn render_topic<B: Backend>(f: &mut Frame<B>, app: &mut App, layout_chunk: Rect) {
@@ -270,34 +1246,35 @@ fn render_topic<B: Backend>(f: &mut Frame<B>, app: &mut App, layout_chunk: Rect)
                 .borders(Borders::ALL)
                 .border_style(border_style),
         )
-        .style(Style::default().fg(Color::White))
+        .style(app.theme.text.normal.fg())
         .alignment(Alignment::Center)
         .wrap(Wrap { trim: true });
     f.render_widget(paragraph, layout_chunk);
 }
 
 fn render_bests<B: Backend>(f: &mut Frame<B>, app: &mut App, layout_chunk: Rect) {
+    let panels = app.config.stats.panels.clone();
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints(
-            [
-                Constraint::Ratio(1, 3),
-                Constraint::Ratio(1, 3),
-                Constraint::Ratio(1, 3),
-                //Constraint::Ratio(1, 6),
-                //Constraint::Ratio(1, 6),
-                //Constraint::Ratio(1, 6),
-            ]
-            .as_ref(),
+            panels
+                .iter()
+                .map(|_| Constraint::Ratio(1, panels.len() as u32))
+                .collect::<Vec<Constraint>>(),
         )
         .split(layout_chunk);
 
-    render_stat(f, app, "Stats 1", app.times.pbsingle, chunks[0]);
-    render_stat(f, app, "Stats 2", app.times.pbao5, chunks[1]);
-    render_stat(f, app, "Stats 3", app.times.pbao12, chunks[2]);
-    //render_stat(f, app, "Stats 4", app.times.ao100, chunks[3]);
-    //render_stat(f, app, "Stats 5", app.times.ao1k, chunks[4]);
-    //render_stat(f, app, "Stats 6", app.times.rollingavg, chunks[5]);
+    for (panel, chunk) in panels.iter().zip(chunks) {
+        let (title, stat) = match panel.as_str() {
+            "best" => (" best ", app.times.pbsingle),
+            "ao5" => (" best ao5 ", app.times.pbao5),
+            "ao12" => (" best ao12 ", app.times.pbao12),
+            "ao100" => (" ao100 ", app.times.ao100),
+            "ao1k" => (" ao1k ", app.times.ao1k),
+            _ => (" mean ", app.times.rollingavg),
+        };
+        render_stat(f, app, title, stat, chunk);
+    }
 }
 
 fn render_stat<B: Backend>(
@@ -309,7 +1286,7 @@ fn render_stat<B: Backend>(
 ) {
     let border_style = app.get_border_style_from_id(ActiveBlock::Stats);
     let text = match stat {
-        Some(v) => format!("{:.2}", v),
+        Some(v) => format_time(v, app.config.stats.decimals),
         None => "n/a".to_string(),
     };
     let paragraph = Paragraph::new(text)
@@ -319,7 +1296,7 @@ fn render_stat<B: Backend>(
                 .borders(Borders::ALL)
                 .border_style(border_style),
         )
-        .style(Style::default().fg(Color::White))
+        .style(app.theme.text.normal.fg())
         .alignment(Alignment::Center)
         .wrap(Wrap { trim: true });
     f.render_widget(paragraph, layout_chunk);
@@ -328,62 +1305,528 @@ fn render_stat<B: Backend>(
 fn render_main<B: Backend>(f: &mut Frame<B>, app: &mut App, layout_chunk: Rect) {
     match app.active_tool {
         Tool::Gnostr => render_gnostr_chat(f, app, layout_chunk),
+        Tool::Direct => render_direct(f, app, layout_chunk),
         Tool::Relay => render_relay(f, app, layout_chunk),
         Tool::Commit => render_cube(f, app, layout_chunk),
+        Tool::Virtual => render_virtual_cube(f, app, layout_chunk),
+        Tool::Trainer => render_trainer(f, app, layout_chunk),
+    }
+}
+
+fn face_color(theme: &Theme, face: Face) -> Color {
+    let cube = &theme.cube;
+    match face {
+        Face::U => cube.u.0,
+        Face::R => cube.r.0,
+        Face::F => cube.f.0,
+        Face::D => cube.d.0,
+        Face::L => cube.l.0,
+        Face::B => cube.b.0,
+        _ => Color::Reset,
+    }
+}
+
+/// Unfolded net of the cube: U on top, L F R B in the middle row and D at the bottom
+fn cube_net(theme: &Theme, state: &CubeState) -> Vec<Spans<'static>> {
+    let sticker = |face: Face| Span::styled("██", Style::default().fg(face_color(theme, face)));
+    let indent = || Span::raw(" ".repeat(7));
+    let mut lines = vec![];
+    for row in 0..3 {
+        let mut spans = vec![indent()];
+        spans.extend(
+            state.face(Face::U)[row * 3..row * 3 + 3]
+                .iter()
+                .map(|f| sticker(*f)),
+        );
+        lines.push(Spans::from(spans));
+    }
+    for row in 0..3 {
+        let mut spans = vec![];
+        for face in [Face::L, Face::F, Face::R, Face::B] {
+            spans.extend(
+                state.face(face)[row * 3..row * 3 + 3]
+                    .iter()
+                    .map(|f| sticker(*f)),
+            );
+            spans.push(Span::raw(" "));
+        }
+        lines.push(Spans::from(spans));
+    }
+    for row in 0..3 {
+        let mut spans = vec![indent()];
+        spans.extend(
+            state.face(Face::D)[row * 3..row * 3 + 3]
+                .iter()
+                .map(|f| sticker(*f)),
+        );
+        lines.push(Spans::from(spans));
+    }
+    lines
+}
+
+/// Top view of the last layer with the side stickers around it
+fn ll_diagram(theme: &Theme, state: &CubeState) -> Vec<Spans<'static>> {
+    let sticker = |face: Face| Span::styled("██", Style::default().fg(face_color(theme, face)));
+    let (u, f, r, b, l) = (
+        state.face(Face::U),
+        state.face(Face::F),
+        state.face(Face::R),
+        state.face(Face::B),
+        state.face(Face::L),
+    );
+    let mut lines = vec![Spans::from(vec![
+        Span::raw("  "),
+        sticker(b[2]),
+        sticker(b[1]),
+        sticker(b[0]),
+    ])];
+    for row in 0..3 {
+        let mut spans = vec![sticker(l[row])];
+        spans.extend(u[row * 3..row * 3 + 3].iter().map(|f| sticker(*f)));
+        spans.push(sticker(r[2 - row]));
+        lines.push(Spans::from(spans));
+    }
+    lines.push(Spans::from(vec![
+        Span::raw("  "),
+        sticker(f[0]),
+        sticker(f[1]),
+        sticker(f[2]),
+    ]));
+    lines
+}
+
+fn render_trainer<B: Backend>(f: &mut Frame<B>, app: &mut App, layout_chunk: Rect) {
+    let border_style = app.get_border_style_from_id(ActiveBlock::Main);
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(layout_chunk);
+    let trainer = &app.trainer;
+
+    let set_name = match trainer.current_set() {
+        None => "no case sets".to_string(),
+        Some(set) => match set.enabled().count() {
+            n if n == set.cases.len() => set.name.clone(),
+            n => format!("{} ({} of {})", set.name, n, set.cases.len()),
+        },
+    };
+    let mut lines = vec![
+        Spans::from(format!("setup  {}", trainer.setup_text())),
+        Spans::from(""),
+    ];
+    if trainer.revealed {
+        lines.extend(ll_diagram(&app.theme, &trainer.state));
+    } else {
+        lines.push(Spans::from(format!(
+            "{} to show the case and start",
+            app.keymap.keys(Context::Trainer, Action::Press)
+        )));
+    }
+    lines.push(Spans::from(""));
+    let timer_style = match trainer.running() {
+        true => app.theme.timer.running.fg(),
+        false => app.theme.timer.stopped.fg(),
+    };
+    lines.push(Spans::from(Span::styled(trainer.text(), timer_style)));
+    if let Some(e) = &trainer.error {
+        lines.push(Spans::from(Span::styled(
+            e.clone(),
+            app.theme.text.error.fg(),
+        )));
+    }
+    let paragraph = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title(format!(" Trainer: {} ", set_name))
+                .borders(Borders::ALL)
+                .border_style(border_style),
+        )
+        .alignment(Alignment::Left)
+        .wrap(Wrap { trim: false });
+    f.render_widget(paragraph, chunks[0]);
+
+    let header = Row::new(["case", "n", "mean", "best"].iter().map(|h| Cell::from(*h)))
+        .style(app.theme.text.dim.fg())
+        .bottom_margin(1);
+    let rows = trainer.slowest().into_iter().map(|(name, s)| {
+        let (mean, best) = match s.count {
+            0 => ("-".to_string(), "-".to_string()),
+            _ => (
+                format!("{:.2}", s.mean()),
+                s.best.map(|v| format!("{:.2}", v)).unwrap_or_default(),
+            ),
+        };
+        Row::new(vec![name.to_string(), s.count.to_string(), mean, best])
+    });
+    let table = Table::new(rows)
+        .header(header)
+        .block(
+            Block::default()
+                .title(" Slowest cases ")
+                .borders(Borders::ALL)
+                .border_style(border_style),
+        )
+        .widths(&[
+            Constraint::Ratio(4, 10),
+            Constraint::Ratio(2, 10),
+            Constraint::Ratio(2, 10),
+            Constraint::Ratio(2, 10),
+        ]);
+    f.render_widget(table, chunks[1]);
+}
+
+fn render_virtual_cube<B: Backend>(f: &mut Frame<B>, app: &mut App, layout_chunk: Rect) {
+    let border_style = app.get_border_style_from_id(ActiveBlock::Main);
+    let mut lines = vec![Spans::from("")];
+    lines.extend(cube_net(&app.theme, &app.virtual_cube.state));
+    lines.push(Spans::from(""));
+    lines.push(Spans::from(format_alg(&app.virtual_cube.moves)));
+    let title = format!(" Virtual ({} moves) ", app.virtual_cube.move_count());
+    let paragraph = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .border_style(border_style),
+        )
+        .alignment(Alignment::Left)
+        .wrap(Wrap { trim: false });
+    f.render_widget(paragraph, layout_chunk);
+}
+
+/// A line for an envelope that is not a chat message
+fn describe(body: &Body) -> String {
+    match body {
+        Body::Chat { text } => text.clone(),
+        Body::Solve {
+            time,
+            penalty,
+            scramble,
+        } => {
+            let mut solve = Time::from(*time);
+            solve.penalty = penalty.parse().unwrap_or(Penalty::None);
+            match scramble {
+                Some(scramble) => format!("solved {} in {}", scramble, solve.label(2)),
+                None => format!("solved in {}", solve.label(2)),
+            }
+        }
+        Body::Presence { status } => match status {
+            Presence::Online => "is online",
+            Presence::Solving => "is solving",
+            Presence::Away => "is away",
+            Presence::Offline => "went offline",
+        }
+        .to_string(),
+        Body::Race { race, control } => match control {
+            RaceControl::Open { scramble } => format!("opened race {} on {}", race, scramble),
+            RaceControl::Ready => format!("is ready for race {}", race),
+            RaceControl::Start => format!("started race {}", race),
+            RaceControl::Abort => format!("aborted race {}", race),
+        },
     }
 }
 
 fn render_gnostr_chat<B: Backend>(f: &mut Frame<B>, app: &mut App, layout_chunk: Rect) {
     let border_style = app.get_border_style_from_id(ActiveBlock::Main);
-    let paragraph = Paragraph::new(WELCOME_TEXT)
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(3)].as_ref())
+        .split(layout_chunk);
+    // The peer list goes beside the messages when there is room
+    let (log_area, peers_area) = match chunks[0].width >= 60 {
+        true => {
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Min(30), Constraint::Length(24)].as_ref())
+                .split(chunks[0]);
+            (columns[0], Some(columns[1]))
+        }
+        false => (chunks[0], None),
+    };
+
+    let mut lines = WELCOME_TEXT
+        .lines()
+        .map(Spans::from)
+        .collect::<Vec<Spans>>();
+    let quiet = !app
+        .chat
+        .room()
+        .log
+        .iter()
+        .any(|e| matches!(e, NetworkEvent::Message { .. }));
+    if quiet {
+        let style = app.theme.text.dim.fg();
+        let message = &app.config.frontend.default_message;
+        lines.extend(
+            message
+                .lines()
+                .map(|l| Spans::from(Span::styled(l.to_string(), style))),
+        );
+    }
+    // Whatever is drawn of the shown room has been read
+    app.chat.room_mut().unread = 0;
+    lines.extend(app.chat.room().log.iter().map(|event| match event {
+        NetworkEvent::Message {
+            source, envelope, ..
+        } => {
+            let (text, style) = match &envelope.body {
+                Body::Chat { text } => (text.clone(), app.theme.text.normal.fg()),
+                body => (describe(body), app.theme.text.dim.fg()),
+            };
+            Spans::from(vec![
+                Span::styled(
+                    format!("{}: ", app.sender_name(source, &envelope.nick)),
+                    app.theme.text.accent.fg(),
+                ),
+                Span::styled(text, style),
+            ])
+        }
+        NetworkEvent::Status(status) => {
+            Spans::from(Span::styled(status.clone(), app.theme.text.dim.fg()))
+        }
+        _ => Spans::default(),
+    }));
+    // Keep the newest messages in view unless scrolled back
+    let height = log_area.height.saturating_sub(2) as usize;
+    let room = app.chat.room_mut();
+    room.scroll = room.scroll.min(lines.len().saturating_sub(height));
+    lines.truncate(lines.len() - room.scroll);
+    let lines = lines.split_off(lines.len().saturating_sub(height));
+    // Rooms as tabs, with their unread messages
+    let mut title = vec![Span::raw(" gnostr ")];
+    for (i, room) in app.chat.rooms.iter().enumerate() {
+        let style = match i == app.chat.current {
+            true => app.theme.text.accent.fg().add_modifier(Modifier::BOLD),
+            false => app.theme.text.dim.fg(),
+        };
+        let name = match room.unread {
+            0 => format!("#{}", room.name),
+            unread => format!("#{} ({})", room.name, unread),
+        };
+        title.push(Span::styled(name, style));
+        title.push(Span::raw(" "));
+    }
+    title.push(Span::raw(format!("· {} ", app.chat.connection())));
+    let paragraph = Paragraph::new(lines)
         .block(
             Block::default()
-                .title(" gnostr ")
+                .title(Spans::from(title))
                 .borders(Borders::ALL)
                 .border_style(border_style),
         )
         .alignment(Alignment::Left);
-    f.render_widget(paragraph, layout_chunk);
+    f.render_widget(paragraph, log_area);
+
+    if let Some(area) = peers_area {
+        let mut items = app
+            .chat
+            .peers
+            .iter()
+            .map(|p| ListItem::new(app.address_book.name(p)))
+            .collect::<Vec<ListItem>>();
+        if let Some(local) = &app.chat.local {
+            items.insert(
+                0,
+                ListItem::new(format!("{} (me)", app.address_book.name(local)))
+                    .style(app.theme.text.dim.fg()),
+            );
+        }
+        let list = List::new(items).block(
+            Block::default()
+                .title(" peers ")
+                .borders(Borders::ALL)
+                .border_style(border_style),
+        );
+        f.render_widget(list, area);
+    }
+
+    if app.chat_active() {
+        render_input(f, &app.theme, &app.chat.input, " message ", chunks[1]);
+    } else {
+        let text = match app.chat.input.value() {
+            "" => "enter the panel to chat",
+            draft => draft,
+        };
+        let paragraph = Paragraph::new(text).style(app.theme.text.dim.fg()).block(
+            Block::default()
+                .title(" message ")
+                .borders(Borders::ALL)
+                .border_style(border_style),
+        );
+        f.render_widget(paragraph, chunks[1]);
+    }
 }
 
-fn render_cube<B: Backend>(f: &mut Frame<B>, app: &mut App, layout_chunk: Rect) {
+/// Conversations beside the messages of the one shown, ours marked with
+/// how far they got
+fn render_direct<B: Backend>(f: &mut Frame<B>, app: &mut App, layout_chunk: Rect) {
     let border_style = app.get_border_style_from_id(ActiveBlock::Main);
-    let paragraph = Paragraph::new(CUBE_TEXT)
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(24), Constraint::Min(30)].as_ref())
+        .split(layout_chunk);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(3)].as_ref())
+        .split(columns[1]);
+
+    let items = app
+        .direct
+        .conversations
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let name = app.address_book.name(&c.peer);
+            let (text, style) = match (i == app.direct.current, c.unread) {
+                (true, _) => (
+                    name,
+                    app.theme.text.accent.fg().add_modifier(Modifier::BOLD),
+                ),
+                (false, 0) => (name, app.theme.text.normal.fg()),
+                (false, unread) => (
+                    format!("{} ({})", name, unread),
+                    app.theme.text.normal.fg().add_modifier(Modifier::BOLD),
+                ),
+            };
+            ListItem::new(text).style(style)
+        })
+        .collect::<Vec<ListItem>>();
+    let list = List::new(items).block(
+        Block::default()
+            .title(" conversations ")
+            .borders(Borders::ALL)
+            .border_style(border_style),
+    );
+    f.render_widget(list, columns[0]);
+
+    let height = chunks[0].height.saturating_sub(2) as usize;
+    let title = match app.direct.conversation() {
+        Some(c) => format!(" direct · {} ", app.address_book.name(&c.peer)),
+        None => " direct ".to_string(),
+    };
+    let mut lines = match app.direct.conversation() {
+        None => vec![Spans::from(Span::styled(
+            "no conversations yet, start one with :dm <peer>",
+            app.theme.text.dim.fg(),
+        ))],
+        Some(c) => c
+            .log
+            .iter()
+            .map(|line| {
+                let name = match line.mine {
+                    true => "me".to_string(),
+                    false => app.address_book.name(&c.peer),
+                };
+                let mark = match &line.delivery {
+                    None | Some(Delivery::Acknowledged) => String::new(),
+                    Some(Delivery::Failed(why)) => format!("  (failed, {})", why),
+                    Some(state) => format!("  ({})", state),
+                };
+                Spans::from(vec![
+                    Span::styled(format!("{}: ", name), app.theme.text.accent.fg()),
+                    Span::styled(line.text.clone(), app.theme.text.normal.fg()),
+                    Span::styled(mark, app.theme.text.dim.fg()),
+                ])
+            })
+            .collect::<Vec<Spans>>(),
+    };
+    // Whatever is drawn of the conversation shown has been read
+    if let Some(conversation) = app.direct.conversation_mut() {
+        conversation.unread = 0;
+        conversation.scroll = conversation.scroll.min(lines.len().saturating_sub(height));
+        lines.truncate(lines.len() - conversation.scroll);
+    }
+    let lines = lines.split_off(lines.len().saturating_sub(height));
+    let paragraph = Paragraph::new(lines)
         .block(
             Block::default()
-                .title(" Commit ")
+                .title(title)
                 .borders(Borders::ALL)
                 .border_style(border_style),
         )
         .alignment(Alignment::Left);
-    f.render_widget(paragraph, layout_chunk);
+    f.render_widget(paragraph, chunks[0]);
+
+    if app.direct_active() {
+        render_input(f, &app.theme, &app.direct.input, " message ", chunks[1]);
+    } else {
+        let text = match app.direct.input.value() {
+            "" => "enter the panel to write",
+            draft => draft,
+        };
+        let paragraph = Paragraph::new(text).style(app.theme.text.dim.fg()).block(
+            Block::default()
+                .title(" message ")
+                .borders(Borders::ALL)
+                .border_style(border_style),
+        );
+        f.render_widget(paragraph, chunks[1]);
+    }
+}
+
+fn render_cube<B: Backend>(f: &mut Frame<B>, app: &mut App, layout_chunk: Rect) {
+    let border_style = app.get_border_style_from_id(ActiveBlock::Main);
+    let block = Block::default()
+        .title(" Commit ")
+        .borders(Borders::ALL)
+        .border_style(border_style);
+    let Some(git_log) = &app.git_log else {
+        let paragraph = Paragraph::new(CUBE_TEXT)
+            .block(block)
+            .alignment(Alignment::Left);
+        f.render_widget(paragraph, layout_chunk);
+        return;
+    };
+
+    let inner = block.inner(layout_chunk);
+    f.render_widget(block, layout_chunk);
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(56), Constraint::Min(0)].as_ref())
+        .split(inner);
+    f.render_widget(Paragraph::new(CUBE_TEXT), chunks[0]);
+    let log = match git_log {
+        Ok(log) => Paragraph::new(log.clone()),
+        Err(e) => Paragraph::new(e.clone()).style(app.theme.text.error.fg()),
+    };
+    f.render_widget(
+        log.block(Block::default().title(" git log ").borders(Borders::LEFT)),
+        chunks[1],
+    );
 }
 
 fn render_relay<B: Backend>(f: &mut Frame<B>, app: &mut App, layout_chunk: Rect) {
+    // Solves in view after zooming and panning with the mouse
+    let (start, end) = app.chart.window(app.times.times.len());
+    let in_view = |(x, _): &(f64, f64)| *x >= start as f64 && *x <= end as f64;
     let singles = app
         .times
         .times
         .iter()
         .enumerate()
-        .map(|(i, v)| (i as f64, v.time))
+        .map(|(i, v)| (i as f64, v.result()))
+        .filter(|(_, t)| t.is_finite())
+        .filter(in_view)
         .collect::<Vec<(f64, f64)>>();
     let ao5s = &app
         .times
         .iter()
         .enumerate()
         .filter_map(|(i, v)| match v.ao5 {
-            Some(a) => Some((i as f64, a)),
-            None => None,
+            Some(a) if a.is_finite() => Some((i as f64, a)),
+            _ => None,
         })
+        .filter(in_view)
         .collect::<Vec<(f64, f64)>>();
     let ao12s = &app
         .times
         .iter()
         .enumerate()
         .filter_map(|(i, v)| match v.ao12 {
-            Some(a) => Some((i as f64, a)),
-            None => None,
+            Some(a) if a.is_finite() => Some((i as f64, a)),
+            _ => None,
         })
+        .filter(in_view)
         .collect::<Vec<(f64, f64)>>();
 
     let border_style = app.get_border_style_from_id(ActiveBlock::Main);
@@ -392,26 +1835,26 @@ fn render_relay<B: Backend>(f: &mut Frame<B>, app: &mut App, layout_chunk: Rect)
             .name("single")
             .marker(symbols::Marker::Dot)
             .graph_type(GraphType::Line)
-            .style(Style::default().fg(Color::Cyan))
+            .style(app.theme.chart.single.fg())
             .data(&singles),
         Dataset::default()
             .name("ao5")
             .marker(symbols::Marker::Dot)
             .graph_type(GraphType::Line)
-            .style(Style::default().fg(Color::LightGreen))
+            .style(app.theme.chart.ao5.fg())
             .data(&ao5s),
         Dataset::default()
             .name("ao12")
             .marker(symbols::Marker::Dot)
             .graph_type(GraphType::Line)
-            .style(Style::default().fg(Color::Magenta))
+            .style(app.theme.chart.ao12.fg())
             .data(&ao12s),
     ];
 
-    let xmid = app.times.times.len() / 2;
-    let xmax = app.times.times.len();
+    let xmid = (start + end) / 2;
+    let xmin_str = start.to_string();
     let xmid_str = xmid.to_string();
-    let xmax_str = xmax.to_string();
+    let xmax_str = end.to_string();
 
     let ymin = app.times.pbsingle.unwrap_or(0.0);
     let ymax = app.times.worst;
@@ -430,10 +1873,10 @@ fn render_relay<B: Backend>(f: &mut Frame<B>, app: &mut App, layout_chunk: Rect)
         .x_axis(
             Axis::default()
                 .title(Span::styled("n", Style::default()))
-                .style(Style::default().fg(Color::White))
-                .bounds([0.0, app.times.times.len() as f64])
+                .style(app.theme.chart.axis.fg())
+                .bounds([start as f64, end as f64])
                 .labels(
-                    ["0", &xmid_str, &xmax_str]
+                    [xmin_str, xmid_str, xmax_str]
                         .iter()
                         .cloned()
                         .map(Span::from)
@@ -443,7 +1886,7 @@ fn render_relay<B: Backend>(f: &mut Frame<B>, app: &mut App, layout_chunk: Rect)
         .y_axis(
             Axis::default()
                 .title(Span::styled("Time", Style::default()))
-                .style(Style::default().fg(Color::White))
+                .style(app.theme.chart.axis.fg())
                 .bounds([ymin, ymax])
                 .labels(
                     [ymin_str, ymid_str, ymax_str]
@@ -0,0 +1,655 @@
+//! Renders every screen into tui's `TestBackend` and compares the text with
+//! the fixtures in `tests/snapshots`. Run with `UPDATE_SNAPSHOTS=1` to write
+//! missing or changed fixtures after checking the new output by eye.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use crossterm::event::KeyEvent;
+use tui::{backend::TestBackend, Terminal};
+
+use cube_tui::{
+    app::{ActiveBlock, App, Penalty, Screen, Time, Tool},
+    chat::{
+        outbox::Delivery,
+        wire::{self, Body, Envelope},
+        ChatCommand,
+    },
+    cube::{parse_alg, CubeState},
+    handlers::{event::NetworkEvent, keymap::KeyChord},
+    ui, CompleteConfig,
+};
+
+const SCRAMBLE: &str = "R U R' U' F2 D L2 B' U2 R D' F";
+const SOLVES: [(f64, &str); 6] = [
+    (14.21, "D2 F' R2 U L2 B D' R' F2 U"),
+    (12.87, "L' U2 B R2 D F' L2 U' R B2"),
+    (13.5, "F R' D2 L U' B2 R F' D L2"),
+    (11.92, "U' L2 F D' R B' U2 L' F2 R"),
+    (15.03, "B2 D R' F U2 L' B R2 D' F"),
+    (12.4, "R2 F' U L' D2 B R' U2 F L"),
+];
+
+/// Times file of one test in a fresh directory, so earlier runs do not leak in
+fn times_path(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("ui").join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir.join("times")
+}
+
+/// An app with fixed solves, scramble and trainer case so frames repeat
+fn app(path: &Path) -> App {
+    let mut config = CompleteConfig::default();
+    config.storage.autosave = false;
+    let mut app = App::new(config, path).unwrap();
+    for (time, scramble) in SOLVES {
+        let mut t = Time::from(time);
+        t.scramble = Some(scramble.to_string());
+        t.gen_stats(&app.times.times);
+        app.times.insert(t);
+    }
+    app.scramble = SCRAMBLE.to_string();
+    app.virtual_cube.reset(SCRAMBLE);
+    app.trainer.set = 0;
+    app.trainer.case = 0;
+    app.trainer.setup = parse_alg("R U R' U R U2 R'").unwrap();
+    app.trainer.state = CubeState::from_moves(&app.trainer.setup);
+    app
+}
+
+/// Feed space separated key chords, e.g. `"esc l enter"`, to the input handler
+fn press(app: &mut App, keys: &str) {
+    for chord in keys.split_whitespace() {
+        let chord = chord.parse::<KeyChord>().unwrap();
+        let quit = ui::handle_input(app, KeyEvent::new(chord.code, chord.modifiers)).unwrap();
+        assert!(!quit, "{chord} quit the app");
+    }
+}
+
+/// The frame as text, one line per row without trailing blanks
+fn render(app: &mut App, width: u16, height: u16) -> String {
+    let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+    // Twice, since panels are laid out on the first frame and hit tested after
+    terminal.draw(|f| ui::draw(f, app)).unwrap();
+    let buffer = terminal.draw(|f| ui::draw(f, app)).unwrap().buffer.clone();
+    (0..height)
+        .map(|y| {
+            let line = (0..width)
+                .map(|x| buffer.get(x, y).symbol.as_str())
+                .collect::<String>();
+            line.trim_end().to_string() + "\n"
+        })
+        .collect()
+}
+
+/// Compare a frame with its fixture, written instead when `UPDATE_SNAPSHOTS`
+/// is set. A missing fixture fails, so a renamed snapshot is not lost.
+fn assert_snapshot(name: &str, frame: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{name}.txt"));
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, frame).unwrap();
+        return;
+    }
+    let Ok(expected) = fs::read_to_string(&path) else {
+        panic!(
+            "{} is missing, run with UPDATE_SNAPSHOTS=1 to write it:\n{frame}",
+            path.display()
+        );
+    };
+    if expected == frame {
+        return;
+    }
+    let (expected, actual) = (
+        expected.lines().collect::<Vec<&str>>(),
+        frame.lines().collect::<Vec<&str>>(),
+    );
+    let mut diff = (0..expected.len().max(actual.len()))
+        .filter(|i| expected.get(*i) != actual.get(*i))
+        .map(|i| {
+            let (e, a) = (expected.get(i), actual.get(i));
+            format!(
+                "{:>3} - {}\n    + {}",
+                i + 1,
+                e.unwrap_or(&"<no line>"),
+                a.unwrap_or(&"<no line>")
+            )
+        })
+        .collect::<Vec<String>>();
+    if expected.len() != actual.len() {
+        diff.insert(
+            0,
+            format!(
+                "{} lines expected, {} rendered",
+                expected.len(),
+                actual.len()
+            ),
+        );
+    }
+    panic!(
+        "{name} differs from {}:\n{}",
+        path.display(),
+        diff.join("\n")
+    );
+}
+
+#[test]
+fn test_default_screen() {
+    let path = times_path("default");
+    let mut app = app(&path);
+    assert_snapshot("default", &render(&mut app, 140, 40));
+    assert_snapshot("default_narrow", &render(&mut app, 80, 50));
+    // The chat panel shows the configured message until the room has one
+    app.config.frontend.default_message = "quiet in here".to_string();
+    assert!(render(&mut app, 140, 40).contains("quiet in here"));
+}
+
+#[test]
+fn test_help_screen() {
+    let path = times_path("help");
+    let mut app = app(&path);
+    press(&mut app, "?");
+    assert_snapshot("help", &render(&mut app, 140, 50));
+}
+
+#[test]
+fn test_tools() {
+    let path = times_path("tools");
+    let mut app = app(&path);
+    // The tools block starts active, and up moves to the next tool
+    for tool in ["gnostr", "direct", "relay", "commit", "virtual", "trainer"] {
+        assert_eq!(app.active_tool.to_string().to_lowercase(), tool);
+        assert_snapshot(&format!("tool_{tool}"), &render(&mut app, 140, 40));
+        press(&mut app, "k");
+    }
+    assert!(app.active_tool == Tool::Gnostr);
+}
+
+#[test]
+fn test_popups() {
+    let path = times_path("popups");
+    let mut app = app(&path);
+    // Navigation goes by where panels were drawn
+    render(&mut app, 120, 40);
+    // Memo of the newest solve
+    press(&mut app, "esc b");
+    assert!(matches!(app.route.screen, Screen::Memo));
+    assert_snapshot("memo", &render(&mut app, 120, 40));
+
+    // Replay of the newest solve from the times block
+    press(&mut app, "esc j j enter j r");
+    assert!(matches!(app.route.screen, Screen::Replay));
+    assert_snapshot("replay", &render(&mut app, 120, 40));
+    // An edited reconstruction is saved right away
+    app.config.storage.autosave = true;
+    press(&mut app, "e R space U enter");
+    assert!(fs::read_to_string(&path).unwrap().contains("recon=R U"));
+    // Step times in the editor are the splits the breakdown goes by
+    press(&mut app, "e");
+    let replay = app.replay.as_mut().unwrap();
+    replay.input = tui_input::Input::new("R // a @ 4.5; U // b @ 12.4".to_string());
+    press(&mut app, "enter");
+    let solve = &app.times.times[app.replay.as_ref().unwrap().solve];
+    assert_eq!(solve.splits, vec![4.5, 12.4]);
+    let frame = render(&mut app, 120, 40);
+    assert!(frame.contains("4.50") && frame.contains("7.90"));
+
+    press(&mut app, "esc a");
+    assert!(matches!(app.route.screen, Screen::Library));
+    assert_snapshot("library", &render(&mut app, 120, 40));
+}
+
+#[test]
+fn test_navigation() {
+    let path = times_path("navigation");
+    let mut app = app(&path);
+    render(&mut app, 140, 40);
+    let mut walk = |keys: &str, selected: ActiveBlock, active: ActiveBlock| {
+        press(&mut app, keys);
+        assert_eq!(app.route.selected_block, selected, "after {keys}");
+        assert_eq!(app.route.active_block, active, "after {keys}");
+    };
+    walk("esc", ActiveBlock::Tools, ActiveBlock::Home);
+    walk("j", ActiveBlock::Timer, ActiveBlock::Home);
+    walk("j", ActiveBlock::Times, ActiveBlock::Home);
+    walk("j", ActiveBlock::Times, ActiveBlock::Home);
+    walk("l", ActiveBlock::Main, ActiveBlock::Home);
+    walk("k k", ActiveBlock::Stats, ActiveBlock::Home);
+    walk("h", ActiveBlock::Tools, ActiveBlock::Home);
+    walk("enter", ActiveBlock::Tools, ActiveBlock::Tools);
+    // Leaving an active list moves on from it
+    walk("l", ActiveBlock::Stats, ActiveBlock::Home);
+
+    // The narrow layout is one column, so left and right go nowhere
+    render(&mut app, 80, 50);
+    press(&mut app, "h");
+    assert_eq!(app.route.selected_block, ActiveBlock::Stats);
+    press(&mut app, "k");
+    assert_eq!(app.route.selected_block, ActiveBlock::Scramble);
+}
+
+#[test]
+fn test_focus_mode() {
+    let path = times_path("focus");
+    let mut app = app(&path);
+    // The running time changes between frames, so hide it
+    app.config.timer.hide_running = true;
+    render(&mut app, 120, 40);
+    press(&mut app, "esc space");
+    assert!(app.timer.on);
+    assert_snapshot("focus", &render(&mut app, 120, 40));
+    assert!(app.hit_rects.is_empty());
+
+    // Stopping brings the dashboard back with the new solve highlighted
+    press(&mut app, "space");
+    assert!(!app.timer.on && app.new_result);
+    assert_eq!(app.times.times.len(), SOLVES.len() + 1);
+    render(&mut app, 120, 40);
+    assert!(!app.hit_rects.is_empty());
+    press(&mut app, "space");
+    assert!(!app.new_result);
+
+    // A virtual solve keeps its cube on screen
+    let mut app = crate::app(&times_path("focus_virtual"));
+    app.config.timer.hide_running = true;
+    app.select_tool(4);
+    assert!(app.active_tool == Tool::Virtual);
+    app.virtual_turn(parse_alg("R").unwrap()[0]);
+    assert!(app.timer.on);
+    assert_snapshot("focus_virtual", &render(&mut app, 120, 40));
+}
+
+#[test]
+fn test_solve_details() {
+    let path = times_path("solve");
+    let mut app = app(&path);
+    render(&mut app, 120, 40);
+    press(&mut app, "esc j j enter j enter");
+    assert!(matches!(app.route.screen, Screen::Solve));
+    assert_snapshot("solve", &render(&mut app, 120, 40));
+
+    press(
+        &mut app,
+        "c g o o d space c r o s s enter p ctrl-u + 2 enter",
+    );
+    let newest = &app.times.times[SOLVES.len() - 1];
+    assert_eq!(newest.comment, "good cross");
+    assert_eq!(newest.penalty, Penalty::PlusTwo);
+    assert_eq!(newest.label(2), "14.40+");
+    assert_snapshot("solve_edited", &render(&mut app, 120, 40));
+
+    // A bad penalty keeps the edit line open
+    press(&mut app, "p ctrl-u x enter");
+    assert!(app.solve_view.as_ref().unwrap().edit.is_some());
+    press(&mut app, "esc");
+
+    // Delete takes two presses in a row
+    press(&mut app, "d j");
+    assert!(matches!(app.route.screen, Screen::Solve));
+    assert_eq!(app.times.times.len(), SOLVES.len());
+    press(&mut app, "d d");
+    assert!(matches!(app.route.screen, Screen::Default));
+    assert_eq!(app.times.times.len(), SOLVES.len() - 1);
+    // The best ao5 was the one ending at the deleted solve
+    assert!((app.times.pbao5.unwrap() - 13.5267).abs() < 1e-3);
+}
+
+/// The keys typing `text`
+fn typed(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            ' ' => "space".to_string(),
+            c => c.to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[test]
+fn test_replay_splits() {
+    let path = times_path("replay");
+    let mut app = app(&path);
+    render(&mut app, 120, 40);
+    press(&mut app, "esc j j enter j r e");
+    press(&mut app, &typed("R U // a @ 1.5; U2 // b @ 3"));
+    press(&mut app, "enter");
+    let solve = app.replay.as_ref().unwrap().solve;
+    assert_eq!(app.times.times[solve].splits, vec![1.5, 3.0]);
+
+    // The editor shows the splits, so taking them out clears them
+    press(&mut app, "e");
+    assert_eq!(
+        app.replay.as_ref().unwrap().input.value(),
+        "R U // a @ 1.5; U2 // b @ 3"
+    );
+    press(&mut app, "ctrl-u");
+    press(&mut app, &typed("R U // a; U2 // b"));
+    press(&mut app, "enter");
+    assert!(app.times.times[solve].splits.is_empty());
+
+    // A label with `@` would not read back the same
+    press(&mut app, "e ctrl-u");
+    press(&mut app, &typed("R U // x-cross @ home"));
+    press(&mut app, "enter");
+    let replay = app.replay.as_ref().unwrap();
+    assert!(replay.editing && replay.error.is_some());
+}
+
+#[test]
+fn test_command_line() {
+    let path = times_path("command");
+    let mut app = app(&path);
+    render(&mut app, 120, 40);
+    press(&mut app, "esc : g o tab 2");
+    assert_eq!(app.command_line.input.value(), "goto 2");
+    assert_snapshot("command_line", &render(&mut app, 120, 40));
+    press(&mut app, "enter");
+    assert!(!app.command_line.open);
+    assert_eq!(app.route.active_block, ActiveBlock::Times);
+    assert_eq!(app.times_state.selected(), Some(SOLVES.len() - 2));
+
+    // Sessions keep their own times file
+    press(&mut app, "esc : s e s tab n e w space o h enter");
+    assert_eq!(app.session, "oh");
+    assert_eq!(app.chat.room().name, "cube-tui/oh");
+    assert!(app.times.times.is_empty());
+    assert!(app.path.ends_with("sessions/oh"));
+    press(
+        &mut app,
+        ": s e s s i o n space s w i t c h space d e f tab enter",
+    );
+    assert_eq!(app.times.times.len(), SOLVES.len());
+
+    // Errors are shown until the next key, and lines are kept in history
+    press(&mut app, ": g o t o space 9 9 enter");
+    assert!(matches!(app.command_line.message, Some(Err(_))));
+    press(&mut app, ": up");
+    assert_eq!(app.command_line.input.value(), "goto 99");
+    press(&mut app, "up down down esc");
+    assert!(!app.command_line.open);
+}
+
+const PEER: &str = "12D3KooWPeerOfTheChatTestWithALongId5xQ";
+
+/// An app online as a local peer, with a message of each kind from `PEER`
+/// called ana, and the channel its chat commands go to
+fn chat_app(path: &Path) -> (App, tokio::sync::mpsc::Receiver<ChatCommand>) {
+    let mut app = app(path);
+    let (tx, rx) = tokio::sync::mpsc::channel(8);
+    app.chat.sender = Some(tx);
+    for event in [
+        NetworkEvent::Started("12D3KooWLocalPeerOfTheChatTest7hZ".to_string()),
+        NetworkEvent::Listening("/ip4/127.0.0.1/tcp/4001".to_string()),
+        NetworkEvent::PeerJoined(PEER.to_string()),
+        NetworkEvent::Message {
+            id: "a1".to_string(),
+            topic: "test-net".to_string(),
+            source: PEER.to_string(),
+            envelope: Envelope::new(
+                "ana",
+                Body::Solve {
+                    time: 9.87,
+                    penalty: "+2".to_string(),
+                    scramble: None,
+                },
+            ),
+        },
+        NetworkEvent::Message {
+            id: "b2".to_string(),
+            topic: "test-net".to_string(),
+            source: PEER.to_string(),
+            envelope: Envelope::new(
+                "ana",
+                Body::Chat {
+                    text: "sub 10 today".to_string(),
+                },
+            ),
+        },
+    ] {
+        app.on_network(event);
+    }
+    // Peers go by nickname once given one, by the end of their id here
+    press(&mut app, ": n i c k space 5 x Q space a n a enter");
+    render(&mut app, 120, 40);
+    (app, rx)
+}
+
+/// A chat message from `PEER` in the first room
+fn chat_message(id: &str, text: &str) -> NetworkEvent {
+    NetworkEvent::Message {
+        id: id.to_string(),
+        topic: "test-net".to_string(),
+        source: PEER.to_string(),
+        envelope: Envelope::new(
+            "",
+            Body::Chat {
+                text: text.to_string(),
+            },
+        ),
+    }
+}
+
+/// Chat texts of the first room, oldest first
+fn chat_texts(app: &App) -> Vec<String> {
+    app.chat.rooms[0]
+        .log
+        .iter()
+        .filter_map(|e| match e {
+            NetworkEvent::Message { envelope, .. } => match &envelope.body {
+                Body::Chat { text } => Some(text.clone()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_chat_nicknames() {
+    let (mut app, _rx) = chat_app(&times_path("chat_nicknames"));
+    assert_eq!(app.address_book.name(PEER), "ana");
+    assert_eq!(app.address_book.peer("ana").as_deref(), Some(PEER));
+    press(&mut app, "esc : n i c k space 5 x Q enter");
+    assert_eq!(app.address_book.peer("ana"), None);
+    assert_ne!(app.address_book.name(PEER), "ana");
+}
+
+#[test]
+fn test_chat_send() {
+    let path = times_path("chat_send");
+    let (mut app, mut rx) = chat_app(&path);
+    // Entering the panel sends keys to the input, even navigation keys
+    press(&mut app, "esc j j l enter n i c e space j o b");
+    assert!(app.chat_active());
+    assert_eq!(app.chat.input.value(), "nice job");
+    press(&mut app, "enter");
+    let sent = match rx.try_recv() {
+        Ok(ChatCommand::Publish { room, envelope }) if room == "test-net" => envelope,
+        other => panic!("expected a publish, got {other:?}"),
+    };
+    assert_eq!(
+        sent.body,
+        Body::Chat {
+            text: "nice job".to_string()
+        }
+    );
+    assert_eq!(app.chat.input.value(), "");
+    app.on_network(NetworkEvent::Published {
+        topic: "test-net".to_string(),
+        id: wire::message_id(&sent.encode()),
+        result: Ok(()),
+    });
+    assert_snapshot("chat", &render(&mut app, 120, 40));
+
+    // A message that did not go out leaves the log and the history
+    press(&mut app, "l o s t enter");
+    let lost = match rx.try_recv() {
+        Ok(ChatCommand::Publish { envelope, .. }) => envelope,
+        other => panic!("expected a publish, got {other:?}"),
+    };
+    let logged = app.chat.room().log.len();
+    app.on_network(NetworkEvent::Published {
+        topic: "test-net".to_string(),
+        id: wire::message_id(&lost.encode()),
+        result: Err("InsufficientPeers".to_string()),
+    });
+    assert_eq!(app.chat.room().log.len(), logged);
+    assert!(matches!(
+        app.chat.room().log.last(),
+        Some(NetworkEvent::Status(text)) if text == "not sent: InsufficientPeers"
+    ));
+    let app = crate::app(&path);
+    assert_eq!(chat_texts(&app), ["sub 10 today", "nice job"]);
+}
+
+#[test]
+fn test_chat_rooms() {
+    let (mut app, mut rx) = chat_app(&times_path("chat_rooms"));
+    // Messages in other rooms count as unread until shown
+    press(
+        &mut app,
+        "esc j j l enter esc : r o o m space j o i n space 3 x 3 enter",
+    );
+    assert!(matches!(rx.try_recv(), Ok(ChatCommand::Join(room)) if room == "3x3"));
+    assert_eq!(app.chat.room().name, "3x3");
+    app.on_network(chat_message("c3", "anyone racing?"));
+    assert_eq!(app.chat.rooms[0].unread, 1);
+    press(&mut app, ": r o o m space l i s t enter");
+    assert_eq!(
+        app.command_line.message,
+        Some(Ok("#test-net (1) *#3x3".to_string()))
+    );
+    assert_snapshot("chat_rooms", &render(&mut app, 120, 40));
+    press(&mut app, "enter tab");
+    assert_eq!(app.chat.current, 0);
+    assert_eq!(app.chat.rooms[0].unread, 0);
+}
+
+#[test]
+fn test_chat_peers() {
+    let (mut app, _rx) = chat_app(&times_path("chat_peers"));
+    app.on_network(NetworkEvent::PeerLeft(PEER.to_string()));
+    assert!(app.chat.peers.is_empty());
+
+    // What others pass to `--peer` to reach us
+    press(&mut app, "esc : a d d r s enter");
+    let addr = "/ip4/127.0.0.1/tcp/4001/p2p/12D3KooWLocalPeerOfTheChatTest7hZ";
+    assert!(matches!(
+        app.chat.room().log.last(),
+        Some(NetworkEvent::Status(text)) if text == addr
+    ));
+}
+
+#[test]
+fn test_direct() {
+    let path = times_path("direct");
+    let (mut app, mut rx) = chat_app(&path);
+    // `:dm` opens the conversation in the Direct panel, ready to type
+    press(
+        &mut app,
+        "esc j j l enter esc : d m space a n a space g g enter",
+    );
+    let id = match rx.try_recv() {
+        Ok(ChatCommand::Direct(message)) if message.peer == PEER => message.id,
+        other => panic!("expected a direct message, got {other:?}"),
+    };
+    assert!(app.direct_active());
+    app.on_network(NetworkEvent::Delivery {
+        peer: PEER.to_string(),
+        id,
+        state: Delivery::Acknowledged,
+    });
+    // Only the conversation shown counts as read
+    let other = "12D3KooWAnotherPeerOfTheChatTestWithId9kL";
+    for (peer, text) in [(other, "hi there"), (PEER, "gg wp")] {
+        app.on_network(NetworkEvent::Direct {
+            peer: peer.to_string(),
+            id: 1,
+            text: text.to_string(),
+        });
+    }
+    assert_eq!(app.direct.conversations[0].unread, 0);
+    assert_eq!(app.direct.conversations[1].unread, 1);
+    press(&mut app, "s o o n enter");
+    assert!(matches!(rx.try_recv(), Ok(ChatCommand::Direct(m)) if m.text == "soon"));
+    assert_snapshot("direct", &render(&mut app, 120, 40));
+    press(&mut app, "tab");
+    assert_eq!(app.direct.conversations[1].unread, 0);
+
+    // A restart reads the conversations back from the history
+    let app = crate::app(&path);
+    let ana = &app.direct.conversations[app.direct.find(PEER).unwrap()];
+    assert_eq!(ana.log.len(), 3);
+    assert!(ana.log[0].mine && !ana.log[1].mine);
+}
+
+#[test]
+fn test_chat_history() {
+    let path = times_path("chat_history");
+    let (mut app, _rx) = chat_app(&path);
+    app.on_network(chat_message("c3", "nice job"));
+    app.on_network(chat_message("d4", "anyone racing?"));
+
+    // A restart reads the rooms back from the history
+    let mut app = crate::app(&path);
+    assert_eq!(
+        chat_texts(&app),
+        ["sub 10 today", "nice job", "anyone racing?"]
+    );
+    // Gossip bringing a message again does not log it twice
+    let (id, envelope) = match &app.chat.rooms[0].log[0] {
+        NetworkEvent::Message { id, envelope, .. } => (id.clone(), envelope.clone()),
+        other => panic!("expected a message, got {other:?}"),
+    };
+    app.on_network(NetworkEvent::Message {
+        id,
+        topic: "test-net".to_string(),
+        source: PEER.to_string(),
+        envelope,
+    });
+    assert_eq!(app.chat.rooms[0].log.len(), 4);
+    assert_eq!(app.chat.rooms[0].unread, 0);
+
+    // Searching again finds the match before the last one, whatever the case
+    press(&mut app, ": s e a r c h space O enter");
+    assert!(matches!(&app.command_line.message, Some(Ok(m)) if m.starts_with("found \"O\"")));
+    assert_eq!(app.chat.room().scroll, 0);
+    for scroll in [1, 2] {
+        press(&mut app, "enter : s e a r c h space O enter");
+        assert!(app.command_line.message.as_ref().unwrap().is_ok());
+        assert_eq!(app.chat.room().scroll, scroll);
+    }
+    press(&mut app, "enter : s e a r c h space O enter");
+    assert_eq!(
+        app.command_line.message,
+        Some(Err("nothing older has \"O\"".to_string()))
+    );
+}
+
+#[test]
+fn test_block() {
+    let path = times_path("block");
+    let (mut app, _rx) = chat_app(&path);
+    press(&mut app, "esc : b l o c k space a n a enter");
+    assert_eq!(
+        app.command_line.message,
+        Some(Ok("blocked ana".to_string()))
+    );
+
+    // Blocked peers stay blocked after a restart
+    let mut app = crate::app(&path);
+    assert!(app.blocked.contains(PEER));
+    app.on_network(NetworkEvent::Direct {
+        peer: PEER.to_string(),
+        id: 2,
+        text: "let me in".to_string(),
+    });
+    assert_eq!(app.direct.find(PEER), None);
+    press(&mut app, ": u n b l o c k space a n a enter");
+    assert!(app.blocked.peers.is_empty());
+}